@@ -0,0 +1,46 @@
+use std::error::Error;
+
+use axum::{http::{header, StatusCode}, response::IntoResponse, routing::get, Router};
+use tokio_util::sync::CancellationToken;
+
+use crate::{config, services::{exporter, metrics}};
+
+/// Serves the admin endpoints (Prometheus metrics) on their own port, away from the WebTransport clients
+pub async fn start_admin(shutdown: CancellationToken) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(address) = &config::get().admin_address else {
+        tracing::info!("Admin server disabled");
+        return Ok(());
+    };
+    
+    metrics::init();
+    
+    let app = Router::new()
+        .route("/metrics", get(get_metrics))
+        .route("/metrics/containers", get(get_container_metrics));
+    
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    tracing::info!("Admin server listening on {}", address);
+    
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
+    
+    Ok(())
+}
+
+async fn get_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(&metrics::REGISTRY)
+    )
+}
+
+async fn get_container_metrics() -> impl IntoResponse {
+    match exporter::render_container_metrics().await {
+        Ok(body) => (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body),
+        Err(e) => {
+            tracing::error!("Failed to collect container metrics: {:?}", e);
+            (StatusCode::SERVICE_UNAVAILABLE, [(header::CONTENT_TYPE, "text/plain")], format!("Failed to collect container metrics: {}", e))
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::{error::Error, sync::Arc, time::Duration};
+
+use bollard::errors::Error as DockerError;
+use tokio::sync::{broadcast::error::RecvError, Mutex};
+use tokio_util::sync::CancellationToken;
+use wtransport::{tls::{Sha256Digest, Sha256DigestFmt}, ClientConfig, Endpoint};
+
+use crate::config::{self, AgentConfig};
+use crate::events::{agent::{AgentCall, AgentEvent, AgentRegisterData, AgentResponseData, AgentResult}, docker::{ContainerFilters, DockerEvent}, Event};
+use crate::serializers::format::{Format, FrameReader};
+use crate::services::{bus::EventBus, docker, hosts};
+use crate::webtransport::session::Session;
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Runs as an agent: dials the configured hub, registers the local host and proxies
+/// the Docker calls of the hub, reconnecting until shutdown
+pub async fn start_agent(shutdown: CancellationToken) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(agent) = &config::get().agent else {
+        return Err("Agent mode requires the `agent` configuration".into());
+    };
+    
+    let endpoint = Endpoint::client(client_config(agent)?)?;
+    
+    let bus = EventBus::new();
+    hosts::open_tunnels(shutdown.clone());
+    docker::listen_all_docker_events(bus.clone());
+    
+    loop {
+        tokio::select! {
+            result = run(&endpoint, agent, &bus) => match result {
+                Ok(_) => tracing::warn!(hub = agent.hub, "Hub closed the connection, reconnecting in {:?}", RETRY_INTERVAL),
+                Err(e) => tracing::error!(hub = agent.hub, "Agent connection failed, retrying in {:?}: {:?}", RETRY_INTERVAL, e)
+            },
+            _ = shutdown.cancelled() => break
+        }
+        
+        tokio::select! {
+            _ = tokio::time::sleep(RETRY_INTERVAL) => {},
+            _ = shutdown.cancelled() => break
+        }
+    }
+    
+    Ok(())
+}
+
+fn client_config(agent: &AgentConfig) -> Result<ClientConfig, Box<dyn Error + Send + Sync>> {
+    let builder = ClientConfig::builder().with_bind_default();
+    
+    let config = match &agent.certificate_hash {
+        Some(hash) => builder.with_server_certificate_hashes([Sha256Digest::from_str_fmt(hash, Sha256DigestFmt::DottedHex)?]),
+        None => builder.with_native_certs()
+    };
+    
+    Ok(config.build())
+}
+
+async fn run(endpoint: &Endpoint<wtransport::endpoint::endpoint_side::Client>, agent: &AgentConfig, bus: &EventBus) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let connection = endpoint.connect(&agent.hub).await?;
+    let (send_stream, mut recv_stream) = connection.open_bi().await?.await?;
+    let session = Arc::new(Mutex::new(Session::new(connection, send_stream)));
+    
+    let host_id = hosts::default_name();
+    session.lock().await.write_event(&Event::Agent(AgentEvent::AgentRegister {
+        data: AgentRegisterData {
+            host_id: host_id.clone(),
+            token: agent.token.clone(),
+            accepted: None
+        }
+    })).await?;
+    
+    let mut rx = bus.subscribe();
+    let session_clone = session.clone();
+    let forwarder = tokio::spawn(async move {
+        loop {
+            let event = match rx.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Agent lagged, {} Docker events dropped", skipped);
+                    continue;
+                },
+                Err(RecvError::Closed) => break
+            };
+            
+            // The hub derives the typed events from the raw ones, under the host ID of the agent
+            if let Event::Docker(DockerEvent::DockerEventMessage { .. } | DockerEvent::DockerStatus { .. }) = event.as_ref()
+                && let Err(e) = session_clone.lock().await.write_event(&event).await {
+                tracing::error!("Failed to forward Docker event to the hub: {:?}", e);
+                break;
+            }
+        }
+    });
+    
+    let mut buffer = [0; 4096];
+    let mut reader = FrameReader::new(Format::default());
+    let result = loop {
+        let n = match recv_stream.read(&mut buffer).await {
+            Ok(Some(0)) | Ok(None) => break Ok(()),
+            Ok(Some(n)) => n,
+            Err(e) => break Err(e.into())
+        };
+        
+        reader.push(&buffer[..n]);
+        while let Some(result) = reader.next_event() {
+            match result {
+                Ok(Event::Agent(AgentEvent::AgentRegister { data })) => match data.accepted {
+                    Some(true) => tracing::info!(hub = agent.hub, host = host_id, "Registered with the hub"),
+                    _ => tracing::error!(hub = agent.hub, host = host_id, "Hub refused the registration")
+                },
+                Ok(Event::Agent(AgentEvent::AgentRequest { data })) => {
+                    let session = session.clone();
+                    tokio::spawn(async move {
+                        let response = Event::Agent(AgentEvent::AgentResponse {
+                            data: AgentResponseData {
+                                request_id: data.request_id,
+                                result: execute(data.call).await
+                            }
+                        });
+                        if let Err(e) = session.lock().await.write_event(&response).await {
+                            tracing::error!("Failed to answer the hub: {:?}", e);
+                        }
+                    });
+                },
+                Ok(event) => tracing::trace!(event = event.name(), "Ignoring event from the hub"),
+                Err(e) => tracing::error!("Failed to parse event from the hub: {:?}", e)
+            }
+        }
+    };
+    
+    forwarder.abort();
+    result
+}
+
+/// Runs a proxied call against the local Docker host
+async fn execute(call: AgentCall) -> AgentResult {
+    tracing::debug!("Executing agent call {:?}", call);
+    
+    let result = match call {
+        AgentCall::Ping => match docker::ping(None).await {
+            1 => Ok(AgentResult::Done),
+            _ => Err(DockerError::DockerStreamError { error: "Docker is unreachable".to_string() })
+        },
+        AgentCall::ListContainers => docker::get_containers(None, &ContainerFilters::default()).await.map(AgentResult::Containers),
+        AgentCall::InspectContainer { id } => docker::get_container(None, &id).await.map(|container| AgentResult::Container(Box::new(container))),
+        AgentCall::StartContainer { id } => docker::start_container(None, &id).await.map(|_| AgentResult::Done),
+        AgentCall::StopContainer { id } => docker::stop_container(None, &id).await.map(|_| AgentResult::Done),
+        AgentCall::RestartContainer { id } => docker::restart_container(None, &id).await.map(|_| AgentResult::Done),
+        AgentCall::PauseContainer { id } => docker::pause_container(None, &id).await.map(|_| AgentResult::Done),
+        AgentCall::RemoveContainer { id } => docker::remove_container(None, &id).await.map(|_| AgentResult::Done),
+        AgentCall::ContainerStats { id, one_shot } => docker::get_container_stats(None, &id, one_shot).await.map(|stats| AgentResult::Stats(Box::new(stats))),
+        AgentCall::ExecContainer { id, command } => docker::exec_container(None, &id, command).await.map(AgentResult::Exec)
+    };
+    
+    match result {
+        Ok(result) => result,
+        Err(DockerError::DockerResponseServerError { status_code, message }) => AgentResult::Error {
+            status_code: Some(status_code),
+            message
+        },
+        Err(error) => AgentResult::Error {
+            status_code: None,
+            message: error.to_string()
+        }
+    }
+}
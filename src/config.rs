@@ -0,0 +1,444 @@
+use std::{collections::HashMap, error::Error, path::PathBuf, sync::OnceLock, time::Duration};
+
+use serde::Deserialize;
+
+use crate::events::system::Role;
+
+const CONFIG_ENV: &str = "ADMIN_API_CONFIG";
+const DEFAULT_CONFIG_PATH: &str = "config.json";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Server configuration, read from the JSON file pointed by `ADMIN_API_CONFIG` (`config.json` by default).
+/// Every field is optional, a missing file means the defaults.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Config {
+    pub port: u16,
+    
+    /// Seconds to wait for in-flight Docker actions once a shutdown is requested
+    pub shutdown_timeout: u64,
+    
+    /// Seconds clients are told to wait before reconnecting after a shutdown
+    pub reconnect_after: u64,
+    
+    /// Address of the admin HTTP server (metrics), `null` to disable it
+    pub admin_address: Option<String>,
+    
+    pub logging: LoggingConfig,
+    
+    /// Directory of the embedded database holding schedules, audit logs and history
+    pub data_dir: String,
+    
+    /// Docker hosts, the local socket is used when empty
+    pub hosts: Vec<HostConfig>,
+    
+    /// Name of the local socket host when `hosts` is empty, also the host ID an agent registers with
+    pub host_name: String,
+    
+    /// Token agents must present to register, agents are refused when unset
+    pub agent_token: Option<String>,
+    
+    /// Clients authenticating with a token in their `Hello`
+    pub clients: Vec<ClientConfig>,
+    
+    /// Role of the streams that present no token, `operator` by default while tokenless actions are deprecated
+    pub anonymous_role: Role,
+    
+    /// Hub to connect to when started with the `agent` argument
+    pub agent: Option<AgentConfig>,
+    
+    pub alerts: AlertsConfig,
+    
+    /// Channels alerts and container actions are sent to
+    pub notifications: Vec<ChannelConfig>,
+    
+    pub metrics_history: MetricsHistoryConfig,
+    
+    pub event_history: EventHistoryConfig,
+    
+    pub tls: TlsConfig
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClientConfig {
+    /// Identity reported to the client and written to the logs
+    pub name: String,
+    
+    pub token: String,
+    
+    pub role: Role
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentConfig {
+    /// WebTransport URL of the hub, e.g. `https://hub.example.com:4433`
+    pub hub: String,
+    
+    pub token: Option<String>,
+    
+    /// Dotted hex SHA-256 of a self-signed hub certificate, the system roots are used when unset
+    pub certificate_hash: Option<String>
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HostConfig {
+    pub name: String,
+    
+    /// `unix:///path.sock`, `tcp://host:2375`, `https://host:2376` or `ssh://user@host[:port]`
+    pub url: String,
+    
+    /// Client certificates for TLS hosts
+    pub tls: Option<HostTlsConfig>,
+    
+    /// Docker socket on the remote side of an SSH tunnel
+    #[serde(default = "default_remote_socket")]
+    pub remote_socket: String,
+    
+    /// Request timeout in seconds
+    #[serde(default = "default_host_timeout")]
+    pub timeout: u64
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HostTlsConfig {
+    pub ca: String,
+    
+    pub certificate: String,
+    
+    pub key: String
+}
+
+fn default_remote_socket() -> String {
+    "/var/run/docker.sock".to_string()
+}
+
+fn default_host_timeout() -> u64 {
+    120
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LoggingConfig {
+    /// Filter directives, as in `RUST_LOG`
+    pub level: String,
+    
+    pub format: LogFormat,
+    
+    /// OTLP/gRPC collector spans are exported to, requires the `otlp` feature
+    pub otlp_endpoint: Option<String>
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::Text,
+            otlp_endpoint: None
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AlertsConfig {
+    /// Seconds between two checks of the daemons and of the memory usage
+    pub interval: u64,
+    
+    pub rules: Vec<AlertRule>
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AlertRule {
+    pub name: String,
+    
+    #[serde(flatten)]
+    pub condition: AlertCondition
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "condition", rename_all = "camelCase")]
+pub enum AlertCondition {
+    /// A container exits with a non-zero code, other than after a stop or kill request
+    ExitCode,
+    /// A container starts more than `count` times in `window` seconds
+    Restarts { count: usize, window: u64 },
+    /// A container uses more than `percent` of its memory limit for `duration` seconds
+    Memory { percent: f64, duration: u64 },
+    /// A container health check reports unhealthy
+    Unhealthy,
+    /// The Docker daemon of a host cannot be reached or does not answer pings
+    DaemonUnreachable
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        let rule = |name: &str, condition| AlertRule { name: name.to_string(), condition };
+        
+        Self {
+            interval: 15,
+            rules: vec![
+                rule("exitCode", AlertCondition::ExitCode),
+                rule("restarts", AlertCondition::Restarts { count: 3, window: 600 }),
+                rule("memory", AlertCondition::Memory { percent: 90.0, duration: 300 }),
+                rule("unhealthy", AlertCondition::Unhealthy),
+                rule("daemonUnreachable", AlertCondition::DaemonUnreachable)
+            ]
+        }
+    }
+}
+
+/// Container and host metrics sampled into the store, then downsampled into coarser rollups
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MetricsHistoryConfig {
+    /// Seconds between two samples, `0` disables the history
+    pub interval: u64,
+    
+    /// Seconds raw samples are kept
+    pub retention: u64,
+    
+    pub rollups: Vec<RollupConfig>
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct RollupConfig {
+    /// Seconds averaged into one point
+    pub step: u64,
+    
+    /// Seconds the points are kept
+    pub retention: u64
+}
+
+impl Default for MetricsHistoryConfig {
+    fn default() -> Self {
+        Self {
+            interval: 10,
+            retention: 24 * 3600,
+            rollups: vec![RollupConfig { step: 300, retention: 30 * 24 * 3600 }]
+        }
+    }
+}
+
+/// Docker events recorded into the store
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EventHistoryConfig {
+    /// Seconds events are kept, `0` disables the history
+    pub retention: u64
+}
+
+impl Default for EventHistoryConfig {
+    fn default() -> Self {
+        Self {
+            retention: 30 * 24 * 3600
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelConfig {
+    pub name: String,
+    
+    #[serde(flatten)]
+    pub notifier: NotifierConfig,
+    
+    /// Notification kinds routed to the channel, `*` suffix wildcards allowed (`alert.*`)
+    #[serde(default = "default_channel_events")]
+    pub events: Vec<String>,
+    
+    /// Hosts routed to the channel, every host when empty
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    
+    /// Attempts after the first failure, with an exponential backoff from `retryDelay` seconds
+    #[serde(default = "default_channel_retries")]
+    pub retries: u32,
+    
+    #[serde(default = "default_channel_retry_delay")]
+    pub retry_delay: u64,
+    
+    /// At most `rateLimit.max` notifications per `rateLimit.per` seconds, the others are dropped
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum NotifierConfig {
+    /// JSON POST of the notification, or of `template` with its `{{placeholders}}` filled
+    #[serde(rename_all = "camelCase")]
+    Webhook {
+        url: String,
+        template: Option<serde_json::Value>,
+        #[serde(default)]
+        headers: HashMap<String, String>
+    },
+    Slack { url: String },
+    Discord { url: String },
+    #[serde(rename_all = "camelCase")]
+    Email {
+        host: String,
+        port: Option<u16>,
+        #[serde(default)]
+        security: SmtpSecurity,
+        username: Option<String>,
+        password: Option<String>,
+        from: String,
+        to: Vec<String>
+    },
+    /// Program run with the notification as JSON on stdin
+    Command {
+        command: Vec<String>,
+        /// Seconds before the program is killed
+        #[serde(default = "default_command_timeout")]
+        timeout: u64
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    None,
+    #[default]
+    Starttls,
+    Tls
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub max: usize,
+    
+    pub per: u64
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            max: 10,
+            per: 60
+        }
+    }
+}
+
+fn default_channel_events() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+fn default_channel_retries() -> u32 {
+    3
+}
+
+fn default_channel_retry_delay() -> u64 {
+    5
+}
+
+fn default_command_timeout() -> u64 {
+    30
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TlsConfig {
+    /// Generate a short-lived certificate at startup instead of loading `certificate`/`key`
+    pub self_signed: bool,
+    
+    pub certificate: String,
+    
+    pub key: String,
+    
+    pub subject_alt_names: Vec<String>,
+    
+    /// Browsers only accept `serverCertificateHashes` for certificates valid 14 days or less
+    pub validity_days: u32,
+    
+    /// Hours before expiry at which the self-signed certificate is replaced
+    pub renew_before: u64,
+    
+    /// File the SHA-256 hash of the current certificate is written to
+    pub hash_file: Option<String>,
+    
+    /// Seconds between two checks of the certificate files
+    pub reload_interval: u64
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            self_signed: false,
+            certificate: "localhost.pem".to_string(),
+            key: "localhost-key.pem".to_string(),
+            subject_alt_names: vec!["localhost".to_string(), "127.0.0.1".to_string(), "::1".to_string()],
+            validity_days: 13,
+            renew_before: 24,
+            hash_file: None,
+            reload_interval: 10
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 4433,
+            shutdown_timeout: 30,
+            reconnect_after: 5,
+            admin_address: Some("127.0.0.1:9464".to_string()),
+            logging: LoggingConfig::default(),
+            data_dir: "data".to_string(),
+            hosts: Vec::new(),
+            host_name: crate::services::hosts::DEFAULT_HOST.to_string(),
+            agent_token: None,
+            clients: Vec::new(),
+            anonymous_role: Role::Operator,
+            agent: None,
+            alerts: AlertsConfig::default(),
+            notifications: Vec::new(),
+            metrics_history: MetricsHistoryConfig::default(),
+            event_history: EventHistoryConfig::default(),
+            tls: TlsConfig::default()
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let path = std::env::var_os(CONFIG_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+        
+        match std::fs::read_to_string(&path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            // Logging is not initialized yet, the defaults are used silently
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(Box::new(error))
+        }
+    }
+    
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout)
+    }
+}
+
+pub fn init(config: Config) {
+    if CONFIG.set(config).is_err() {
+        tracing::warn!("Configuration already initialized");
+    }
+}
+
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
@@ -0,0 +1,94 @@
+use bollard::{container::Stats, secret::{ContainerInspectResponse, ContainerSummary}};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use strum::{IntoStaticStr, VariantNames};
+
+use crate::{events::{registry::Family, Event}, schema::Traced, services::backend::ExecOutput};
+
+/// Messages exchanged between an agent and its hub, on the stream the agent opened
+#[derive(Serialize, Deserialize, JsonSchema, Debug, IntoStaticStr, VariantNames)]
+#[serde(tag = "type")]
+#[allow(clippy::enum_variant_names)]
+pub enum AgentEvent {
+  AgentRegister { data: AgentRegisterData },
+  AgentRequest { data: AgentRequestData },
+  AgentResponse { data: AgentResponseData }
+}
+
+impl From<AgentEvent> for Event {
+  fn from(event: AgentEvent) -> Self {
+    Event::Agent(event)
+  }
+}
+
+impl Family for AgentEvent {
+  fn of(event: &Event) -> Option<&Self> {
+    match event {
+      Event::Agent(event) => Some(event),
+      _ => None
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct AgentRegisterData {
+  #[serde(rename = "hostId")]
+  pub host_id: String,
+  
+  pub token: Option<String>,
+  
+  /// Set by the hub in its answer
+  pub accepted: Option<bool>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct AgentRequestData {
+  #[serde(rename = "requestId")]
+  pub request_id: u64,
+  
+  pub call: AgentCall
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct AgentResponseData {
+  #[serde(rename = "requestId")]
+  pub request_id: u64,
+  
+  pub result: AgentResult
+}
+
+/// Docker call proxied to an agent, mirroring `DockerBackend`
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(tag = "call")]
+pub enum AgentCall {
+  Ping,
+  ListContainers,
+  InspectContainer { id: String },
+  StartContainer { id: String },
+  StopContainer { id: String },
+  RestartContainer { id: String },
+  PauseContainer { id: String },
+  RemoveContainer { id: String },
+  ContainerStats {
+    id: String,
+    /// Agents predating it always wait for a second sample
+    #[serde(rename = "oneShot", default)]
+    one_shot: bool
+  },
+  ExecContainer { id: String, command: Vec<String> }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(tag = "result", content = "value")]
+pub enum AgentResult {
+  Done,
+  Containers(#[schemars(with = "Vec<Traced<ContainerSummary>>")] Vec<ContainerSummary>),
+  Container(#[schemars(with = "Traced<ContainerInspectResponse>")] Box<ContainerInspectResponse>),
+  Stats(#[schemars(with = "Traced<Stats>")] Box<Stats>),
+  Exec(ExecOutput),
+  Error {
+    #[serde(rename = "statusCode")]
+    status_code: Option<u16>,
+    message: String
+  }
+}
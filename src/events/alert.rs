@@ -0,0 +1,73 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use strum::{IntoStaticStr, VariantNames};
+
+use crate::events::{registry::Family, Event};
+
+/// Alerts raised by the rule engine. `AlertList` returns the firing alerts, `AlertChanged` is
+/// broadcast whenever an alert fires or resolves.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, IntoStaticStr, VariantNames)]
+#[serde(tag = "type")]
+#[allow(clippy::enum_variant_names)]
+pub enum AlertEvent {
+  AlertList { data: AlertListData },
+  AlertChanged { data: Alert }
+}
+
+impl From<AlertEvent> for Event {
+  fn from(event: AlertEvent) -> Self {
+    Event::Alert(event)
+  }
+}
+
+impl Family for AlertEvent {
+  fn of(event: &Event) -> Option<&Self> {
+    match event {
+      Event::Alert(event) => Some(event),
+      _ => None
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct AlertListData {
+  pub alerts: Option<Vec<Alert>>,
+  
+  /// Also return the recently resolved alerts
+  #[serde(rename = "includeResolved")]
+  pub include_resolved: Option<bool>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+  Firing,
+  Resolved
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Alert {
+  /// Rule name, host and container, stable while the condition lasts
+  pub id: String,
+  
+  pub rule: String,
+  
+  pub state: AlertState,
+  
+  pub host: String,
+  
+  #[serde(rename = "containerId")]
+  pub container_id: Option<String>,
+  
+  #[serde(rename = "containerName")]
+  pub container_name: Option<String>,
+  
+  pub message: String,
+  
+  /// UNIX timestamps in seconds
+  #[serde(rename = "firedAt")]
+  pub fired_at: u64,
+  
+  #[serde(rename = "resolvedAt")]
+  pub resolved_at: Option<u64>
+}
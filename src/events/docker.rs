@@ -1,49 +1,550 @@
+use std::collections::HashMap;
+
 use bollard::secret::{ContainerInspectResponse, ContainerSummary};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use strum::{IntoStaticStr, VariantNames};
+
+use crate::{events::{registry::Family, Event}, schema::Traced};
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Every payload carries an optional `host`: the Docker host a request targets (the default one when omitted)
+/// or the host a broadcast comes from. `DockerContainerList` also accepts `"*"` to merge every host.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, IntoStaticStr, VariantNames)]
 #[serde(tag = "type")]
+#[allow(clippy::enum_variant_names)]
 pub enum DockerEvent {
   DockerStatus { data: DockerStatusData },
   DockerContainerList { data: DockerContainerListData },
   DockerContainerInspect { data: DockerContainerInspectData },
   DockerContainerStart { data: DockerContainerStartData },
   DockerContainerRestart { data: DockerContainerRestartData },
-  DockerContainerStop { data: DockerContainerStopData }
+  DockerContainerStop { data: DockerContainerStopData },
+  DockerContainerStats { data: DockerContainerStatsData },
+  DockerEventMessage { data: DockerEventRecord },
+  DockerEventHistory { data: DockerEventHistoryData },
+  DockerContainerSync { data: DockerContainerSyncData },
+  DockerContainerPatch { data: ContainerPatch },
+  DockerContainerBulk { data: DockerContainerBulkData },
+  DockerContainerBulkProgress { data: BulkProgressData }
+}
+
+impl From<DockerEvent> for Event {
+  fn from(event: DockerEvent) -> Self {
+    Event::Docker(event)
+  }
+}
+
+impl Family for DockerEvent {
+  fn of(event: &Event) -> Option<&Self> {
+    match event {
+      Event::Docker(event) => Some(event),
+      _ => None
+    }
+  }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct DockerStatusData {
-  pub status: Option<i8>
+  pub status: Option<i8>,
+  
+  pub host: Option<String>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// `containers` keep the fields picked by `projection` and `fields`, all of them when neither is given.
+/// Without `limit` every matching container is returned, otherwise `cursor` continues from the
+/// `nextCursor` of the previous page.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
 pub struct DockerContainerListData {
-  pub containers: Option<Vec<ContainerSummary>>
+  #[schemars(with = "Option<Vec<HostContainerSummary>>")]
+  pub containers: Option<Vec<Value>>,
+  
+  pub host: Option<String>,
+  
+  pub projection: Option<Projection>,
+  
+  /// Fields added to the projection, as dotted paths of the Docker shape (`Names`, `State.Health`...)
+  pub fields: Option<Vec<String>>,
+  
+  pub filters: Option<ContainerFilters>,
+  
+  /// Sort keys by priority, containers are then ordered by host and ID
+  pub sort: Option<Vec<ContainerSort>>,
+  
+  pub limit: Option<usize>,
+  
+  pub cursor: Option<usize>,
+  
+  #[serde(rename = "nextCursor")]
+  pub next_cursor: Option<usize>,
+  
+  /// Containers matching the filters, over every page
+  pub total: Option<usize>
+}
+
+/// Docker list filters: a container matches one of the values of each given filter, and every `label`
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+pub struct ContainerFilters {
+  /// `created`, `restarting`, `running`, `removing`, `paused`, `exited` or `dead`
+  #[serde(default)]
+  pub status: Vec<String>,
+  
+  /// `key` or `key=value`
+  #[serde(default)]
+  pub label: Vec<String>,
+  
+  /// Part of the container name
+  #[serde(default)]
+  pub name: Vec<String>,
+  
+  /// Image the container runs, by name (with or without tag) or ID
+  #[serde(default)]
+  pub ancestor: Vec<String>,
+  
+  /// Network name or ID
+  #[serde(default)]
+  pub network: Vec<String>,
+  
+  /// Compose project
+  #[serde(default)]
+  pub project: Vec<String>
+}
+
+pub const COMPOSE_PROJECT_LABEL: &str = "com.docker.compose.project";
+
+/// Containers carrying this label (with any value but `false`) can only be acted on by admins
+pub const PROTECTED_LABEL: &str = "admin-api.protected";
+
+impl ContainerFilters {
+  pub fn matches(&self, summary: &ContainerSummary) -> bool {
+    let labels = summary.labels.as_ref();
+    let names = summary.names.as_deref().unwrap_or_default();
+    let image = summary.image.as_deref().unwrap_or_default();
+    let networks = summary.network_settings.as_ref().and_then(|settings| settings.networks.as_ref());
+    
+    let status = self.status.is_empty() || self.status.iter().any(|status| summary.state.as_deref() == Some(status));
+    let label = self.label.iter().all(|label| match label.split_once('=') {
+      Some((key, value)) => labels.and_then(|labels| labels.get(key)).is_some_and(|actual| actual == value),
+      None => labels.is_some_and(|labels| labels.contains_key(label))
+    });
+    let name = self.name.is_empty() || self.name.iter().any(|name| names.iter().any(|actual| actual.trim_start_matches('/').contains(name.as_str())));
+    let ancestor = self.ancestor.is_empty() || self.ancestor.iter().any(|ancestor| {
+      image == ancestor
+        || image.strip_prefix(ancestor.as_str()).is_some_and(|tag| tag.starts_with(':'))
+        || summary.image_id.as_deref().is_some_and(|id| id.trim_start_matches("sha256:").starts_with(ancestor.trim_start_matches("sha256:")))
+    });
+    let network = self.network.is_empty() || self.network.iter().any(|network| networks.is_some_and(|networks| {
+      networks.iter().any(|(name, endpoint)| name == network || endpoint.network_id.as_deref() == Some(network))
+    }));
+    
+    status && label && name && ancestor && network && self.matches_project(summary)
+  }
+  
+  pub fn matches_project(&self, summary: &ContainerSummary) -> bool {
+    let project = summary.labels.as_ref().and_then(|labels| labels.get(COMPOSE_PROJECT_LABEL));
+    self.project.is_empty() || self.project.iter().any(|expected| project == Some(expected))
+  }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
+pub struct ContainerSort {
+  pub by: SortField,
+  
+  #[serde(default)]
+  pub order: SortOrder
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortField {
+  Name,
+  Created,
+  State,
+  Image,
+  Host
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+  #[default]
+  Asc,
+  Desc
+}
+
+/// Named set of container fields. `host` and `Id` are always returned.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Projection {
+  /// What a container list renders: names, image, state, status, creation date and labels
+  Summary,
+  Full,
+  /// Ports and networks
+  Network
+}
+
+/// Container of a list, tagged with the host it runs on
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct HostContainerSummary {
+  pub host: String,
+  
+  #[serde(flatten)]
+  #[schemars(with = "Traced<ContainerSummary>")]
+  pub container: ContainerSummary
+}
+
+/// Containers targeted by an action: listed by name or ID, or carrying every given label.
+/// Nothing is selected when both are empty.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+pub struct ContainerSelector {
+  /// Host to look on, `"*"` for every host
+  pub host: Option<String>,
+  
+  #[serde(default)]
+  pub containers: Vec<String>,
+  
+  #[serde(default)]
+  pub labels: HashMap<String, String>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl ContainerSelector {
+  pub fn matches(&self, summary: &ContainerSummary) -> bool {
+    let id = summary.id.as_deref().unwrap_or_default();
+    let names = summary.names.as_deref().unwrap_or_default();
+    let listed = self.containers.iter().any(|container| {
+      (container.len() >= 12 && id.starts_with(container.as_str()))
+        || names.iter().any(|name| name.trim_start_matches('/') == container.trim_start_matches('/'))
+    });
+    
+    let labels = summary.labels.as_ref();
+    let labelled = !self.labels.is_empty() && self.labels.iter()
+      .all(|(key, value)| labels.and_then(|labels| labels.get(key)) == Some(value));
+    
+    listed || labelled
+  }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct DockerContainerInspectData {
   #[serde(rename = "containerId", alias = "ID")]
   pub container_id: Option<String>,
   
-  pub container: Option<ContainerInspectResponse>
+  #[schemars(with = "Option<Traced<ContainerInspectResponse>>")]
+  pub container: Option<Value>,
+  
+  pub host: Option<String>,
+  
+  pub projection: Option<Projection>,
+  
+  pub fields: Option<Vec<String>>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct DockerContainerStartData {
   #[serde(rename = "containerId", alias = "ID")]
-  pub container_id: Option<String>
+  pub container_id: Option<String>,
+  
+  pub host: Option<String>,
+  
+  /// Describes the action in `affected` instead of running it
+  #[serde(rename = "dryRun")]
+  pub dry_run: Option<bool>,
+  
+  pub affected: Option<Vec<AffectedContainer>>,
+  
+  /// Why the action was refused
+  pub error: Option<String>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct DockerContainerRestartData {
   #[serde(rename = "containerId", alias = "ID")]
-  pub container_id: Option<String>
+  pub container_id: Option<String>,
+  
+  pub host: Option<String>,
+  
+  /// Describes the action in `affected` instead of running it
+  #[serde(rename = "dryRun")]
+  pub dry_run: Option<bool>,
+  
+  pub affected: Option<Vec<AffectedContainer>>,
+  
+  /// Why the action was refused
+  pub error: Option<String>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Stopping is destructive: without `confirm` the server answers with the `affected` containers and a
+/// `confirm` token, and stops the container once the request is sent again with that token.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct DockerContainerStopData {
   #[serde(rename = "containerId", alias = "ID")]
-  pub container_id: Option<String>
-}
\ No newline at end of file
+  pub container_id: Option<String>,
+  
+  pub host: Option<String>,
+  
+  /// Describes the action in `affected` instead of running it
+  #[serde(rename = "dryRun")]
+  pub dry_run: Option<bool>,
+  
+  /// Token of the confirmation challenge, sent by the server then echoed by the client
+  pub confirm: Option<String>,
+  
+  /// UNIX timestamp in seconds after which `confirm` is refused
+  #[serde(rename = "expiresAt")]
+  pub expires_at: Option<u64>,
+  
+  pub affected: Option<Vec<AffectedContainer>>,
+  
+  /// Why the action was refused
+  pub error: Option<String>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct DockerContainerStatsData {
+  #[serde(rename = "containerId", alias = "ID")]
+  pub container_id: Option<String>,
+  
+  pub name: Option<String>,
+  
+  #[serde(rename = "cpuPercent")]
+  pub cpu_percent: Option<f64>,
+  
+  #[serde(rename = "memoryUsage")]
+  pub memory_usage: Option<u64>,
+  
+  #[serde(rename = "memoryLimit")]
+  pub memory_limit: Option<u64>,
+  
+  #[serde(rename = "networkRx")]
+  pub network_rx: Option<u64>,
+  
+  #[serde(rename = "networkTx")]
+  pub network_tx: Option<u64>,
+  
+  pub host: Option<String>
+}
+/// Raw Docker event as received from a host, whatever its type
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct DockerEventRecord {
+  /// Position in the history, set once stored
+  pub id: Option<i64>,
+  
+  pub host: String,
+  
+  /// `container`, `image`, `network`, `volume`...
+  #[serde(rename = "type")]
+  pub typ: String,
+  
+  pub action: String,
+  
+  #[serde(rename = "actorId")]
+  pub actor_id: Option<String>,
+  
+  #[serde(default)]
+  pub attributes: HashMap<String, String>,
+  
+  /// Unix seconds
+  pub time: i64,
+  
+  #[serde(rename = "timeNano")]
+  pub time_nano: i64
+}
+
+/// Stored events, most recent first. Every filter is optional, `cursor` continues from the `nextCursor`
+/// of the previous page.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
+pub struct DockerEventHistoryData {
+  pub host: Option<String>,
+  
+  /// Container name or ID (or ID prefix of 12 characters or more)
+  pub container: Option<String>,
+  
+  pub actions: Option<Vec<String>>,
+  
+  pub types: Option<Vec<String>>,
+  
+  pub from: Option<i64>,
+  
+  pub to: Option<i64>,
+  
+  pub limit: Option<usize>,
+  
+  pub cursor: Option<i64>,
+  
+  pub events: Option<Vec<DockerEventRecord>>,
+  
+  #[serde(rename = "nextCursor")]
+  pub next_cursor: Option<i64>,
+  
+  pub error: Option<String>
+}
+
+/// Subscribes to the container state of every host. Without `since`, or when the server cannot
+/// compute the changes after it (restarted, `epoch` differs, or too old), the answer is a snapshot
+/// of every container at `version`. Otherwise it carries the `patches` after `since`. Either way,
+/// `DockerContainerPatch` events follow.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
+pub struct DockerContainerSyncData {
+  /// Last version the client applied
+  pub since: Option<u64>,
+  
+  /// Identifies the server run versions belong to
+  pub epoch: Option<u64>,
+  
+  pub version: Option<u64>,
+  
+  /// Every container, when the answer is a snapshot
+  pub containers: Option<Vec<HostContainerSummary>>,
+  
+  /// Changes after `since`, in version order
+  pub patches: Option<Vec<ContainerPatch>>
+}
+
+/// Change of one container. Patches at or below the version a client holds are to be ignored.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct ContainerPatch {
+  pub version: u64,
+  
+  pub op: PatchOp,
+  
+  pub host: String,
+  
+  pub id: String,
+  
+  /// State after the change, absent on `remove`
+  #[schemars(with = "Option<Traced<ContainerSummary>>")]
+  pub container: Option<Box<ContainerSummary>>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PatchOp {
+  Add,
+  Update,
+  Remove
+}
+
+/// Runs an action on several containers: the `containers` of `host` (names or IDs), or those matched by
+/// `selector`. A `DockerContainerBulkProgress` follows each container, then the request is answered with
+/// the `results` in target order. `stop` and `remove` are first answered with a confirmation challenge, like
+/// `DockerContainerStop`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct DockerContainerBulkData {
+  /// Echoed in the progress and the answer, assigned by the server when omitted
+  pub id: Option<String>,
+  
+  pub action: BulkAction,
+  
+  pub host: Option<String>,
+  
+  pub containers: Option<Vec<String>>,
+  
+  pub selector: Option<ContainerSelector>,
+  
+  /// Actions running at once, 4 by default
+  pub concurrency: Option<usize>,
+  
+  /// Order the actions are started in, the listed order (or host and ID for a selector) by default.
+  /// Up to `concurrency` run at once, set it to 1 for each action to complete before the next starts.
+  pub order: Option<Vec<ContainerSort>>,
+  
+  /// Describes the action in `affected` instead of running it
+  #[serde(rename = "dryRun")]
+  pub dry_run: Option<bool>,
+  
+  /// Token of the confirmation challenge of `stop` and `remove`, echoed by the client
+  pub confirm: Option<String>,
+  
+  #[serde(rename = "expiresAt")]
+  pub expires_at: Option<u64>,
+  
+  pub affected: Option<Vec<AffectedContainer>>,
+  
+  /// UNIX timestamps in seconds
+  #[serde(rename = "startedAt")]
+  pub started_at: Option<u64>,
+  
+  #[serde(rename = "finishedAt")]
+  pub finished_at: Option<u64>,
+  
+  pub succeeded: Option<usize>,
+  
+  pub failed: Option<usize>,
+  
+  pub results: Option<Vec<BulkResult>>,
+  
+  /// Set when the targets could not be resolved or the action was refused
+  pub error: Option<String>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkAction {
+  Start,
+  Stop,
+  Restart,
+  Remove,
+  Pause
+}
+
+impl BulkAction {
+  /// Actions that take a container down, run only once confirmed
+  pub fn destructive(self) -> bool {
+    matches!(self, BulkAction::Stop | BulkAction::Remove)
+  }
+}
+
+/// What an action would touch, reported by confirmation challenges and dry runs
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct AffectedContainer {
+  pub host: String,
+  
+  #[serde(rename = "containerId")]
+  pub container_id: String,
+  
+  pub name: Option<String>,
+  
+  pub state: Option<String>,
+  
+  pub protected: bool,
+  
+  /// Compose project and service of the container
+  pub project: Option<String>,
+  
+  pub service: Option<String>,
+  
+  /// Services of the same compose project that depend on this one
+  pub dependents: Vec<String>,
+  
+  /// Named volumes mounted by the container
+  pub volumes: Vec<String>
+}
+
+/// Outcome of the action on one container
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct BulkResult {
+  pub host: String,
+  
+  #[serde(rename = "containerId")]
+  pub container_id: String,
+  
+  pub name: Option<String>,
+  
+  pub success: bool,
+  
+  pub error: Option<String>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct BulkProgressData {
+  pub id: String,
+  
+  pub action: BulkAction,
+  
+  /// Containers done so far, out of `total`
+  pub done: usize,
+  
+  pub total: usize,
+  
+  pub result: BulkResult
+}
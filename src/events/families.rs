@@ -0,0 +1,13 @@
+//! The event families of the protocol, each also a variant of [`Event`](super::Event). Nothing else
+//! lives here: a new family is its module, its variant and one line below.
+
+use super::{agent::AgentEvent, alert::AlertEvent, docker::DockerEvent, metrics::MetricsEvent, registry::Registry, schedule::ScheduleEvent, system::SystemEvent};
+
+pub fn register(registry: &mut Registry) {
+  registry.register::<SystemEvent>();
+  registry.register::<DockerEvent>();
+  registry.register::<AgentEvent>();
+  registry.register::<AlertEvent>();
+  registry.register::<ScheduleEvent>();
+  registry.register::<MetricsEvent>();
+}
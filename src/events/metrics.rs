@@ -0,0 +1,60 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use strum::{IntoStaticStr, VariantNames};
+
+use crate::events::{registry::Family, Event};
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, IntoStaticStr, VariantNames)]
+#[serde(tag = "type")]
+pub enum MetricsEvent {
+  MetricsQuery { data: MetricsQueryData }
+}
+
+impl From<MetricsEvent> for Event {
+  fn from(event: MetricsEvent) -> Self {
+    Event::Metrics(event)
+  }
+}
+
+impl Family for MetricsEvent {
+  fn of(event: &Event) -> Option<&Self> {
+    match event {
+      Event::Metrics(event) => Some(event),
+      _ => None
+    }
+  }
+}
+
+/// Time series of a container, or of the server host when `container` is omitted.
+/// The answer carries the effective `from`, `to` and `step` along with the `series`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct MetricsQueryData {
+  /// Docker host of the container, the default one when omitted
+  pub host: Option<String>,
+  
+  /// Container name
+  pub container: Option<String>,
+  
+  /// Metrics to return, all of them when omitted
+  pub metrics: Option<Vec<String>>,
+  
+  /// UNIX timestamps in seconds, the last hour by default
+  pub from: Option<u64>,
+  
+  pub to: Option<u64>,
+  
+  /// Seconds between two points
+  pub step: Option<u64>,
+  
+  pub series: Option<Vec<MetricSeries>>,
+  
+  pub error: Option<String>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct MetricSeries {
+  pub metric: String,
+  
+  /// `[timestamp, value]` pairs, the average over each step
+  pub points: Vec<(u64, f64)>
+}
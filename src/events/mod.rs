@@ -1,13 +1,100 @@
-use serde::{Deserialize, Serialize};
-use system::SystemEvent;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use system::{Role, SystemEvent, Topic};
 use docker::DockerEvent;
+use agent::AgentEvent;
+use alert::AlertEvent;
+use schedule::ScheduleEvent;
+use metrics::MetricsEvent;
 
 pub mod system;
 pub mod docker;
+pub mod agent;
+pub mod alert;
+pub mod schedule;
+pub mod metrics;
+pub mod registry;
+mod families;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Version of the protocol spoken by the server, announced in `Hello`
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Oldest version still accepted: `1` is the protocol before the `Hello` handshake
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Every event, serialized as the family's own `{ "type", "data" }` shape. Deserializing goes through
+/// the [`registry`], which picks the family from `type`.
+#[derive(Serialize, JsonSchema, Debug)]
 #[serde(untagged)]
 pub enum Event {
   System(SystemEvent),
-  Docker(DockerEvent)
-}
\ No newline at end of file
+  Docker(DockerEvent),
+  Agent(AgentEvent),
+  Alert(AlertEvent),
+  Schedule(ScheduleEvent),
+  Metrics(MetricsEvent)
+}
+
+impl<'de> Deserialize<'de> for Event {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    registry::decode_event(Value::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+  }
+}
+
+impl Event {
+  /// Value of the `type` field
+  pub fn name(&self) -> &'static str {
+    match self {
+      Event::System(event) => event.into(),
+      Event::Docker(event) => event.into(),
+      Event::Agent(event) => event.into(),
+      Event::Alert(event) => event.into(),
+      Event::Schedule(event) => event.into(),
+      Event::Metrics(event) => event.into()
+    }
+  }
+  
+  /// Container targeted by the event, if any
+  pub fn container_id(&self) -> Option<&str> {
+    match self {
+      Event::Docker(DockerEvent::DockerContainerInspect { data }) => data.container_id.as_deref(),
+      Event::Docker(DockerEvent::DockerContainerStart { data }) => data.container_id.as_deref(),
+      Event::Docker(DockerEvent::DockerContainerRestart { data }) => data.container_id.as_deref(),
+      Event::Docker(DockerEvent::DockerContainerStop { data }) => data.container_id.as_deref(),
+      Event::Docker(DockerEvent::DockerContainerStats { data }) => data.container_id.as_deref(),
+      _ => None
+    }
+  }
+  
+  /// Least role allowed to send the event: operators act on containers and schedules, viewers only read
+  pub fn required_role(&self) -> Role {
+    match self {
+      Event::Docker(DockerEvent::DockerContainerStart { .. })
+      | Event::Docker(DockerEvent::DockerContainerRestart { .. })
+      | Event::Docker(DockerEvent::DockerContainerStop { .. })
+      | Event::Docker(DockerEvent::DockerContainerBulk { .. })
+      | Event::Schedule(ScheduleEvent::ScheduleCreate { .. })
+      | Event::Schedule(ScheduleEvent::ScheduleUpdate { .. })
+      | Event::Schedule(ScheduleEvent::ScheduleDelete { .. }) => Role::Operator,
+      _ => Role::Viewer
+    }
+  }
+  
+  /// Whether the handler changes the session (identity, format, subscriptions, stream), it then runs holding
+  /// it. The others run on a detached copy, so that broadcasts are not held back by slow Docker calls.
+  pub fn changes_session(&self) -> bool {
+    matches!(self, Event::System(_) | Event::Docker(DockerEvent::DockerContainerSync { .. }))
+  }
+  
+  /// Telemetry topic of the event, `None` for events delivered to every client
+  pub fn topic(&self) -> Option<Topic> {
+    match self {
+      Event::Docker(DockerEvent::DockerContainerStats { .. }) => Some(Topic::ContainerStats),
+      Event::System(SystemEvent::SystemLoad { .. }) => Some(Topic::HostLoad),
+      Event::Docker(DockerEvent::DockerEventMessage { .. }) => Some(Topic::DockerEvents),
+      Event::Docker(DockerEvent::DockerContainerPatch { .. }) => Some(Topic::Containers),
+      _ => None
+    }
+  }
+}
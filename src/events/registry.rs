@@ -0,0 +1,92 @@
+//! Decoding of incoming events, routed on their `type` to the family declaring it. Families are
+//! listed in [`families`](super::families): a type declared twice is refused at startup instead of
+//! being shadowed, and an unknown type or invalid data is reported as such.
+
+use std::{collections::HashMap, fmt, sync::LazyLock};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use strum::VariantNames;
+
+use super::{families, Event};
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(|| {
+  let mut registry = Registry::default();
+  families::register(&mut registry);
+  registry
+});
+
+/// Enum of related events, internally tagged by `type`, and one of the variants of [`Event`]
+pub trait Family: DeserializeOwned + VariantNames + Into<Event> + Send + Sync + 'static {
+  /// The family's event wrapped in `event`, if any
+  fn of(event: &Event) -> Option<&Self>;
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+  /// Not an object, or without a string `type`
+  Malformed(String),
+  UnknownType(String),
+  Invalid { typ: &'static str, error: serde_json::Error }
+}
+
+impl DecodeError {
+  /// Type of the rejected event, when it is known
+  pub fn event_type(&self) -> Option<&str> {
+    match self {
+      DecodeError::Malformed(_) => None,
+      DecodeError::UnknownType(typ) => Some(typ),
+      DecodeError::Invalid { typ, .. } => Some(typ)
+    }
+  }
+}
+
+impl fmt::Display for DecodeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      DecodeError::Malformed(error) => write!(f, "malformed event: {}", error),
+      DecodeError::UnknownType(typ) => write!(f, "unknown event type `{}`", typ),
+      DecodeError::Invalid { typ, error } => write!(f, "invalid `{}` event: {}", typ, error)
+    }
+  }
+}
+
+impl std::error::Error for DecodeError {}
+
+type Decode = fn(Value) -> Result<Event, serde_json::Error>;
+
+#[derive(Default)]
+pub struct Registry {
+  decoders: HashMap<&'static str, (&'static str, Decode)>
+}
+
+impl Registry {
+  /// Decodes every type of the family `F`
+  pub fn register<F: Family>(&mut self) {
+    let family = std::any::type_name::<F>().rsplit("::").next().unwrap_or_default();
+    for typ in F::VARIANTS {
+      if let Some((other, _)) = self.decoders.insert(typ, (family, decode::<F>)) {
+        panic!("Event type `{}` is declared by both {} and {}", typ, other, family);
+      }
+    }
+  }
+}
+
+fn decode<F: Family>(value: Value) -> Result<Event, serde_json::Error> {
+  serde_json::from_value::<F>(value).map(Into::into)
+}
+
+/// Decodes an event from its JSON value, whatever the encoding it was read from
+pub fn decode_event(value: Value) -> Result<Event, DecodeError> {
+  let typ = match value.get("type") {
+    Some(Value::String(typ)) => typ,
+    Some(_) => return Err(DecodeError::Malformed("`type` is not a string".to_string())),
+    None if value.is_object() => return Err(DecodeError::Malformed("missing `type`".to_string())),
+    None => return Err(DecodeError::Malformed("not an object".to_string()))
+  };
+  
+  let Some((typ, decode)) = REGISTRY.decoders.get_key_value(typ.as_str()).map(|(typ, (_, decode))| (*typ, *decode)) else {
+    return Err(DecodeError::UnknownType(typ.clone()));
+  };
+  decode(value).map_err(|error| DecodeError::Invalid { typ, error })
+}
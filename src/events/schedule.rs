@@ -0,0 +1,131 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use strum::{IntoStaticStr, VariantNames};
+
+use crate::{events::{docker::ContainerSelector, registry::Family, Event}, services::backend::ExecOutput};
+
+/// Scheduled container actions. Create, update and delete answer with the stored schedule or an `error`,
+/// `ScheduleRun` is broadcast after every run.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, IntoStaticStr, VariantNames)]
+#[serde(tag = "type")]
+#[allow(clippy::enum_variant_names)]
+pub enum ScheduleEvent {
+  ScheduleList { data: ScheduleListData },
+  ScheduleCreate { data: ScheduleData },
+  ScheduleUpdate { data: ScheduleData },
+  ScheduleDelete { data: ScheduleDeleteData },
+  ScheduleRun { data: ScheduleRunData }
+}
+
+impl From<ScheduleEvent> for Event {
+  fn from(event: ScheduleEvent) -> Self {
+    Event::Schedule(event)
+  }
+}
+
+impl Family for ScheduleEvent {
+  fn of(event: &Event) -> Option<&Self> {
+    match event {
+      Event::Schedule(event) => Some(event),
+      _ => None
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct ScheduleListData {
+  pub schedules: Option<Vec<Schedule>>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct ScheduleData {
+  pub schedule: Option<Schedule>,
+  
+  pub error: Option<String>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct ScheduleDeleteData {
+  pub id: String,
+  
+  pub deleted: Option<bool>,
+  
+  pub error: Option<String>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleAction {
+  Start,
+  Stop,
+  Restart,
+  Exec
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Schedule {
+  /// Assigned by the server on creation
+  pub id: Option<String>,
+  
+  pub name: Option<String>,
+  
+  /// Cron expression in UTC, with 5 fields (minute to day of week) or 6 with seconds first
+  pub cron: String,
+  
+  pub action: ScheduleAction,
+  
+  /// Command run by the `exec` action
+  pub command: Option<Vec<String>>,
+  
+  pub target: ContainerSelector,
+  
+  pub enabled: Option<bool>,
+  
+  /// Client that created or last updated the schedule, filled by the server. Protected containers are only
+  /// acted on while it is a configured admin.
+  pub owner: Option<String>,
+  
+  /// Next run as a UNIX timestamp in seconds, filled by the server
+  #[serde(rename = "nextRun")]
+  pub next_run: Option<u64>,
+  
+  #[serde(rename = "lastRun")]
+  pub last_run: Option<ScheduleRunData>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct ScheduleRunData {
+  #[serde(rename = "scheduleId")]
+  pub schedule_id: String,
+  
+  pub action: ScheduleAction,
+  
+  /// UNIX timestamps in seconds
+  #[serde(rename = "startedAt")]
+  pub started_at: u64,
+  
+  #[serde(rename = "finishedAt")]
+  pub finished_at: u64,
+  
+  pub results: Vec<ScheduleResult>,
+  
+  /// Set when the targets could not be resolved
+  pub error: Option<String>
+}
+
+/// Outcome of the action on one targeted container
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct ScheduleResult {
+  pub host: String,
+  
+  #[serde(rename = "containerId")]
+  pub container_id: String,
+  
+  pub name: Option<String>,
+  
+  pub success: bool,
+  
+  pub error: Option<String>,
+  
+  pub exec: Option<ExecOutput>
+}
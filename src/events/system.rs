@@ -1,7 +1,234 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use strum::{IntoStaticStr, VariantNames};
 
-#[derive(Serialize, Deserialize, Debug)]
+use crate::{events::{registry::Family, Event}, serializers::format::{Compression, Encoding}};
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, IntoStaticStr, VariantNames)]
 #[serde(tag = "type")]
+#[allow(clippy::enum_variant_names)]
 pub enum SystemEvent {
-  SystemStatus
+  Hello { data: HelloData },
+  SystemStatus,
+  SystemNegotiate { data: SystemNegotiateData },
+  SystemSubscribe { data: SystemSubscribeData },
+  SystemUnsubscribe { data: SystemSubscribeData },
+  SystemLoad { data: SystemLoadData },
+  ServerShutdown { data: ServerShutdownData },
+  SystemCertificate { data: SystemCertificateData },
+  SystemError { data: SystemErrorData }
+}
+
+impl From<SystemEvent> for Event {
+  fn from(event: SystemEvent) -> Self {
+    Event::System(event)
+  }
+}
+
+impl Family for SystemEvent {
+  fn of(event: &Event) -> Option<&Self> {
+    match event {
+      Event::System(event) => Some(event),
+      _ => None
+    }
+  }
+}
+
+/// First event of a stream: the client sends its protocol version and the features it wants, the server
+/// answers with what it supports and who the client is authenticated as. An answer carrying an `error`
+/// closes the stream.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
+pub struct HelloData {
+  /// Protocol version of the sender, `1` when omitted (clients predating the handshake)
+  pub version: Option<u32>,
+  
+  /// Oldest version the server still accepts
+  #[serde(rename = "minVersion")]
+  pub min_version: Option<u32>,
+  
+  /// `name/version` of the sending software
+  pub software: Option<String>,
+  
+  /// Client token, from the `clients` of the server configuration
+  pub token: Option<String>,
+  
+  /// Requested by the client, the effective values in the answer
+  pub features: Option<HelloFeatures>,
+  
+  /// Every event type the server handles or sends
+  pub events: Option<Vec<String>>,
+  
+  pub limits: Option<HelloLimits>,
+  
+  pub identity: Option<Identity>,
+  
+  /// Shapes still accepted for a transition period
+  pub deprecations: Option<Vec<Deprecation>>,
+  
+  pub error: Option<String>
+}
+
+/// Same as `SystemNegotiate` and `SystemSubscribe`, the wire format switches once the answer is sent
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
+pub struct HelloFeatures {
+  pub encoding: Option<Encoding>,
+  
+  pub compression: Option<Compression>,
+  
+  #[serde(rename = "compressionThreshold")]
+  pub compression_threshold: Option<usize>,
+  
+  pub subscriptions: Option<Vec<SystemSubscribeData>>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct HelloLimits {
+  /// Bytes of a framed event, compressed or not
+  #[serde(rename = "maxFrameSize")]
+  pub max_frame_size: usize,
+  
+  /// Bytes of a datagram on this connection, bigger messages go on the stream
+  #[serde(rename = "maxDatagramSize")]
+  pub max_datagram_size: Option<usize>,
+  
+  /// Events of a `DockerEventHistory` page
+  #[serde(rename = "maxEventHistory")]
+  pub max_event_history: usize,
+  
+  /// Points of a `MetricsQuery` series
+  #[serde(rename = "maxMetricsPoints")]
+  pub max_metrics_points: u64
+}
+
+/// What a client may do, each role allowing what the previous ones do
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+  Viewer,
+  Operator,
+  Admin
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Identity {
+  pub name: String,
+  
+  pub role: Role,
+  
+  /// Whether the client presented a token, anonymous streams never act on protected containers
+  #[serde(skip)]
+  pub authenticated: bool
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Deprecation {
+  pub shape: String,
+  
+  pub replacement: String,
+  
+  /// Protocol version that drops the shape
+  #[serde(rename = "removedIn")]
+  pub removed_in: u32
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct SystemNegotiateData {
+  pub encoding: Option<Encoding>,
+  
+  pub compression: Option<Compression>,
+  
+  #[serde(rename = "compressionThreshold")]
+  pub compression_threshold: Option<usize>
+}
+
+
+/// High-rate broadcasts a client can opt into
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Topic {
+  #[serde(rename = "containerStats")]
+  ContainerStats,
+  #[serde(rename = "hostLoad")]
+  HostLoad,
+  /// Raw Docker events of every host, as they are recorded
+  #[serde(rename = "dockerEvents")]
+  DockerEvents,
+  /// `DockerContainerPatch` events, subscribed by `DockerContainerSync`
+  #[serde(rename = "containers")]
+  Containers
+}
+
+/// Transport used to deliver a subscription: reliable stream or loss-tolerant datagrams
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+  Stream,
+  #[default]
+  Datagram
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct SystemSubscribeData {
+  pub topic: Topic,
+  
+  pub channel: Option<Channel>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct SystemLoadData {
+  pub load1: Option<f64>,
+  
+  pub load5: Option<f64>,
+  
+  pub load15: Option<f64>,
+  
+  #[serde(rename = "memoryTotal")]
+  pub memory_total: Option<u64>,
+  
+  #[serde(rename = "memoryAvailable")]
+  pub memory_available: Option<u64>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct ServerShutdownData {
+  pub reason: Option<String>,
+  
+  /// Seconds the client should wait before reconnecting
+  #[serde(rename = "reconnectAfter")]
+  pub reconnect_after: Option<u64>
+}
+
+/// Sent by the server on the stream of an event it could not read. The stream stays open.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct SystemErrorData {
+  /// Type of the rejected event, when it could be read
+  pub event: Option<String>,
+  
+  pub code: Option<ErrorCode>,
+  
+  pub error: Option<String>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+  /// Undecodable frame, or an event without a string `type`
+  Malformed,
+  FrameTooLarge,
+  UnknownType,
+  /// Known type, but its data does not match the schema
+  InvalidEvent,
+  /// Received while the server drains its connections, the request was not handled
+  ShuttingDown,
+  /// The role of the client does not allow the request, it was not handled
+  Forbidden
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+pub struct SystemCertificateData {
+  /// SHA-256 hash of the certificate, usable in `serverCertificateHashes`
+  pub hash: Option<String>,
+  
+  /// Expiry as a UNIX timestamp in seconds, known for self-signed certificates only
+  #[serde(rename = "expiresAt")]
+  pub expires_at: Option<u64>
 }
\ No newline at end of file
@@ -0,0 +1,138 @@
+use std::error::Error;
+
+use serde::Serialize;
+use serde_json::Value;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::config::{LogFormat, LoggingConfig};
+
+/// Field names whose values never reach the logs, matched whole and case-insensitively, ignoring `_` and `-`
+/// so that `apiKey`, `api_key` and `API-KEY` are the same field. `env` covers the environment of containers.
+const SENSITIVE_KEYS: [&str; 12] = [
+    "token", "accesstoken", "agenttoken", "password", "passwd", "secret", "clientsecret", "authorization",
+    "apikey", "privatekey", "credentials", "env"
+];
+const REDACTED: &str = "[REDACTED]";
+
+#[cfg(feature = "otlp")]
+static TRACER_PROVIDER: std::sync::OnceLock<opentelemetry_sdk::trace::SdkTracerProvider> = std::sync::OnceLock::new();
+
+/// Installs the global subscriber: text or JSON lines on stdout, plus OTLP export when enabled.
+/// `RUST_LOG` takes precedence over the configured level.
+pub fn init(config: &LoggingConfig) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let filter = EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new(&config.level))?;
+    
+    let output = match config.format {
+        LogFormat::Json => fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+        LogFormat::Text => fmt::layer().boxed()
+    };
+    
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(output);
+    
+    #[cfg(feature = "otlp")]
+    let registry = registry.with(otlp_layer(config)?);
+    
+    registry.try_init()?;
+    
+    #[cfg(not(feature = "otlp"))]
+    if config.otlp_endpoint.is_some() {
+        tracing::warn!("otlpEndpoint is set but the server was built without the `otlp` feature");
+    }
+    
+    Ok(())
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_layer<S>(config: &LoggingConfig) -> Result<Option<impl Layer<S>>, Box<dyn Error + Send + Sync>>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>
+{
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::WithExportConfig;
+    
+    let Some(endpoint) = &config.otlp_endpoint else {
+        return Ok(None);
+    };
+    
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()?;
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(opentelemetry_sdk::Resource::builder().with_service_name(env!("CARGO_PKG_NAME")).build())
+        .build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    let _ = TRACER_PROVIDER.set(provider);
+    
+    Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+}
+
+/// Flushes the spans still waiting to be exported
+pub fn shutdown() {
+    #[cfg(feature = "otlp")]
+    if let Some(provider) = TRACER_PROVIDER.get()
+        && let Err(e) = provider.shutdown() {
+        eprintln!("Failed to flush OTLP spans: {:?}", e);
+    }
+}
+
+/// Serializes a payload for the logs with its sensitive fields masked
+pub fn redact<T: Serialize>(payload: &T) -> String {
+    match serde_json::to_value(payload) {
+        Ok(mut value) => {
+            redact_value(&mut value);
+            value.to_string()
+        },
+        Err(_) => REDACTED.to_string()
+    }
+}
+
+fn redact_value(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if sensitive(key) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact_value(value);
+                }
+            }
+        },
+        Value::Array(values) => values.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+fn sensitive(key: &str) -> bool {
+    let key: String = key.chars().filter(|c| *c != '_' && *c != '-').collect::<String>().to_lowercase();
+    SENSITIVE_KEYS.contains(&key.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    
+    use super::*;
+    
+    #[test]
+    fn only_sensitive_fields_are_redacted() {
+        let payload = json!({
+            "token": "t", "API_KEY": "k", "Authorization": "a", "Env": ["A=1"],
+            "keys": ["name"], "monkey": 1, "environment": "prod",
+            "nested": [{ "password": "p", "author": "me" }]
+        });
+        
+        assert_eq!(serde_json::from_str::<Value>(&redact(&payload)).unwrap(), json!({
+            "token": REDACTED, "API_KEY": REDACTED, "Authorization": REDACTED, "Env": REDACTED,
+            "keys": ["name"], "monkey": 1, "environment": "prod",
+            "nested": [{ "password": REDACTED, "author": "me" }]
+        }));
+    }
+}
@@ -1,18 +1,87 @@
+mod admin;
+mod agent;
+mod config;
 mod events;
+mod logging;
 mod serializers;
 mod webtransport;
 mod services;
+mod schema;
+#[cfg(test)]
+mod tests;
 
 use rustls::crypto::{ring::default_provider, CryptoProvider};
+use tokio_util::sync::CancellationToken;
 
 #[tokio::main]
 async fn main() {
-    env_logger::builder().filter_level(log::LevelFilter::Info).init();
+    if std::env::args().nth(1).as_deref() == Some("schema") {
+        let dir = std::env::args().nth(2).unwrap_or_else(|| ".".to_string());
+        match schema::write(std::path::Path::new(&dir)) {
+            Ok(_) => println!("Protocol schema, types and client written to {}", dir),
+            Err(e) => {
+                eprintln!("Failed to write the protocol schema: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let config = match config::Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load configuration: {:?}", e);
+            return;
+        }
+    };
+    
+    if let Err(e) = logging::init(&config.logging) {
+        eprintln!("Failed to initialize logging: {:?}", e);
+        return;
+    }
+    config::init(config);
+    
     CryptoProvider::install_default(default_provider())
         .expect("Failed to install default crypto provider");
     
-    match webtransport::start_webtransport().await {
-        Ok(_) => log::info!("WebTransport server stopped"),
-        Err(e) => log::error!("WebTransport server failed: {:?}", e)
+    let shutdown = CancellationToken::new();
+    tokio::spawn(wait_for_signal(shutdown.clone()));
+    
+    let admin_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        if let Err(e) = admin::start_admin(admin_shutdown).await {
+            tracing::error!("Admin server failed: {:?}", e);
+        }
+    });
+    
+    if std::env::args().nth(1).as_deref() == Some("agent") {
+        match agent::start_agent(shutdown).await {
+            Ok(_) => tracing::info!("Agent stopped"),
+            Err(e) => tracing::error!("Agent failed: {:?}", e)
+        };
+    } else {
+        match webtransport::start_webtransport(shutdown).await {
+            Ok(_) => tracing::info!("WebTransport server stopped"),
+            Err(e) => tracing::error!("WebTransport server failed: {:?}", e)
+        };
+    }
+    
+    logging::shutdown();
+}
+
+async fn wait_for_signal(shutdown: CancellationToken) {
+    let mut terminate = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(e) => {
+            tracing::error!("Failed to listen for SIGTERM: {:?}", e);
+            return;
+        }
     };
-}
\ No newline at end of file
+    
+    tokio::select! {
+        _ = terminate.recv() => tracing::info!("Received SIGTERM"),
+        _ = tokio::signal::ctrl_c() => tracing::info!("Received SIGINT")
+    }
+    
+    shutdown.cancel();
+}
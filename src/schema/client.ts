@@ -0,0 +1,118 @@
+// Generated by `admin-api-rust schema`, do not edit
+//
+// Typed client of the admin API: events are exchanged as JSON on a single bidirectional WebTransport
+// stream, every request is answered with an event of the same `type`.
+
+import type { Event } from "./protocol";
+
+export type EventType = Event["type"];
+
+export type EventOf<T extends EventType> = Extract<Event, { type: T }>;
+
+export type DataOf<T extends EventType> = EventOf<T> extends { data: infer D } ? D : never;
+
+export type Listener<T extends EventType> = (event: EventOf<T>) => void;
+
+export interface ClientOptions {
+  /** Hashes of a self-signed certificate, as written to `tls.hashFile` */
+  serverCertificateHashes?: WebTransportHash[];
+
+  /** Milliseconds `request` waits for the answer */
+  timeout?: number;
+}
+
+export class AdminClient {
+  private readonly listeners = new Map<string, Set<(event: Event) => void>>();
+  private readonly encoder = new TextEncoder();
+  private buffer = "";
+
+  private constructor(
+    private readonly transport: WebTransport,
+    private readonly writer: WritableStreamDefaultWriter<Uint8Array>,
+    private readonly timeout: number
+  ) {}
+
+  static async connect(url: string, options: ClientOptions = {}): Promise<AdminClient> {
+    const transport = new WebTransport(url, { serverCertificateHashes: options.serverCertificateHashes });
+    await transport.ready;
+
+    const stream = await transport.createBidirectionalStream();
+    const client = new AdminClient(transport, stream.writable.getWriter(), options.timeout ?? 10_000);
+    void client.read(stream.readable);
+    return client;
+  }
+
+  /** Sends an event without waiting for an answer */
+  async send<T extends EventType>(type: T, ...data: [DataOf<T>] extends [never] ? [] : [DataOf<T>]): Promise<void> {
+    await this.write(data.length > 0 ? { type, data: data[0] } : { type });
+  }
+
+  /** Sends an event and resolves with the first event of the same type accepted by `matches` */
+  async request<T extends EventType>(type: T, data: [DataOf<T>] extends [never] ? undefined : DataOf<T>, matches: (event: EventOf<T>) => boolean = () => true): Promise<EventOf<T>> {
+    const answer = new Promise<EventOf<T>>((resolve, reject) => {
+      const timer = setTimeout(() => {
+        unsubscribe();
+        reject(new Error(`No answer to ${type} within ${this.timeout} ms`));
+      }, this.timeout);
+      const unsubscribe = this.on(type, event => {
+        if (matches(event)) {
+          clearTimeout(timer);
+          unsubscribe();
+          resolve(event);
+        }
+      });
+    });
+
+    await this.write({ type, data });
+    return answer;
+  }
+
+  /** Calls `listener` for every event of `type`, until the returned function is called */
+  on<T extends EventType>(type: T, listener: Listener<T>): () => void {
+    const listeners = this.listeners.get(type) ?? new Set();
+    listeners.add(listener as (event: Event) => void);
+    this.listeners.set(type, listeners);
+    return () => listeners.delete(listener as (event: Event) => void);
+  }
+
+  close(): void {
+    this.transport.close();
+  }
+
+  private async write(event: object): Promise<void> {
+    await this.writer.write(this.encoder.encode(JSON.stringify(event)));
+  }
+
+  private async read(readable: ReadableStream<Uint8Array>): Promise<void> {
+    const decoder = new TextDecoder();
+    for await (const chunk of readable as unknown as AsyncIterable<Uint8Array>) {
+      this.buffer += decoder.decode(chunk, { stream: true });
+      let end: number;
+      while ((end = nextEvent(this.buffer)) > 0) {
+        const event = JSON.parse(this.buffer.slice(0, end)) as Event;
+        this.buffer = this.buffer.slice(end);
+        this.listeners.get(event.type)?.forEach(listener => listener(event));
+      }
+    }
+  }
+}
+
+/** Length of the first complete JSON object of `text`, 0 when more data is needed */
+function nextEvent(text: string): number {
+  let depth = 0;
+  let inString = false;
+  for (let i = 0; i < text.length; i++) {
+    const c = text[i];
+    if (inString) {
+      if (c === "\\") i++;
+      else if (c === "\"") inString = false;
+    } else if (c === "\"") {
+      inString = true;
+    } else if (c === "{") {
+      depth++;
+    } else if (c === "}" && --depth === 0) {
+      return i + 1;
+    }
+  }
+  return 0;
+}
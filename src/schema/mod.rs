@@ -0,0 +1,69 @@
+//! Machine-readable description of the event protocol: a JSON Schema derived from the `Event` types,
+//! TypeScript definitions generated from it and a small typed client, written by `admin-api-rust schema <dir>`
+
+use std::{borrow::Cow, marker::PhantomData, path::Path};
+
+use schemars::{generate::SchemaSettings, json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::events::Event;
+
+mod trace;
+mod typescript;
+
+pub const SCHEMA_FILE: &str = "protocol.schema.json";
+
+pub const TYPES_FILE: &str = "protocol.ts";
+
+pub const CLIENT_FILE: &str = "client.ts";
+
+/// Schema of a type only known through its `Deserialize` implementation, such as the bollard models,
+/// for `#[schemars(with = "Traced<T>")]`
+pub struct Traced<T>(PhantomData<T>);
+
+impl<T: DeserializeOwned> JsonSchema for Traced<T> {
+    fn inline_schema() -> bool {
+        // The trace already answers a reference to its own definitions
+        true
+    }
+    
+    fn schema_name() -> Cow<'static, str> {
+        let name = std::any::type_name::<T>();
+        Cow::Borrowed(name.rsplit("::").next().unwrap_or(name))
+    }
+    
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        match trace::trace::<T>() {
+            Ok((schema, definitions)) => {
+                for (name, definition) in definitions {
+                    generator.definitions_mut().entry(name).or_insert(definition);
+                }
+                Schema::try_from(schema).unwrap_or_default()
+            },
+            Err(e) => {
+                tracing::warn!("Failed to trace {}: {}", Self::schema_name(), e);
+                json_schema!({ "description": format!("{} could not be traced: {}", Self::schema_name(), e) })
+            }
+        }
+    }
+}
+
+/// JSON Schema (draft 2020-12) of every event, requests and answers alike
+pub fn protocol() -> Value {
+    let generator = SchemaSettings::draft2020_12().into_generator();
+    let mut schema = generator.into_root_schema_for::<Event>().to_value();
+    schema["title"] = json!("Event");
+    schema["description"] = json!(format!("Events of the admin API protocol, version {}", env!("CARGO_PKG_VERSION")));
+    schema
+}
+
+/// Writes the schema, the TypeScript definitions and the client into `dir`
+pub fn write(dir: &Path) -> std::io::Result<()> {
+    let schema = protocol();
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(SCHEMA_FILE), serde_json::to_string_pretty(&schema)?)?;
+    std::fs::write(dir.join(TYPES_FILE), typescript::types(&schema))?;
+    std::fs::write(dir.join(CLIENT_FILE), typescript::CLIENT)?;
+    Ok(())
+}
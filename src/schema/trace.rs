@@ -0,0 +1,495 @@
+//! Schemas of types that only implement `Deserialize`, such as the bollard models: the type is deserialized
+//! from a tracer that records what each field asks for and hands back placeholder values.
+//!
+//! Fields deserialized from self-describing input (untagged enums, `deserialize_any` helpers) cannot be
+//! traced, they are typed as anything and skipped on the next pass. Enums yield one variant per pass,
+//! passes go on until every variant has been seen.
+
+use std::{collections::{BTreeMap, HashSet}, fmt};
+
+use serde::de::{self, value::BorrowedStrDeserializer, DeserializeOwned, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde_json::{json, Map, Value};
+
+const MAX_PASSES: usize = 256;
+
+pub const DEFINITIONS: &str = "#/$defs/";
+
+#[derive(Debug)]
+pub struct TraceError(String);
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl de::Error for TraceError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        TraceError(msg.to_string())
+    }
+}
+
+#[derive(Default)]
+struct Tracer {
+    /// Structs found during the current pass
+    definitions: Map<String, Value>,
+    
+    /// Paths of the fields typed as anything
+    opaque: HashSet<String>,
+    
+    /// `Type.field` of the aliases, listed along with the field names
+    aliases: HashSet<String>,
+    
+    /// `Type.field` of the fields having aliases, handed before them
+    aliased: HashSet<String>,
+    
+    /// Last field handed to a struct
+    field: String,
+    
+    /// Variant names of every enum seen, with the schemas of the variants traced so far
+    enums: BTreeMap<&'static str, (&'static [&'static str], BTreeMap<usize, Value>)>,
+    
+    /// Structs being traced, a recursive type would never end
+    stack: Vec<&'static str>,
+    
+    /// `Type.field` being traced
+    path: String
+}
+
+/// Schema of `T`, a reference for structs and enums, along with the definitions it relies on
+pub fn trace<T: DeserializeOwned>() -> Result<(Value, Map<String, Value>), TraceError> {
+    let mut tracer = Tracer::default();
+    
+    for _ in 0..MAX_PASSES {
+        tracer.definitions = Map::new();
+        tracer.stack.clear();
+        tracer.path.clear();
+        let progress = tracer.progress();
+        
+        let mut schema = Value::Null;
+        match T::deserialize(Slot { tracer: &mut tracer, schema: &mut schema }) {
+            Ok(_) if tracer.complete() => {
+                let mut definitions = std::mem::take(&mut tracer.definitions);
+                for (name, (_, variants)) in tracer.enums {
+                    definitions.insert(name.to_string(), enum_schema(variants.into_values().collect()));
+                }
+                return Ok((schema, definitions));
+            },
+            Ok(_) => {},
+            Err(_) if tracer.progress() > progress => {},
+            Err(e) => return Err(e)
+        }
+    }
+    
+    Err(TraceError("Too many passes, the type has too many variants".to_string()))
+}
+
+impl Tracer {
+    /// What was learnt from the failed passes
+    fn progress(&self) -> usize {
+        self.opaque.len() + self.aliases.len() + self.aliased.len()
+    }
+    
+    /// Records the alias that failed a struct with a duplicate field
+    fn duplicate(&mut self, name: &str, error: &TraceError) {
+        let Some(field) = error.0.strip_prefix("duplicate field `").and_then(|rest| rest.split('`').next()) else {
+            return;
+        };
+        let path = format!("{}.{}", name, field);
+        match self.field == path {
+            // The alias came first, handing the field first leaves the alias last
+            true => self.aliased.insert(path),
+            false => self.aliases.insert(self.field.clone())
+        };
+    }
+    
+    fn complete(&self) -> bool {
+        self.enums.values().all(|(names, variants)| variants.len() == names.len())
+    }
+    
+    /// First variant not traced yet
+    fn next_variant(&mut self, name: &'static str, names: &'static [&'static str]) -> usize {
+        let (_, variants) = self.enums.entry(name).or_insert_with(|| (names, BTreeMap::new()));
+        (0..names.len()).find(|index| !variants.contains_key(index)).unwrap_or_default()
+    }
+    
+    /// Runs `trace` on `path` then restores the current one
+    fn at<T>(&mut self, path: String, trace: impl FnOnce(&mut Tracer) -> T) -> T {
+        let parent = std::mem::replace(&mut self.path, path);
+        let result = trace(self);
+        self.path = parent;
+        result
+    }
+}
+
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("{}{}", DEFINITIONS, name) })
+}
+
+/// Whether the field can be omitted
+fn optional(schema: &Value) -> bool {
+    schema.as_object().is_some_and(|schema| schema.is_empty())
+        || schema["anyOf"].as_array().is_some_and(|any_of| any_of.contains(&json!({ "type": "null" })))
+}
+
+/// Externally tagged, like serde does by default: unit variants are strings, the others single-key objects
+fn enum_schema(variants: Vec<Value>) -> Value {
+    let names: Option<Vec<Value>> = variants.iter().map(|variant| variant.get("const").cloned()).collect();
+    match names {
+        Some(names) => json!({ "type": "string", "enum": names }),
+        None => json!({ "oneOf": variants })
+    }
+}
+
+fn object_schema(properties: Map<String, Value>) -> Value {
+    let required: Vec<&String> = properties.iter().filter(|(_, schema)| !optional(schema)).map(|(name, _)| name).collect();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+/// Deserializer of one value, recording its schema
+struct Slot<'a> {
+    tracer: &'a mut Tracer,
+    schema: &'a mut Value
+}
+
+impl<'de> Deserializer<'de> for Slot<'_> {
+    type Error = TraceError;
+    
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        match self.tracer.opaque.contains(&self.tracer.path) {
+            true => {
+                *self.schema = json!({});
+                visitor.visit_unit()
+            },
+            false => {
+                self.tracer.opaque.insert(self.tracer.path.clone());
+                Err(TraceError(format!("`{}` is not typed", self.tracer.path)))
+            }
+        }
+    }
+    
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "boolean" });
+        visitor.visit_bool(false)
+    }
+    
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "integer", "format": "int8" });
+        visitor.visit_i8(0)
+    }
+    
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "integer", "format": "int16" });
+        visitor.visit_i16(0)
+    }
+    
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "integer", "format": "int32" });
+        visitor.visit_i32(0)
+    }
+    
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "integer", "format": "int64" });
+        visitor.visit_i64(0)
+    }
+    
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "integer", "format": "uint8", "minimum": 0 });
+        visitor.visit_u8(0)
+    }
+    
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "integer", "format": "uint16", "minimum": 0 });
+        visitor.visit_u16(0)
+    }
+    
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "integer", "format": "uint32", "minimum": 0 });
+        visitor.visit_u32(0)
+    }
+    
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "integer", "format": "uint64", "minimum": 0 });
+        visitor.visit_u64(0)
+    }
+    
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "number", "format": "float" });
+        visitor.visit_f32(0.0)
+    }
+    
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "number", "format": "double" });
+        visitor.visit_f64(0.0)
+    }
+    
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "string", "minLength": 1, "maxLength": 1 });
+        visitor.visit_char(' ')
+    }
+    
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "string" });
+        visitor.visit_str("")
+    }
+    
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        self.deserialize_str(visitor)
+    }
+    
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "array", "items": { "type": "integer", "format": "uint8", "minimum": 0 } });
+        visitor.visit_bytes(&[])
+    }
+    
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        self.deserialize_bytes(visitor)
+    }
+    
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        if self.tracer.opaque.contains(&self.tracer.path) {
+            *self.schema = json!({});
+            return visitor.visit_none();
+        }
+        
+        let mut inner = Value::Null;
+        let value = visitor.visit_some(Slot { tracer: self.tracer, schema: &mut inner })?;
+        *self.schema = json!({ "anyOf": [inner, { "type": "null" }] });
+        Ok(value)
+    }
+    
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.schema = json!({ "type": "null" });
+        visitor.visit_unit()
+    }
+    
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, TraceError> {
+        self.deserialize_unit(visitor)
+    }
+    
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, TraceError> {
+        visitor.visit_newtype_struct(self)
+    }
+    
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        let mut items = vec![Value::Null];
+        let path = format!("{}[]", self.tracer.path);
+        let value = self.tracer.at(path, |tracer| visitor.visit_seq(Elements { tracer, items: &mut items, next: 0 }))?;
+        *self.schema = json!({ "type": "array", "items": items.pop() });
+        Ok(value)
+    }
+    
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, TraceError> {
+        let mut items = vec![Value::Null; len];
+        let path = format!("{}[]", self.tracer.path);
+        let value = self.tracer.at(path, |tracer| visitor.visit_seq(Elements { tracer, items: &mut items, next: 0 }))?;
+        *self.schema = json!({ "type": "array", "prefixItems": items, "minItems": len, "maxItems": len });
+        Ok(value)
+    }
+    
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, len: usize, visitor: V) -> Result<V::Value, TraceError> {
+        self.deserialize_tuple(len, visitor)
+    }
+    
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        let mut values = Value::Null;
+        let path = format!("{}{{}}", self.tracer.path);
+        let value = self.tracer.at(path, |tracer| visitor.visit_map(Entry { tracer, value: &mut values, done: false }))?;
+        *self.schema = json!({ "type": "object", "additionalProperties": values });
+        Ok(value)
+    }
+    
+    fn deserialize_struct<V: Visitor<'de>>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, TraceError> {
+        if self.tracer.stack.contains(&name) {
+            return Err(TraceError(format!("`{}` is recursive", name)));
+        }
+        
+        self.tracer.stack.push(name);
+        let mut properties = Map::new();
+        let value = visitor.visit_map(Fields::new(&mut *self.tracer, name, fields, &mut properties))
+            .inspect_err(|e| self.tracer.duplicate(name, e))?;
+        self.tracer.stack.pop();
+        
+        self.tracer.definitions.insert(name.to_string(), object_schema(properties));
+        *self.schema = reference(name);
+        Ok(value)
+    }
+    
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, TraceError> {
+        let index = self.tracer.next_variant(name, variants);
+        let value = visitor.visit_enum(Variant { tracer: self.tracer, name, variants, index })?;
+        *self.schema = reference(name);
+        Ok(value)
+    }
+    
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        self.deserialize_str(visitor)
+    }
+    
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        visitor.visit_unit()
+    }
+}
+
+/// Elements of a sequence, one per schema in `items`
+struct Elements<'a> {
+    tracer: &'a mut Tracer,
+    items: &'a mut [Value],
+    next: usize
+}
+
+impl<'de> SeqAccess<'de> for Elements<'_> {
+    type Error = TraceError;
+    
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, TraceError> {
+        let Some(schema) = self.items.get_mut(self.next) else {
+            return Ok(None);
+        };
+        self.next += 1;
+        seed.deserialize(Slot { tracer: self.tracer, schema }).map(Some)
+    }
+}
+
+/// Single entry of a map, enough to know the type of its values
+struct Entry<'a> {
+    tracer: &'a mut Tracer,
+    value: &'a mut Value,
+    done: bool
+}
+
+impl<'de> MapAccess<'de> for Entry<'_> {
+    type Error = TraceError;
+    
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, TraceError> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        seed.deserialize(Slot { tracer: self.tracer, schema: &mut Value::Null }).map(Some)
+    }
+    
+    fn next_value_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value, TraceError> {
+        seed.deserialize(Slot { tracer: self.tracer, schema: self.value })
+    }
+}
+
+/// Every field of a struct but the aliases, in declaration order
+struct Fields<'a> {
+    tracer: &'a mut Tracer,
+    name: &'static str,
+    fields: Vec<&'static str>,
+    properties: &'a mut Map<String, Value>,
+    next: usize
+}
+
+impl<'a> Fields<'a> {
+    fn new(tracer: &'a mut Tracer, name: &'static str, fields: &'static [&'static str], properties: &'a mut Map<String, Value>) -> Self {
+        let path = |field: &&str| format!("{}.{}", name, field);
+        let (mut ordered, rest): (Vec<&str>, Vec<&str>) = fields.iter()
+            .filter(|field| !tracer.aliases.contains(&path(field)))
+            .partition(|field| tracer.aliased.contains(&path(field)));
+        ordered.extend(rest);
+        
+        Self { tracer, name, fields: ordered, properties, next: 0 }
+    }
+}
+
+impl<'de> MapAccess<'de> for Fields<'_> {
+    type Error = TraceError;
+    
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, TraceError> {
+        match self.fields.get(self.next) {
+            Some(field) => {
+                self.tracer.field = format!("{}.{}", self.name, field);
+                seed.deserialize(BorrowedStrDeserializer::new(field)).map(Some)
+            },
+            None => Ok(None)
+        }
+    }
+    
+    fn next_value_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value, TraceError> {
+        let field = self.fields[self.next];
+        self.next += 1;
+        
+        let mut schema = Value::Null;
+        let path = format!("{}.{}", self.name, field);
+        let value = self.tracer.at(path, |tracer| seed.deserialize(Slot { tracer, schema: &mut schema }))?;
+        self.properties.insert(field.to_string(), schema);
+        Ok(value)
+    }
+}
+
+/// Variant chosen for the current pass
+struct Variant<'a> {
+    tracer: &'a mut Tracer,
+    name: &'static str,
+    variants: &'static [&'static str],
+    index: usize
+}
+
+impl Variant<'_> {
+    fn record(self, schema: Value) {
+        let variant = self.variants[self.index];
+        let schema = match schema {
+            Value::Null => json!({ "const": variant }),
+            schema => json!({
+                "type": "object",
+                "properties": { variant: schema },
+                "required": [variant],
+                "additionalProperties": false
+            })
+        };
+        if let Some((_, variants)) = self.tracer.enums.get_mut(self.name) {
+            variants.insert(self.index, schema);
+        }
+    }
+    
+    fn path(&self) -> String {
+        format!("{}::{}", self.name, self.variants[self.index])
+    }
+}
+
+impl<'de, 'a> EnumAccess<'de> for Variant<'a> {
+    type Error = TraceError;
+    type Variant = Variant<'a>;
+    
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Variant<'a>), TraceError> {
+        let variant = seed.deserialize(BorrowedStrDeserializer::new(self.variants[self.index]))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Variant<'_> {
+    type Error = TraceError;
+    
+    fn unit_variant(self) -> Result<(), TraceError> {
+        self.record(Value::Null);
+        Ok(())
+    }
+    
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, TraceError> {
+        let mut schema = Value::Null;
+        let path = self.path();
+        let value = self.tracer.at(path, |tracer| seed.deserialize(Slot { tracer, schema: &mut schema }))?;
+        self.record(schema);
+        Ok(value)
+    }
+    
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, TraceError> {
+        let mut schema = Value::Null;
+        let path = self.path();
+        let value = self.tracer.at(path, |tracer| Slot { tracer, schema: &mut schema }.deserialize_tuple(len, visitor))?;
+        self.record(schema);
+        Ok(value)
+    }
+    
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, TraceError> {
+        let mut properties = Map::new();
+        let name = self.name;
+        let value = visitor.visit_map(Fields::new(&mut *self.tracer, name, fields, &mut properties))?;
+        self.record(object_schema(properties));
+        Ok(value)
+    }
+}
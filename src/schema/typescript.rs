@@ -0,0 +1,155 @@
+//! TypeScript definitions of the protocol schema: every definition becomes an exported type, events are
+//! unions discriminated on `type`
+
+use serde_json::{Map, Value};
+
+use super::trace::DEFINITIONS;
+
+/// Typed client over WebTransport, relying on the generated definitions
+pub const CLIENT: &str = include_str!("client.ts");
+
+pub fn types(schema: &Value) -> String {
+    let mut output = String::from("// Generated by `admin-api-rust schema`, do not edit\n\n");
+    
+    output.push_str(&comment(schema, ""));
+    output.push_str(&format!("export type Event = {};\n", ts(schema, "")));
+    
+    if let Some(definitions) = schema["$defs"].as_object() {
+        for (name, definition) in definitions {
+            output.push('\n');
+            output.push_str(&comment(definition, ""));
+            match definition.get("properties").is_some() && is_plain_object(definition) {
+                true => output.push_str(&format!("export interface {} {}\n", name, object(definition, ""))),
+                false => output.push_str(&format!("export type {} = {};\n", name, ts(definition, "")))
+            }
+        }
+    }
+    
+    output
+}
+
+/// Objects without combinators can be interfaces
+fn is_plain_object(schema: &Value) -> bool {
+    ["$ref", "anyOf", "oneOf", "allOf", "enum", "const"].iter().all(|key| schema.get(key).is_none())
+}
+
+fn comment(schema: &Value, indent: &str) -> String {
+    match schema["description"].as_str() {
+        Some(description) => {
+            let lines: Vec<String> = description.lines().map(|line| format!("{} * {}", indent, line).trim_end().to_string()).collect();
+            format!("{}/**\n{}\n{} */\n", indent, lines.join("\n"), indent)
+        },
+        None => String::new()
+    }
+}
+
+fn ts(schema: &Value, indent: &str) -> String {
+    let Some(object) = schema.as_object() else {
+        return match schema {
+            Value::Bool(false) => "never".to_string(),
+            _ => "unknown".to_string()
+        };
+    };
+    
+    let mut parts = Vec::new();
+    if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+        parts.push(reference.trim_start_matches(DEFINITIONS).to_string());
+    }
+    if let Some(value) = object.get("const") {
+        parts.push(value.to_string());
+    }
+    if let Some(values) = object.get("enum").and_then(Value::as_array) {
+        parts.push(union(values.iter().map(Value::to_string).collect()));
+    }
+    for key in ["anyOf", "oneOf"] {
+        if let Some(schemas) = object.get(key).and_then(Value::as_array) {
+            parts.push(union(schemas.iter().map(|schema| ts(schema, indent)).collect()));
+        }
+    }
+    if let Some(schemas) = object.get("allOf").and_then(Value::as_array) {
+        parts.extend(schemas.iter().map(|schema| ts(schema, indent)));
+    }
+    if object.get("const").is_none() && object.get("enum").is_none() {
+        match object.get("type") {
+            Some(Value::String(typ)) => parts.push(primitive(typ, object, indent)),
+            Some(Value::Array(types)) => parts.push(union(types.iter().filter_map(Value::as_str).map(|typ| primitive(typ, object, indent)).collect())),
+            _ if object.contains_key("properties") => parts.push(object_type(object, indent)),
+            _ => {}
+        }
+    }
+    
+    match parts.len() {
+        0 => "unknown".to_string(),
+        1 => parts.remove(0),
+        _ => parts.iter().map(|part| format!("({})", part)).collect::<Vec<_>>().join(" & ")
+    }
+}
+
+fn union(mut members: Vec<String>) -> String {
+    members.dedup();
+    match members.len() {
+        0 => "never".to_string(),
+        _ => members.join(" | ")
+    }
+}
+
+fn primitive(typ: &str, schema: &Map<String, Value>, indent: &str) -> String {
+    match typ {
+        "string" => "string".to_string(),
+        "integer" | "number" => "number".to_string(),
+        "boolean" => "boolean".to_string(),
+        "null" => "null".to_string(),
+        "array" => match (schema.get("prefixItems").and_then(Value::as_array), schema.get("items")) {
+            (Some(items), _) => format!("[{}]", items.iter().map(|item| ts(item, indent)).collect::<Vec<_>>().join(", ")),
+            (None, Some(items)) => {
+                let item = ts(items, indent);
+                match item.contains(' ') && !item.starts_with('{') {
+                    true => format!("({})[]", item),
+                    false => format!("{}[]", item)
+                }
+            },
+            (None, None) => "unknown[]".to_string()
+        },
+        "object" => object_type(schema, indent),
+        _ => "unknown".to_string()
+    }
+}
+
+fn object(schema: &Value, indent: &str) -> String {
+    schema.as_object().map(|schema| object_type(schema, indent)).unwrap_or_else(|| "unknown".to_string())
+}
+
+fn object_type(schema: &Map<String, Value>, indent: &str) -> String {
+    let properties = schema.get("properties").and_then(Value::as_object);
+    // Open objects with known properties come from flattened types, the index signature would only add noise
+    let additional = schema.get("additionalProperties")
+        .filter(|additional| **additional != Value::Bool(false) && !(**additional == Value::Bool(true) && properties.is_some()));
+    let required: Vec<&str> = schema.get("required").and_then(Value::as_array)
+        .map(|required| required.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    
+    let inner = format!("{}  ", indent);
+    let mut lines = Vec::new();
+    for (name, property) in properties.into_iter().flatten() {
+        let optional = if required.contains(&name.as_str()) { "" } else { "?" };
+        lines.push(format!("{}{}{}{}: {};", comment(property, &inner), inner, key(name), optional, ts(property, &inner)));
+    }
+    if let Some(additional) = additional {
+        lines.push(format!("{}[key: string]: {};", inner, ts(additional, &inner)));
+    }
+    
+    match lines.is_empty() {
+        true if additional.is_none() && properties.is_some() => "{}".to_string(),
+        true => "Record<string, unknown>".to_string(),
+        false => format!("{{\n{}\n{}}}", lines.join("\n"), indent)
+    }
+}
+
+fn key(name: &str) -> String {
+    let identifier = name.chars().enumerate()
+        .all(|(index, c)| c == '_' || c == '$' || c.is_ascii_alphabetic() || (index > 0 && c.is_ascii_digit()));
+    match identifier && !name.is_empty() {
+        true => name.to_string(),
+        false => Value::String(name.to_string()).to_string()
+    }
+}
@@ -1,30 +1,43 @@
-use bollard::secret::ContainerSummary;
-use serde_json::{json, Value};
+use bollard::secret::{ContainerInspectResponse, ContainerSummary};
 
-use super::EventDTO;
+use crate::events::docker::{HostContainerSummary, Projection};
 
+use super::EventDTO;
 
 impl EventDTO for ContainerSummary {
-  fn to_json(&self) -> Value {
-    json!({
-      "id": self.id,
-      "names": self.names,
-      "image": self.image,
-      "command": self.command,
-      "created": self.created,
-      "state": self.state,
-      "status": self.status,
-      "ports": self.ports,
-      "labels": self.labels,
-      "size_rw": self.size_rw,
-      "size_root_fs": self.size_root_fs,
-      "mounts": self.mounts,
-    })
+  fn key_fields() -> &'static [&'static str] {
+    &["Id"]
+  }
+  
+  fn projection(projection: Projection) -> Option<&'static [&'static str]> {
+    match projection {
+      Projection::Summary => Some(&["Names", "Image", "State", "Status", "Created", "Labels"]),
+      Projection::Full => None,
+      Projection::Network => Some(&["Names", "Ports", "HostConfig.NetworkMode", "NetworkSettings"])
+    }
   }
 }
 
-impl EventDTO for Vec<ContainerSummary> {
-  fn to_json(&self) -> Value {
-    json!(self.iter().map(|container| container.to_json()).collect::<Vec<Value>>())
+impl EventDTO for HostContainerSummary {
+  fn key_fields() -> &'static [&'static str] {
+    &["host", "Id"]
   }
-}
\ No newline at end of file
+  
+  fn projection(projection: Projection) -> Option<&'static [&'static str]> {
+    ContainerSummary::projection(projection)
+  }
+}
+
+impl EventDTO for ContainerInspectResponse {
+  fn key_fields() -> &'static [&'static str] {
+    &["Id"]
+  }
+  
+  fn projection(projection: Projection) -> Option<&'static [&'static str]> {
+    match projection {
+      Projection::Summary => Some(&["Name", "Created", "State", "RestartCount", "Config.Image", "Config.Labels"]),
+      Projection::Full => None,
+      Projection::Network => Some(&["Name", "HostConfig.NetworkMode", "HostConfig.PortBindings", "NetworkSettings"])
+    }
+  }
+}
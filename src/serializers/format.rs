@@ -0,0 +1,319 @@
+use std::{fmt, io::{Read, Write}};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::events::{registry::{self, DecodeError}, Event};
+
+/// Frames bigger than this are rejected, whether compressed or not
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+const DEFAULT_COMPRESSION_THRESHOLD: usize = 1024;
+const FLAG_BROTLI: u8 = 0b0000_0001;
+const HEADER_SIZE: usize = 5;
+
+/// Brotli internal buffer size, quality (0-11) and window size (log2, 10-24)
+const BROTLI_BUFFER_SIZE: usize = 4096;
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+  #[default]
+  #[serde(rename = "json")]
+  Json,
+  #[serde(rename = "msgpack", alias = "messagepack")]
+  MessagePack,
+  #[serde(rename = "cbor")]
+  Cbor
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+  #[default]
+  None,
+  Brotli
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+  Encode(String),
+  Decode(String),
+  Compression(std::io::Error),
+  FrameTooLarge(usize),
+  /// Decoded, but not a valid event
+  Event(DecodeError)
+}
+
+impl fmt::Display for FormatError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      FormatError::Encode(error) => write!(f, "failed to encode event: {}", error),
+      FormatError::Decode(error) => write!(f, "failed to decode event: {}", error),
+      FormatError::Compression(error) => write!(f, "failed to (de)compress frame: {}", error),
+      FormatError::FrameTooLarge(size) => write!(f, "frame of {} bytes exceeds the {} bytes limit", size, MAX_FRAME_SIZE),
+      FormatError::Event(error) => error.fmt(f)
+    }
+  }
+}
+
+impl std::error::Error for FormatError {}
+
+/// Wire format negotiated by a client at session start.
+///
+/// Plain JSON without compression is the legacy format: events are written as raw JSON text.
+/// Any other combination is framed as `[u32 BE length][u8 flags][payload]`, where `length`
+/// covers the flags byte and the payload, and the `FLAG_BROTLI` bit marks a compressed payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+  pub encoding: Encoding,
+  pub compression: Compression,
+  pub compression_threshold: usize
+}
+
+impl Default for Format {
+  fn default() -> Self {
+    Self {
+      encoding: Encoding::Json,
+      compression: Compression::None,
+      compression_threshold: DEFAULT_COMPRESSION_THRESHOLD
+    }
+  }
+}
+
+impl Format {
+  pub fn is_legacy(&self) -> bool {
+    self.encoding == Encoding::Json && self.compression == Compression::None
+  }
+  
+  /// Serializes an event with the negotiated encoding, without any framing or compression
+  pub fn serialize(&self, event: &Event) -> Result<Vec<u8>, FormatError> {
+    match self.encoding {
+      Encoding::Json => serde_json::to_vec(event).map_err(|error| FormatError::Encode(error.to_string())),
+      Encoding::MessagePack => rmp_serde::to_vec_named(event).map_err(|error| FormatError::Encode(error.to_string())),
+      Encoding::Cbor => {
+        let mut buffer = Vec::new();
+        ciborium::into_writer(event, &mut buffer).map_err(|error| FormatError::Encode(error.to_string()))?;
+        Ok(buffer)
+      }
+    }
+  }
+  
+  /// Deserializes an event, routed on its `type` by the event registry
+  pub fn deserialize(&self, bytes: &[u8]) -> Result<Event, FormatError> {
+    let value: serde_json::Value = match self.encoding {
+      Encoding::Json => serde_json::from_slice(bytes).map_err(|error| FormatError::Decode(error.to_string()))?,
+      Encoding::MessagePack => rmp_serde::from_slice(bytes).map_err(|error| FormatError::Decode(error.to_string()))?,
+      Encoding::Cbor => ciborium::from_reader(bytes).map_err(|error| FormatError::Decode(error.to_string()))?
+    };
+    registry::decode_event(value).map_err(FormatError::Event)
+  }
+  
+  /// Encodes a single message: the flags byte followed by the (possibly compressed) payload.
+  /// Legacy format messages are the raw JSON text.
+  pub fn encode(&self, event: &Event) -> Result<Vec<u8>, FormatError> {
+    let payload = self.serialize(event)?;
+    if self.is_legacy() {
+      return Ok(payload);
+    }
+    
+    let mut message = Vec::with_capacity(payload.len() + 1);
+    if self.compression == Compression::Brotli && payload.len() >= self.compression_threshold {
+      message.push(FLAG_BROTLI);
+      let mut compressor = brotli::CompressorWriter::new(&mut message, BROTLI_BUFFER_SIZE, BROTLI_QUALITY, BROTLI_WINDOW);
+      compressor.write_all(&payload).map_err(FormatError::Compression)?;
+      compressor.flush().map_err(FormatError::Compression)?;
+    } else {
+      message.push(0);
+      message.extend_from_slice(&payload);
+    }
+    
+    Ok(message)
+  }
+  
+  /// Decodes a message produced by [`Format::encode`]
+  pub fn decode(&self, message: &[u8]) -> Result<Event, FormatError> {
+    if self.is_legacy() {
+      return self.deserialize(message);
+    }
+    
+    let (flags, payload) = message.split_first().ok_or_else(|| FormatError::Decode("empty message".to_string()))?;
+    if flags & FLAG_BROTLI == 0 {
+      return self.deserialize(payload);
+    }
+    
+    let mut decompressed = Vec::new();
+    brotli::Decompressor::new(payload, BROTLI_BUFFER_SIZE)
+      .take(MAX_FRAME_SIZE as u64 + 1)
+      .read_to_end(&mut decompressed)
+      .map_err(FormatError::Compression)?;
+    if decompressed.len() > MAX_FRAME_SIZE {
+      return Err(FormatError::FrameTooLarge(decompressed.len()));
+    }
+    
+    self.deserialize(&decompressed)
+  }
+  
+  /// Encodes an event for a reliable stream, adding the length prefix outside of the legacy format
+  pub fn frame(&self, event: &Event) -> Result<Vec<u8>, FormatError> {
+    let message = self.encode(event)?;
+    if self.is_legacy() {
+      return Ok(message);
+    }
+    if message.len() > MAX_FRAME_SIZE {
+      return Err(FormatError::FrameTooLarge(message.len()));
+    }
+    
+    let mut frame = Vec::with_capacity(message.len() + 4);
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&message);
+    Ok(frame)
+  }
+}
+
+/// Reassembles events from the chunks read on a stream
+pub struct FrameReader {
+  format: Format,
+  buffer: Vec<u8>
+}
+
+impl FrameReader {
+  pub fn new(format: Format) -> Self {
+    Self { format, buffer: Vec::new() }
+  }
+  
+  pub fn set_format(&mut self, format: Format) {
+    self.format = format;
+  }
+  
+  pub fn push(&mut self, data: &[u8]) {
+    self.buffer.extend_from_slice(data);
+  }
+  
+  /// Returns the next complete event, or `None` if more data is needed
+  pub fn next_event(&mut self) -> Option<Result<Event, FormatError>> {
+    if self.buffer.is_empty() {
+      return None;
+    }
+    
+    if self.format.is_legacy() {
+      return self.next_json_event();
+    }
+    
+    if self.buffer.len() < HEADER_SIZE {
+      return None;
+    }
+    let length = u32::from_be_bytes([self.buffer[0], self.buffer[1], self.buffer[2], self.buffer[3]]) as usize;
+    if length > MAX_FRAME_SIZE {
+      self.buffer.clear();
+      return Some(Err(FormatError::FrameTooLarge(length)));
+    }
+    if self.buffer.len() < length + 4 {
+      return None;
+    }
+    
+    let message: Vec<u8> = self.buffer.drain(..length + 4).skip(4).collect();
+    Some(self.format.decode(&message))
+  }
+  
+  fn next_json_event(&mut self) -> Option<Result<Event, FormatError>> {
+    let mut stream = serde_json::Deserializer::from_slice(&self.buffer).into_iter::<serde_json::Value>();
+    match stream.next()? {
+      Ok(value) => {
+        let offset = stream.byte_offset();
+        self.buffer.drain(..offset);
+        Some(registry::decode_event(value).map_err(FormatError::Event))
+      },
+      Err(error) if error.is_eof() => {
+        if self.buffer.len() > MAX_FRAME_SIZE {
+          let size = self.buffer.len();
+          self.buffer.clear();
+          return Some(Err(FormatError::FrameTooLarge(size)));
+        }
+        None
+      },
+      Err(error) => {
+        self.buffer.clear();
+        Some(Err(FormatError::Decode(error.to_string())))
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::Value;
+  
+  use super::*;
+  use crate::events::docker::{DockerEvent, DockerStatusData};
+  
+  fn status(host: &str) -> Event {
+    Event::Docker(DockerEvent::DockerStatus {
+      data: DockerStatusData { status: Some(1), host: Some(host.to_string()) }
+    })
+  }
+  
+  fn json(event: &Event) -> Value {
+    serde_json::to_value(event).unwrap()
+  }
+  
+  fn format(encoding: Encoding, compression: Compression) -> Format {
+    Format { encoding, compression, ..Default::default() }
+  }
+  
+  #[test]
+  fn every_encoding_round_trips() {
+    let event = status("local");
+    for encoding in [Encoding::Json, Encoding::MessagePack, Encoding::Cbor] {
+      for compression in [Compression::None, Compression::Brotli] {
+        let format = format(encoding, compression);
+        let decoded = format.decode(&format.encode(&event).unwrap()).unwrap();
+        assert_eq!(json(&decoded), json(&event), "{:?} with {:?}", encoding, compression);
+      }
+    }
+  }
+  
+  #[test]
+  fn brotli_applies_above_the_threshold() {
+    let format = format(Encoding::MessagePack, Compression::Brotli);
+    
+    let small = format.encode(&status("local")).unwrap();
+    assert_eq!(small[0], 0);
+    
+    let event = status(&"a".repeat(4 * DEFAULT_COMPRESSION_THRESHOLD));
+    let large = format.encode(&event).unwrap();
+    assert_eq!(large[0], FLAG_BROTLI);
+    assert!(large.len() < format.serialize(&event).unwrap().len());
+    assert_eq!(json(&format.decode(&large).unwrap()), json(&event));
+  }
+  
+  #[test]
+  fn frames_split_across_reads_are_reassembled() {
+    for format in [Format::default(), format(Encoding::Cbor, Compression::Brotli)] {
+      let events = [status("first"), status(&"b".repeat(2 * DEFAULT_COMPRESSION_THRESHOLD))];
+      let bytes: Vec<u8> = events.iter().flat_map(|event| format.frame(event).unwrap()).collect();
+      
+      let mut reader = FrameReader::new(format);
+      let mut decoded = Vec::new();
+      for chunk in bytes.chunks(3) {
+        reader.push(chunk);
+        while let Some(event) = reader.next_event() {
+          decoded.push(json(&event.unwrap()));
+        }
+      }
+      
+      assert_eq!(decoded, events.iter().map(json).collect::<Vec<_>>(), "{:?}", format);
+    }
+  }
+  
+  #[test]
+  fn oversized_length_prefix_is_rejected() {
+    let mut reader = FrameReader::new(format(Encoding::MessagePack, Compression::None));
+    reader.push(&(MAX_FRAME_SIZE as u32 + 1).to_be_bytes());
+    reader.push(&[0]);
+    
+    assert!(matches!(reader.next_event(), Some(Err(FormatError::FrameTooLarge(size))) if size == MAX_FRAME_SIZE + 1));
+    assert!(reader.next_event().is_none());
+  }
+}
@@ -1,21 +1,61 @@
-use serde_json::{json, Value};
-use tokio::sync::broadcast;
+use serde::Serialize;
+use serde_json::{Map, Value};
 
-use crate::events::Event;
+use crate::{events::{docker::Projection, Event}, services::bus::EventBus};
 
 pub mod docker;
+pub mod format;
 
-pub fn create_event_dto(event: Event) -> String {
-  serde_json::to_string(&event).unwrap_or_else(|_| "".to_string())
-}
-
-pub trait EventDTO {
-  fn to_json(&self) -> Value;
+/// Payload sent trimmed to the fields a client asked for. Fields are dotted paths of the serialized shape.
+pub trait EventDTO: Serialize {
+  /// Fields returned whatever the selection
+  fn key_fields() -> &'static [&'static str] {
+    &[]
+  }
+  
+  /// Fields of a named projection, `None` for every field
+  fn projection(projection: Projection) -> Option<&'static [&'static str]>;
+  
+  /// Serializes the fields of `projection` and the extra `fields`, every field when neither is given
+  fn to_json(&self, projection: Option<Projection>, fields: Option<&[String]>) -> Value {
+    let value = match serde_json::to_value(self) {
+      Ok(value) => value,
+      Err(e) => {
+        tracing::error!("Failed to serialize payload: {:?}", e);
+        return Value::Null;
+      }
+    };
+    
+    let mut paths = match (projection.map(Self::projection), fields) {
+      (None, None) | (Some(None), _) => return value,
+      (Some(Some(paths)), _) => paths.to_vec(),
+      (None, Some(_)) => Vec::new()
+    };
+    paths.extend_from_slice(Self::key_fields());
+    paths.extend(fields.unwrap_or_default().iter().map(String::as_str));
+    
+    let mut selected = Value::Object(Map::new());
+    for path in paths {
+      copy_path(&value, &mut selected, &path.split('.').collect::<Vec<_>>());
+    }
+    selected
+  }
 }
 
-impl EventDTO for i8 {
-  fn to_json(&self) -> Value {
-    json!(*self)
+/// Copies the field at `path` of `source` into `target`, creating the intermediate objects. Missing fields are skipped.
+fn copy_path(source: &Value, target: &mut Value, path: &[&str]) {
+  let Some((key, rest)) = path.split_first() else {
+    return;
+  };
+  let (Some(field), Some(target)) = (source.get(*key), target.as_object_mut()) else {
+    return;
+  };
+  
+  if rest.is_empty() {
+    target.insert(key.to_string(), field.clone());
+  } else if field.is_object() {
+    let entry = target.entry(key.to_string()).or_insert_with(|| Value::Object(Map::new()));
+    copy_path(field, entry, rest);
   }
 }
 
@@ -23,18 +63,8 @@ pub trait SendEvent {
   async fn send_event(&mut self, event: Event);
 }
 
-impl SendEvent for wtransport::SendStream {
+impl SendEvent for EventBus {
   async fn send_event(&mut self, event: Event) {
-    if let Err(error) = self.write_all(create_event_dto(event).as_bytes()).await {
-      log::error!("Failed to send event: {:?}", error);
-    }
+    self.publish(event);
   }
 }
-
-impl SendEvent for broadcast::Sender<String> {
-  async fn send_event(&mut self, event: Event) {
-    if let Err(error) = self.send(create_event_dto(event)) {
-      log::error!("Failed to send event: {:?}", error);
-    }
-  }
-}
\ No newline at end of file
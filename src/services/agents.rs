@@ -0,0 +1,211 @@
+use std::{collections::HashMap, sync::{atomic::{AtomicU64, Ordering}, Arc, LazyLock, Mutex, RwLock}, time::Duration};
+
+use async_trait::async_trait;
+use bollard::{container::Stats, errors::Error, secret::{ContainerInspectResponse, ContainerSummary, EventMessage}};
+use futures::{stream::{self, BoxStream}, StreamExt};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{config, events::{agent::{AgentCall, AgentEvent, AgentRequestData, AgentResponseData, AgentResult}, docker::ContainerFilters, Event}, services::backend::{DockerBackend, ExecOutput}};
+
+/// Time an agent has to answer a proxied call
+const CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Agents connected to this hub, by host ID
+static AGENTS: LazyLock<RwLock<HashMap<String, Arc<AgentHandle>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Hub side of a connected agent: requests are queued to its stream and matched with
+/// the responses through their request ID
+pub struct AgentHandle {
+    host_id: String,
+    requests: mpsc::UnboundedSender<Event>,
+    pending: Mutex<HashMap<u64, oneshot::Sender<AgentResult>>>,
+    next_id: AtomicU64
+}
+
+/// Checks the token presented by an agent against `agentToken`
+pub fn authorize(token: Option<&str>) -> bool {
+    match (&config::get().agent_token, token) {
+        (Some(expected), Some(token)) => expected == token,
+        _ => false
+    }
+}
+
+/// Registers an agent, replacing a previous connection with the same host ID.
+/// Requests for the agent are sent through `requests`.
+pub fn register(host_id: String, requests: mpsc::UnboundedSender<Event>) -> Arc<AgentHandle> {
+    let handle = Arc::new(AgentHandle {
+        host_id: host_id.clone(),
+        requests,
+        pending: Mutex::new(HashMap::new()),
+        next_id: AtomicU64::new(1)
+    });
+    
+    if let Some(previous) = AGENTS.write().unwrap().insert(host_id, handle.clone()) {
+        previous.fail_pending();
+    }
+    
+    handle
+}
+
+/// Removes an agent whose stream closed, unless it already reconnected
+pub fn unregister(handle: &Arc<AgentHandle>) {
+    let mut agents = AGENTS.write().unwrap();
+    if agents.get(&handle.host_id).is_some_and(|current| Arc::ptr_eq(current, handle)) {
+        agents.remove(&handle.host_id);
+    }
+    drop(agents);
+    
+    handle.fail_pending();
+}
+
+pub fn get(host_id: &str) -> Option<Arc<AgentHandle>> {
+    AGENTS.read().unwrap().get(host_id).cloned()
+}
+
+/// Host IDs of the connected agents, sorted
+pub fn names() -> Vec<String> {
+    let mut names: Vec<String> = AGENTS.read().unwrap().keys().cloned().collect();
+    names.sort();
+    names
+}
+
+impl AgentHandle {
+    pub fn host_id(&self) -> &str {
+        &self.host_id
+    }
+    
+    /// Hands the response of the agent to the pending call
+    pub fn complete(&self, response: AgentResponseData) {
+        match self.pending.lock().unwrap().remove(&response.request_id) {
+            Some(sender) => {
+                let _ = sender.send(response.result);
+            },
+            None => tracing::warn!(host = self.host_id, request_id = response.request_id, "Received response for an unknown agent request")
+        }
+    }
+    
+    /// Drops the pending calls so that their callers fail right away
+    fn fail_pending(&self) {
+        self.pending.lock().unwrap().clear();
+    }
+    
+    async fn call(&self, call: AgentCall) -> Result<AgentResult, Error> {
+        let request_id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(request_id, sender);
+        
+        let request = Event::Agent(AgentEvent::AgentRequest {
+            data: AgentRequestData { request_id, call }
+        });
+        if self.requests.send(request).is_err() {
+            self.pending.lock().unwrap().remove(&request_id);
+            return Err(disconnected(&self.host_id));
+        }
+        
+        match tokio::time::timeout(CALL_TIMEOUT, receiver).await {
+            Ok(Ok(AgentResult::Error { status_code: Some(status_code), message })) => Err(Error::DockerResponseServerError { status_code, message }),
+            Ok(Ok(AgentResult::Error { status_code: None, message })) => Err(Error::IOError {
+                err: std::io::Error::other(message)
+            }),
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(_)) => Err(disconnected(&self.host_id)),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&request_id);
+                Err(Error::IOError {
+                    err: std::io::Error::new(std::io::ErrorKind::TimedOut, format!("Agent {:?} did not answer within {:?}", self.host_id, CALL_TIMEOUT))
+                })
+            }
+        }
+    }
+}
+
+fn disconnected(host_id: &str) -> Error {
+    Error::IOError {
+        err: std::io::Error::new(std::io::ErrorKind::NotConnected, format!("Agent {:?} disconnected", host_id))
+    }
+}
+
+fn unexpected(result: AgentResult) -> Error {
+    Error::DockerStreamError {
+        error: format!("Unexpected agent result: {:?}", result)
+    }
+}
+
+#[async_trait]
+impl DockerBackend for AgentHandle {
+    async fn ping(&self) -> Result<(), Error> {
+        match self.call(AgentCall::Ping).await? {
+            AgentResult::Done => Ok(()),
+            result => Err(unexpected(result))
+        }
+    }
+    
+    /// Agents list every container, the filters apply here
+    async fn list_containers(&self, filters: &ContainerFilters) -> Result<Vec<ContainerSummary>, Error> {
+        match self.call(AgentCall::ListContainers).await? {
+            AgentResult::Containers(containers) => Ok(containers.into_iter().filter(|container| filters.matches(container)).collect()),
+            result => Err(unexpected(result))
+        }
+    }
+    
+    async fn inspect_container(&self, id: &str) -> Result<ContainerInspectResponse, Error> {
+        match self.call(AgentCall::InspectContainer { id: id.to_string() }).await? {
+            AgentResult::Container(container) => Ok(*container),
+            result => Err(unexpected(result))
+        }
+    }
+    
+    async fn start_container(&self, id: &str) -> Result<(), Error> {
+        match self.call(AgentCall::StartContainer { id: id.to_string() }).await? {
+            AgentResult::Done => Ok(()),
+            result => Err(unexpected(result))
+        }
+    }
+    
+    async fn stop_container(&self, id: &str) -> Result<(), Error> {
+        match self.call(AgentCall::StopContainer { id: id.to_string() }).await? {
+            AgentResult::Done => Ok(()),
+            result => Err(unexpected(result))
+        }
+    }
+    
+    async fn restart_container(&self, id: &str) -> Result<(), Error> {
+        match self.call(AgentCall::RestartContainer { id: id.to_string() }).await? {
+            AgentResult::Done => Ok(()),
+            result => Err(unexpected(result))
+        }
+    }
+    
+    async fn pause_container(&self, id: &str) -> Result<(), Error> {
+        match self.call(AgentCall::PauseContainer { id: id.to_string() }).await? {
+            AgentResult::Done => Ok(()),
+            result => Err(unexpected(result))
+        }
+    }
+    
+    async fn remove_container(&self, id: &str) -> Result<(), Error> {
+        match self.call(AgentCall::RemoveContainer { id: id.to_string() }).await? {
+            AgentResult::Done => Ok(()),
+            result => Err(unexpected(result))
+        }
+    }
+    
+    async fn container_stats(&self, id: &str, one_shot: bool) -> Result<Stats, Error> {
+        match self.call(AgentCall::ContainerStats { id: id.to_string(), one_shot }).await? {
+            AgentResult::Stats(stats) => Ok(*stats),
+            result => Err(unexpected(result))
+        }
+    }
+    
+    async fn exec_container(&self, id: &str, command: Vec<String>) -> Result<ExecOutput, Error> {
+        match self.call(AgentCall::ExecContainer { id: id.to_string(), command }).await? {
+            AgentResult::Exec(output) => Ok(output),
+            result => Err(unexpected(result))
+        }
+    }
+    
+    /// Agents push their events over the hub connection instead
+    fn events(&self) -> BoxStream<'static, Result<EventMessage, Error>> {
+        stream::empty().boxed()
+    }
+}
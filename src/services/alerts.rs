@@ -0,0 +1,512 @@
+use std::{collections::{HashMap, VecDeque}, sync::{LazyLock, Mutex}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use bollard::secret::{EventMessage, EventMessageTypeEnum};
+use futures::future::join_all;
+
+use crate::{config::{self, AlertCondition, AlertRule}, events::{alert::{Alert, AlertEvent, AlertState}, docker::DockerContainerStatsData, Event}, serializers::SendEvent, services::{bus::EventBus, docker, hosts, store, telemetry}};
+
+/// Resolved alerts returned by `AlertList`
+const RESOLVED_HISTORY: usize = 100;
+
+/// A die event following a stop or kill request this closely is not a crash
+const KILL_GRACE: Duration = Duration::from_secs(30);
+
+static ENGINE: LazyLock<Mutex<Engine>> = LazyLock::new(|| Mutex::new(Engine::default()));
+
+/// Container identified by its host and ID
+type ContainerKey = (String, String);
+
+#[derive(Default)]
+struct Engine {
+    firing: HashMap<String, Alert>,
+    starts: HashMap<ContainerKey, VecDeque<Instant>>,
+    killed: HashMap<ContainerKey, Instant>,
+    memory_since: HashMap<(String, ContainerKey), Instant>
+}
+
+/// Container an alert is about, `None` for host-wide alerts
+struct Target<'a> {
+    host: &'a str,
+    id: Option<&'a str>,
+    name: Option<&'a str>
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default()
+}
+
+fn rules() -> &'static [AlertRule] {
+    &config::get().alerts.rules
+}
+
+impl Engine {
+    /// Returns the alert when it was not already firing
+    fn fire(&mut self, rule: &AlertRule, target: Target, message: String) -> Option<Alert> {
+        let id = alert_id(&rule.name, target.host, target.id);
+        if self.firing.contains_key(&id) {
+            return None;
+        }
+        
+        let alert = Alert {
+            id: id.clone(),
+            rule: rule.name.clone(),
+            state: AlertState::Firing,
+            host: target.host.to_string(),
+            container_id: target.id.map(str::to_string),
+            container_name: target.name.map(str::to_string),
+            message,
+            fired_at: now(),
+            resolved_at: None
+        };
+        self.firing.insert(id, alert.clone());
+        Some(alert)
+    }
+    
+    /// Returns the alert when it was firing
+    fn resolve(&mut self, rule: &AlertRule, host: &str, container: Option<&str>) -> Option<Alert> {
+        let mut alert = self.firing.remove(&alert_id(&rule.name, host, container))?;
+        alert.state = AlertState::Resolved;
+        alert.resolved_at = Some(now());
+        Some(alert)
+    }
+    
+    /// Resolves every alert of a container that no longer exists
+    fn forget(&mut self, rules: &[AlertRule], host: &str, container: &str) -> Vec<Alert> {
+        let key = (host.to_string(), container.to_string());
+        self.starts.remove(&key);
+        self.killed.remove(&key);
+        self.memory_since.retain(|(_, memory_key), _| *memory_key != key);
+        
+        rules.iter()
+            .filter_map(|rule| self.resolve(rule, host, Some(container)))
+            .collect()
+    }
+    
+    /// Alerts fired or resolved by a raw container event of a host, received `at`
+    fn observe(&mut self, rules: &[AlertRule], host: &str, event: &EventMessage, at: Instant) -> Vec<Alert> {
+        let mut changes = Vec::new();
+        if event.typ != Some(EventMessageTypeEnum::CONTAINER) {
+            return changes;
+        }
+        
+        let (Some(action), Some(actor)) = (event.action.as_deref(), event.actor.as_ref()) else {
+            return changes;
+        };
+        let Some(id) = actor.id.as_deref() else {
+            return changes;
+        };
+        let attribute = |key: &str| actor.attributes.as_ref().and_then(|attributes| attributes.get(key)).map(String::as_str);
+        let target = || Target { host, id: Some(id), name: attribute("name") };
+        let key = (host.to_string(), id.to_string());
+        
+        match action {
+            "kill" | "stop" => {
+                self.killed.insert(key, at);
+            },
+            "destroy" => changes.extend(self.forget(rules, host, id)),
+            "die" => {
+                let exit_code = attribute("exitCode").unwrap_or("0");
+                let requested = self.killed.remove(&key).is_some_and(|killed_at| at.saturating_duration_since(killed_at) < KILL_GRACE);
+                
+                for rule in rules {
+                    match rule.condition {
+                        AlertCondition::ExitCode if exit_code != "0" && !requested => {
+                            changes.extend(self.fire(rule, target(), format!("Container exited with code {}", exit_code)));
+                        },
+                        AlertCondition::Memory { .. } | AlertCondition::Unhealthy => {
+                            self.memory_since.remove(&(rule.name.clone(), key.clone()));
+                            changes.extend(self.resolve(rule, host, Some(id)));
+                        },
+                        _ => {}
+                    }
+                }
+            },
+            "start" => {
+                // Only tracked for the restart rules, which also expire them
+                let starts = match rules.iter().any(|rule| matches!(rule.condition, AlertCondition::Restarts { .. })) {
+                    true => {
+                        let starts = self.starts.entry(key).or_default();
+                        starts.push_back(at);
+                        starts.clone()
+                    },
+                    false => VecDeque::new()
+                };
+                
+                for rule in rules {
+                    match rule.condition {
+                        AlertCondition::ExitCode => changes.extend(self.resolve(rule, host, Some(id))),
+                        AlertCondition::Restarts { count, window } => {
+                            let window = Duration::from_secs(window);
+                            let recent = starts.iter().filter(|started_at| at.saturating_duration_since(**started_at) <= window).count();
+                            if recent > count {
+                                changes.extend(self.fire(rule, target(), format!("Container started {} times in {:?}", recent, window)));
+                            }
+                        },
+                        _ => {}
+                    }
+                }
+            },
+            "health_status: unhealthy" => {
+                for rule in rules.iter().filter(|rule| matches!(rule.condition, AlertCondition::Unhealthy)) {
+                    changes.extend(self.fire(rule, target(), "Container health check is failing".to_string()));
+                }
+            },
+            "health_status: healthy" => {
+                for rule in rules.iter().filter(|rule| matches!(rule.condition, AlertCondition::Unhealthy)) {
+                    changes.extend(self.resolve(rule, host, Some(id)));
+                }
+            },
+            _ => {}
+        }
+        
+        changes
+    }
+    
+    /// Alerts fired or resolved by the daemon pings of the hosts and by the restart windows expiring, probed `at`
+    fn probe(&mut self, rules: &[AlertRule], hosts: &[String], pings: &[i8], at: Instant) -> Vec<Alert> {
+        let mut changes = Vec::new();
+        
+        let longest_window = rules.iter()
+            .filter_map(|rule| match rule.condition {
+                AlertCondition::Restarts { window, .. } => Some(Duration::from_secs(window)),
+                _ => None
+            })
+            .max()
+            .unwrap_or_default();
+        self.starts.retain(|_, starts| {
+            starts.retain(|started_at| at.saturating_duration_since(*started_at) <= longest_window);
+            !starts.is_empty()
+        });
+        
+        for rule in rules {
+            match rule.condition {
+                AlertCondition::DaemonUnreachable => {
+                    for (host, status) in hosts.iter().zip(pings) {
+                        match status {
+                            1 => changes.extend(self.resolve(rule, host, None)),
+                            0 => changes.extend(self.fire(rule, Target { host, id: None, name: None }, "Cannot connect to the Docker daemon".to_string())),
+                            _ => changes.extend(self.fire(rule, Target { host, id: None, name: None }, "Docker daemon does not answer pings".to_string()))
+                        }
+                    }
+                },
+                AlertCondition::Restarts { count, window } => {
+                    let window = Duration::from_secs(window);
+                    let quiet: Vec<ContainerKey> = self.firing.values()
+                        .filter(|alert| alert.rule == rule.name)
+                        .filter_map(|alert| Some((alert.host.clone(), alert.container_id.clone()?)))
+                        .filter(|key| self.starts.get(key).map_or(0, |starts| starts.iter().filter(|started_at| at.saturating_duration_since(**started_at) <= window).count()) <= count)
+                        .collect();
+                    for (host, id) in quiet {
+                        changes.extend(self.resolve(rule, &host, Some(&id)));
+                    }
+                },
+                _ => {}
+            }
+        }
+        
+        changes
+    }
+    
+    /// Alerts fired or resolved by the memory usage of containers, sampled `at`
+    fn sample(&mut self, rules: &[AlertRule], samples: &[DockerContainerStatsData], at: Instant) -> Vec<Alert> {
+        let mut changes = Vec::new();
+        
+        for stats in samples {
+            let Some(host) = stats.host.as_deref() else {
+                continue;
+            };
+            let (Some(id), Some(usage), Some(limit)) = (stats.container_id.as_deref(), stats.memory_usage, stats.memory_limit) else {
+                continue;
+            };
+            if limit == 0 {
+                continue;
+            }
+            let used = usage as f64 / limit as f64 * 100.0;
+            
+            for rule in rules {
+                let AlertCondition::Memory { percent, duration } = rule.condition else {
+                    continue;
+                };
+                let key = (rule.name.clone(), (host.to_string(), id.to_string()));
+                
+                if used > percent {
+                    let since = *self.memory_since.entry(key).or_insert(at);
+                    if at.saturating_duration_since(since) >= Duration::from_secs(duration) {
+                        let target = Target { host, id: Some(id), name: stats.name.as_deref() };
+                        changes.extend(self.fire(rule, target, format!("Memory usage at {:.1}% of the limit", used)));
+                    }
+                } else {
+                    self.memory_since.remove(&key);
+                    changes.extend(self.resolve(rule, host, Some(id)));
+                }
+            }
+        }
+        
+        changes
+    }
+}
+
+fn alert_id(rule: &str, host: &str, container: Option<&str>) -> String {
+    match container {
+        Some(container) => format!("{}/{}/{}", rule, host, container),
+        None => format!("{}/{}", rule, host)
+    }
+}
+
+/// Firing alerts, followed by the recently resolved ones from the history when asked
+pub async fn list(include_resolved: bool) -> Vec<Alert> {
+    let mut alerts: Vec<Alert> = ENGINE.lock().unwrap().firing.values().cloned().collect();
+    alerts.sort_by_key(|alert| alert.fired_at);
+    
+    if include_resolved {
+        match store::alerts::resolved(RESOLVED_HISTORY).await {
+            Ok(resolved) => alerts.extend(resolved),
+            Err(e) => tracing::error!("Failed to read alert history: {:?}", e)
+        }
+    }
+    
+    alerts
+}
+
+async fn publish(bus: &mut EventBus, changes: Vec<Alert>) {
+    for alert in changes {
+        match alert.state {
+            AlertState::Firing => tracing::warn!(host = alert.host, alert = alert.id, "Alert firing: {}", alert.message),
+            AlertState::Resolved => tracing::info!(host = alert.host, alert = alert.id, "Alert resolved")
+        }
+        if let Err(e) = store::alerts::record(&alert).await {
+            tracing::error!(alert = alert.id, "Failed to record alert history: {:?}", e);
+        }
+        bus.send_event(Event::Alert(AlertEvent::AlertChanged { data: alert })).await;
+    }
+}
+
+/// Evaluates the event based rules against a raw container event of a host
+pub async fn observe_docker_event(bus: &mut EventBus, host: &str, event: &EventMessage) {
+    let changes = ENGINE.lock().unwrap().observe(rules(), host, event, Instant::now());
+    publish(bus, changes).await;
+}
+
+/// Periodically evaluates the rules needing a probe: daemon reachability, memory usage,
+/// and the expiry of restart windows
+pub async fn watch_alerts(mut bus: EventBus) {
+    let alerts = &config::get().alerts;
+    if alerts.rules.is_empty() {
+        return;
+    }
+    
+    let mut interval = tokio::time::interval(Duration::from_secs(alerts.interval.max(1)));
+    
+    loop {
+        interval.tick().await;
+        
+        let hosts = hosts::names();
+        let pings = join_all(hosts.iter().map(|host| docker::ping(Some(host)))).await;
+        let mut changes = ENGINE.lock().unwrap().probe(rules(), &hosts, &pings, Instant::now());
+        
+        if rules().iter().any(|rule| matches!(rule.condition, AlertCondition::Memory { .. })) {
+            match telemetry::sample_containers().await {
+                Ok(samples) => changes.extend(ENGINE.lock().unwrap().sample(rules(), &samples, Instant::now())),
+                Err(e) => tracing::error!("Failed to list containers for alerting: {:?}", e)
+            }
+        }
+        
+        publish(&mut bus, changes).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bollard::secret::EventActor;
+    
+    use super::*;
+    
+    const HOST: &str = "local";
+    const ID: &str = "c0ffee";
+    
+    fn rule(name: &str, condition: AlertCondition) -> AlertRule {
+        AlertRule { name: name.to_string(), condition }
+    }
+    
+    fn event(action: &str, attributes: &[(&str, &str)]) -> EventMessage {
+        let mut attributes: HashMap<String, String> = attributes.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        attributes.insert("name".to_string(), "web".to_string());
+        
+        EventMessage {
+            typ: Some(EventMessageTypeEnum::CONTAINER),
+            action: Some(action.to_string()),
+            actor: Some(EventActor { id: Some(ID.to_string()), attributes: Some(attributes) }),
+            ..Default::default()
+        }
+    }
+    
+    fn memory(usage: u64) -> DockerContainerStatsData {
+        DockerContainerStatsData {
+            container_id: Some(ID.to_string()),
+            name: Some("web".to_string()),
+            cpu_percent: None,
+            memory_usage: Some(usage),
+            memory_limit: Some(100),
+            network_rx: None,
+            network_tx: None,
+            host: Some(HOST.to_string())
+        }
+    }
+    
+    fn states(changes: &[Alert]) -> Vec<(&str, AlertState)> {
+        changes.iter().map(|alert| (alert.rule.as_str(), alert.state)).collect()
+    }
+    
+    #[test]
+    fn crashes_fire_until_the_container_starts_again() {
+        let rules = [rule("exitCode", AlertCondition::ExitCode)];
+        let mut engine = Engine::default();
+        let at = Instant::now();
+        
+        let fired = engine.observe(&rules, HOST, &event("die", &[("exitCode", "137")]), at);
+        assert_eq!(states(&fired), [("exitCode", AlertState::Firing)]);
+        assert_eq!(fired[0].id, "exitCode/local/c0ffee");
+        assert_eq!(fired[0].container_name.as_deref(), Some("web"));
+        assert_eq!(fired[0].message, "Container exited with code 137");
+        
+        assert!(engine.observe(&rules, HOST, &event("die", &[("exitCode", "1")]), at).is_empty(), "a firing alert is not fired twice");
+        
+        let resolved = engine.observe(&rules, HOST, &event("start", &[]), at);
+        assert_eq!(states(&resolved), [("exitCode", AlertState::Resolved)]);
+        assert!(resolved[0].resolved_at.is_some());
+        assert!(engine.firing.is_empty());
+    }
+    
+    #[test]
+    fn clean_exits_and_requested_stops_are_not_crashes() {
+        let rules = [rule("exitCode", AlertCondition::ExitCode)];
+        let mut engine = Engine::default();
+        let at = Instant::now();
+        
+        assert!(engine.observe(&rules, HOST, &event("die", &[("exitCode", "0")]), at).is_empty());
+        
+        engine.observe(&rules, HOST, &event("kill", &[]), at);
+        assert!(engine.observe(&rules, HOST, &event("die", &[("exitCode", "137")]), at + Duration::from_secs(10)).is_empty());
+        
+        // The grace covers the next die only
+        let fired = engine.observe(&rules, HOST, &event("die", &[("exitCode", "137")]), at + Duration::from_secs(11));
+        assert_eq!(states(&fired), [("exitCode", AlertState::Firing)]);
+    }
+    
+    #[test]
+    fn dies_long_after_a_stop_request_are_crashes() {
+        let rules = [rule("exitCode", AlertCondition::ExitCode)];
+        let mut engine = Engine::default();
+        let at = Instant::now();
+        
+        engine.observe(&rules, HOST, &event("stop", &[]), at);
+        let fired = engine.observe(&rules, HOST, &event("die", &[("exitCode", "1")]), at + KILL_GRACE);
+        assert_eq!(states(&fired), [("exitCode", AlertState::Firing)]);
+    }
+    
+    #[test]
+    fn restarts_fire_above_the_count_within_the_window() {
+        let rules = [rule("restarts", AlertCondition::Restarts { count: 2, window: 60 })];
+        let mut engine = Engine::default();
+        let at = Instant::now();
+        let start = |engine: &mut Engine, seconds| engine.observe(&rules, HOST, &event("start", &[]), at + Duration::from_secs(seconds));
+        
+        assert!(start(&mut engine, 0).is_empty());
+        assert!(start(&mut engine, 10).is_empty());
+        // The first start left the window
+        assert!(start(&mut engine, 61).is_empty());
+        
+        let fired = start(&mut engine, 65);
+        assert_eq!(states(&fired), [("restarts", AlertState::Firing)]);
+        assert_eq!(fired[0].message, "Container started 3 times in 60s");
+        
+        let hosts = [HOST.to_string()];
+        assert!(engine.probe(&rules, &hosts, &[1], at + Duration::from_secs(70)).is_empty(), "still restarting");
+        let resolved = engine.probe(&rules, &hosts, &[1], at + Duration::from_secs(126));
+        assert_eq!(states(&resolved), [("restarts", AlertState::Resolved)]);
+        assert!(engine.starts.is_empty(), "expired starts are dropped");
+    }
+    
+    #[test]
+    fn health_checks_fire_and_resolve() {
+        let rules = [rule("unhealthy", AlertCondition::Unhealthy)];
+        let mut engine = Engine::default();
+        let at = Instant::now();
+        
+        let fired = engine.observe(&rules, HOST, &event("health_status: unhealthy", &[]), at);
+        assert_eq!(states(&fired), [("unhealthy", AlertState::Firing)]);
+        let resolved = engine.observe(&rules, HOST, &event("health_status: healthy", &[]), at);
+        assert_eq!(states(&resolved), [("unhealthy", AlertState::Resolved)]);
+        
+        engine.observe(&rules, HOST, &event("health_status: unhealthy", &[]), at);
+        let resolved = engine.observe(&rules, HOST, &event("die", &[("exitCode", "0")]), at);
+        assert_eq!(states(&resolved), [("unhealthy", AlertState::Resolved)], "a stopped container is not unhealthy");
+    }
+    
+    #[test]
+    fn destroyed_containers_resolve_their_alerts() {
+        let rules = [rule("exitCode", AlertCondition::ExitCode), rule("unhealthy", AlertCondition::Unhealthy)];
+        let mut engine = Engine::default();
+        let at = Instant::now();
+        
+        engine.observe(&rules, HOST, &event("health_status: unhealthy", &[]), at);
+        engine.observe(&rules, HOST, &event("die", &[("exitCode", "2")]), at);
+        // The die resolved the health alert already
+        assert_eq!(states(&engine.firing.values().cloned().collect::<Vec<_>>()), [("exitCode", AlertState::Firing)]);
+        
+        let resolved = engine.observe(&rules, HOST, &event("destroy", &[]), at);
+        assert_eq!(states(&resolved), [("exitCode", AlertState::Resolved)]);
+        assert!(engine.firing.is_empty());
+    }
+    
+    #[test]
+    fn memory_fires_once_above_the_threshold_for_the_duration() {
+        let rules = [rule("memory", AlertCondition::Memory { percent: 90.0, duration: 300 })];
+        let mut engine = Engine::default();
+        let at = Instant::now();
+        
+        assert!(engine.sample(&rules, &[memory(95)], at).is_empty());
+        assert!(engine.sample(&rules, &[memory(95)], at + Duration::from_secs(299)).is_empty());
+        
+        let fired = engine.sample(&rules, &[memory(95)], at + Duration::from_secs(300));
+        assert_eq!(states(&fired), [("memory", AlertState::Firing)]);
+        assert_eq!(fired[0].message, "Memory usage at 95.0% of the limit");
+        
+        let resolved = engine.sample(&rules, &[memory(50)], at + Duration::from_secs(301));
+        assert_eq!(states(&resolved), [("memory", AlertState::Resolved)]);
+        
+        // Dropping below the threshold restarts the duration
+        assert!(engine.sample(&rules, &[memory(95)], at + Duration::from_secs(302)).is_empty());
+        assert!(engine.sample(&rules, &[memory(50)], at + Duration::from_secs(400)).is_empty());
+        assert!(engine.sample(&rules, &[memory(95)], at + Duration::from_secs(610)).is_empty());
+    }
+    
+    #[test]
+    fn memory_without_a_limit_is_ignored() {
+        let rules = [rule("memory", AlertCondition::Memory { percent: 90.0, duration: 0 })];
+        let mut engine = Engine::default();
+        let mut unlimited = memory(95);
+        unlimited.memory_limit = Some(0);
+        
+        assert!(engine.sample(&rules, &[unlimited], Instant::now()).is_empty());
+        assert_eq!(states(&engine.sample(&rules, &[memory(95)], Instant::now())), [("memory", AlertState::Firing)]);
+    }
+    
+    #[test]
+    fn unreachable_daemons_fire_per_host() {
+        let rules = [rule("daemonUnreachable", AlertCondition::DaemonUnreachable)];
+        let mut engine = Engine::default();
+        let hosts = ["up".to_string(), "down".to_string(), "stuck".to_string()];
+        let at = Instant::now();
+        
+        let fired = engine.probe(&rules, &hosts, &[1, 0, 2], at);
+        let messages: Vec<(&str, &str)> = fired.iter().map(|alert| (alert.id.as_str(), alert.message.as_str())).collect();
+        assert_eq!(messages, [
+            ("daemonUnreachable/down", "Cannot connect to the Docker daemon"),
+            ("daemonUnreachable/stuck", "Docker daemon does not answer pings")
+        ]);
+        
+        let resolved = engine.probe(&rules, &hosts, &[1, 1, 2], at);
+        assert_eq!(resolved.iter().map(|alert| alert.id.as_str()).collect::<Vec<_>>(), ["daemonUnreachable/down"]);
+    }
+}
@@ -0,0 +1,30 @@
+use crate::{config, events::system::Identity};
+
+const ANONYMOUS: &str = "anonymous";
+
+/// Identity of a stream that presented no token
+pub fn anonymous() -> Identity {
+    Identity {
+        name: ANONYMOUS.to_string(),
+        role: config::get().anonymous_role,
+        authenticated: false
+    }
+}
+
+/// Identity of the configured client called `name`, as if it authenticated
+pub fn client(name: &str) -> Option<Identity> {
+    config::get().clients.iter()
+        .find(|client| client.name == name)
+        .map(|client| Identity { name: client.name.clone(), role: client.role, authenticated: true })
+}
+
+/// Identity of the client owning `token`, anonymous without one, `None` for an unknown token
+pub fn authenticate(token: Option<&str>) -> Option<Identity> {
+    let Some(token) = token else {
+        return Some(anonymous());
+    };
+    
+    config::get().clients.iter()
+        .find(|client| client.token == token)
+        .map(|client| Identity { name: client.name.clone(), role: client.role, authenticated: true })
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use bollard::{container::{ListContainersOptions, RemoveContainerOptions, StartContainerOptions, Stats, StatsOptions}, errors::Error, exec::{CreateExecOptions, StartExecResults}, secret::{ContainerInspectResponse, ContainerSummary, EventMessage}, system::EventsOptions, Docker};
+use futures::{stream::BoxStream, StreamExt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::events::docker::{ContainerFilters, COMPOSE_PROJECT_LABEL};
+
+/// Output kept from a command run in a container, the rest is dropped
+const MAX_EXEC_OUTPUT: usize = 64 * 1024;
+
+/// Result of a command run in a container
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecOutput {
+    pub exit_code: Option<i64>,
+    
+    /// Interleaved stdout and stderr, truncated to 64 KiB
+    pub output: String
+}
+
+/// Docker operations the API relies on, implemented by the bollard client of a reachable host
+/// and by the proxy of a connected agent
+#[async_trait]
+pub trait DockerBackend: Send + Sync {
+    async fn ping(&self) -> Result<(), Error>;
+    
+    /// Every container, stopped ones included, matching `filters`
+    async fn list_containers(&self, filters: &ContainerFilters) -> Result<Vec<ContainerSummary>, Error>;
+    
+    async fn inspect_container(&self, id: &str) -> Result<ContainerInspectResponse, Error>;
+    
+    async fn start_container(&self, id: &str) -> Result<(), Error>;
+    
+    async fn stop_container(&self, id: &str) -> Result<(), Error>;
+    
+    async fn restart_container(&self, id: &str) -> Result<(), Error>;
+    
+    async fn pause_container(&self, id: &str) -> Result<(), Error>;
+    
+    /// Removes a stopped container, its volumes are kept
+    async fn remove_container(&self, id: &str) -> Result<(), Error>;
+    
+    /// A `one_shot` sample leaves `precpu_stats` empty but answers at once, the other waits for a second sample
+    async fn container_stats(&self, id: &str, one_shot: bool) -> Result<Stats, Error>;
+    
+    async fn exec_container(&self, id: &str, command: Vec<String>) -> Result<ExecOutput, Error>;
+    
+    /// Raw events of the host, the stream ends when the connection drops
+    fn events(&self) -> BoxStream<'static, Result<EventMessage, Error>>;
+}
+
+#[async_trait]
+impl DockerBackend for Docker {
+    async fn ping(&self) -> Result<(), Error> {
+        Docker::ping(self).await.map(|_| ())
+    }
+    
+    async fn list_containers(&self, filters: &ContainerFilters) -> Result<Vec<ContainerSummary>, Error> {
+        // Docker ANDs labels, several compose projects are matched here instead
+        let mut labels = filters.label.clone();
+        if let [project] = filters.project.as_slice() {
+            labels.push(format!("{}={}", COMPOSE_PROJECT_LABEL, project));
+        }
+        
+        let options = Some(ListContainersOptions::<String> {
+            all: true,
+            filters: HashMap::from([
+                ("status".to_string(), filters.status.clone()),
+                ("label".to_string(), labels),
+                ("name".to_string(), filters.name.clone()),
+                ("ancestor".to_string(), filters.ancestor.clone()),
+                ("network".to_string(), filters.network.clone())
+            ]).into_iter().filter(|(_, values)| !values.is_empty()).collect(),
+            ..Default::default()
+        });
+        
+        let mut containers = Docker::list_containers(self, options).await?;
+        if filters.project.len() > 1 {
+            containers.retain(|container| filters.matches_project(container));
+        }
+        Ok(containers)
+    }
+    
+    async fn inspect_container(&self, id: &str) -> Result<ContainerInspectResponse, Error> {
+        Docker::inspect_container(self, id, None).await
+    }
+    
+    async fn start_container(&self, id: &str) -> Result<(), Error> {
+        Docker::start_container(self, id, None::<StartContainerOptions<String>>).await
+    }
+    
+    async fn stop_container(&self, id: &str) -> Result<(), Error> {
+        Docker::stop_container(self, id, None).await
+    }
+    
+    async fn restart_container(&self, id: &str) -> Result<(), Error> {
+        Docker::restart_container(self, id, None).await
+    }
+    
+    async fn pause_container(&self, id: &str) -> Result<(), Error> {
+        Docker::pause_container(self, id).await
+    }
+    
+    async fn remove_container(&self, id: &str) -> Result<(), Error> {
+        Docker::remove_container(self, id, None::<RemoveContainerOptions>).await
+    }
+    
+    async fn container_stats(&self, id: &str, one_shot: bool) -> Result<Stats, Error> {
+        let options = Some(StatsOptions {
+            stream: false,
+            one_shot
+        });
+        
+        match self.stats(id, options).next().await {
+            Some(stats) => stats,
+            None => Err(Error::DockerStreamError { error: format!("No stats received for container {}", id) })
+        }
+    }
+    
+    async fn exec_container(&self, id: &str, command: Vec<String>) -> Result<ExecOutput, Error> {
+        let options = CreateExecOptions {
+            cmd: Some(command),
+            attach_stdout: Some(true),
+            attach_stderr: Some(true),
+            ..Default::default()
+        };
+        let exec = self.create_exec(id, options).await?;
+        
+        let mut output = String::new();
+        if let StartExecResults::Attached { output: mut stream, .. } = self.start_exec(&exec.id, None).await? {
+            while let Some(chunk) = stream.next().await {
+                if output.len() < MAX_EXEC_OUTPUT {
+                    output.push_str(&chunk?.to_string());
+                }
+            }
+        }
+        if output.len() > MAX_EXEC_OUTPUT {
+            let mut end = MAX_EXEC_OUTPUT;
+            while !output.is_char_boundary(end) {
+                end -= 1;
+            }
+            output.truncate(end);
+        }
+        
+        let inspect = self.inspect_exec(&exec.id).await?;
+        Ok(ExecOutput {
+            exit_code: inspect.exit_code,
+            output
+        })
+    }
+    
+    fn events(&self) -> BoxStream<'static, Result<EventMessage, Error>> {
+        Docker::events(self, Some(EventsOptions::<String>::default())).boxed()
+    }
+}
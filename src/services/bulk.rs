@@ -0,0 +1,99 @@
+//! Actions run on several containers at once, started in order with a bounded number in flight
+
+use std::{collections::HashSet, sync::atomic::{AtomicU64, Ordering}};
+
+use futures::{stream, Stream, StreamExt};
+
+use crate::{events::docker::{BulkAction, BulkResult, ContainerFilters, ContainerSelector, DockerContainerBulkData, HostContainerSummary}, services::{docker, store}};
+
+const DEFAULT_CONCURRENCY: usize = 4;
+const MAX_CONCURRENCY: usize = 32;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Identifier of the request, the client's own when given
+pub fn id(request: &DockerContainerBulkData) -> String {
+    request.id.clone().unwrap_or_else(|| format!("bulk-{}", NEXT_ID.fetch_add(1, Ordering::Relaxed)))
+}
+
+/// Containers targeted by the request, in the order their actions start. An unknown name or ID fails the
+/// whole request, so a typo never runs the action on the rest.
+pub async fn targets(request: &DockerContainerBulkData) -> Result<Vec<HostContainerSummary>, String> {
+    let mut targets = match (&request.containers, &request.selector) {
+        (Some(containers), None) => {
+            let listed = docker::get_host_containers(request.host.as_deref(), &ContainerFilters::default()).await.map_err(|e| e.to_string())?;
+            let mut targets = Vec::with_capacity(containers.len());
+            for container in containers {
+                let selector = ContainerSelector { containers: vec![container.clone()], ..Default::default() };
+                match listed.iter().find(|summary| selector.matches(&summary.container)) {
+                    Some(summary) => targets.push(summary.clone()),
+                    None => return Err(format!("No such container: {}", container))
+                }
+            }
+            targets
+        },
+        (None, Some(selector)) => {
+            let mut targets = docker::select_containers(selector).await.map_err(|e| e.to_string())?;
+            docker::sort_containers(&mut targets, &[]);
+            targets
+        },
+        _ => return Err("Either `containers` or `selector` is required".to_string())
+    };
+    
+    if let Some(order) = &request.order {
+        docker::sort_containers(&mut targets, order);
+    }
+    let mut seen = HashSet::new();
+    targets.retain(|target| seen.insert((target.host.clone(), target.container.id.clone())));
+    Ok(targets)
+}
+
+/// Results as the actions complete, with the index of their target. Targets are started in order but up to
+/// `concurrency` run at once, so an action may complete before one started earlier: only a concurrency of 1
+/// waits for each action before starting the next.
+pub fn run(action: BulkAction, targets: &[HostContainerSummary], concurrency: Option<usize>) -> impl Stream<Item = (usize, BulkResult)> + '_ {
+    let concurrency = concurrency.unwrap_or(DEFAULT_CONCURRENCY).clamp(1, MAX_CONCURRENCY);
+    
+    stream::iter(targets.iter().enumerate())
+        .map(move |(index, target)| async move { (index, apply(action, target).await) })
+        .buffer_unordered(concurrency)
+}
+
+async fn apply(action: BulkAction, target: &HostContainerSummary) -> BulkResult {
+    let host = Some(target.host.as_str());
+    let id = target.container.id.clone().unwrap_or_default();
+    
+    let result = match action {
+        BulkAction::Start => docker::start_container(host, &id).await,
+        BulkAction::Stop => docker::stop_container(host, &id).await,
+        BulkAction::Restart => docker::restart_container(host, &id).await,
+        BulkAction::Remove => docker::remove_container(host, &id).await,
+        BulkAction::Pause => docker::pause_container(host, &id).await
+    };
+    
+    BulkResult {
+        host: target.host.clone(),
+        container_id: id,
+        name: target.container.names.as_ref().and_then(|names| names.first()).map(|name| name.trim_start_matches('/').to_string()),
+        success: result.is_ok(),
+        error: result.err().map(|e| e.to_string())
+    }
+}
+
+/// Logs the bulk action and records it in the audit log
+pub async fn audit(summary: &DockerContainerBulkData) {
+    let id = summary.id.as_deref().unwrap_or_default();
+    tracing::info!(
+        target: "audit",
+        bulk = id,
+        action = ?summary.action,
+        succeeded = summary.succeeded,
+        failed = summary.failed,
+        error = summary.error,
+        "Bulk action ran"
+    );
+    
+    if let Err(e) = store::audit::append("bulk.run", Some(id), summary).await {
+        tracing::error!(bulk = id, "Failed to audit bulk action: {:?}", e);
+    }
+}
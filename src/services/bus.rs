@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+
+use crate::events::Event;
+
+const CAPACITY: usize = 100;
+
+/// Typed broadcast bus shared by every event producer (Docker, system, scheduler...).
+/// Events are kept as `Arc<Event>` so each subscriber serializes them lazily for its own transport.
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Arc<Event>>
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (tx, _rx) = broadcast::channel(CAPACITY);
+        Self { tx }
+    }
+    
+    pub fn publish(&self, event: Event) {
+        // An error only means nobody is subscribed right now
+        if self.tx.send(Arc::new(event)).is_err() {
+            tracing::trace!("No subscriber for published event");
+        }
+    }
+    
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Event>> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
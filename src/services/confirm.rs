@@ -0,0 +1,227 @@
+//! Safety net of container actions: destructive ones first answer with a challenge describing what they
+//! affect and run once the client echoes its token, dry runs only describe, and protected containers are
+//! left to authenticated admins.
+
+use std::{collections::HashMap, fmt::{Debug, Write}, sync::{LazyLock, Mutex}, time::{SystemTime, UNIX_EPOCH}};
+
+use bollard::secret::{ContainerInspectResponse, ContainerSummary, MountPointTypeEnum};
+use rustls::crypto::ring::default_provider;
+use serde::Serialize;
+
+use crate::{events::{docker::{AffectedContainer, BulkAction, ContainerFilters, HostContainerSummary, COMPOSE_PROJECT_LABEL, PROTECTED_LABEL}, system::{Identity, Role}}, services::{docker, hosts, store}};
+
+const COMPOSE_SERVICE_LABEL: &str = "com.docker.compose.service";
+const COMPOSE_DEPENDS_ON_LABEL: &str = "com.docker.compose.depends_on";
+
+/// Seconds a confirmation token stays valid
+const TOKEN_TTL: u64 = 60;
+
+/// Pending challenges, by token
+static CHALLENGES: LazyLock<Mutex<HashMap<String, Challenge>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The confirmed request must come from the same stream, for the same action on the same containers
+struct Challenge {
+    session: u64,
+    action: BulkAction,
+    targets: Vec<(String, String)>,
+    expires_at: u64
+}
+
+/// Why an action did not run, to answer the client with
+#[derive(Default)]
+pub struct Held {
+    pub affected: Option<Vec<AffectedContainer>>,
+    pub confirm: Option<String>,
+    pub expires_at: Option<u64>,
+    pub error: Option<String>
+}
+
+impl Held {
+    fn refused(error: String) -> Self {
+        Self { error: Some(error), ..Default::default() }
+    }
+}
+
+/// Lets the action run on `targets`, or holds it: described for a dry run, which reports protected containers
+/// without refusing, refused, or challenged when destructive and not confirmed by a valid `confirm` token
+/// issued to the `session` stream
+pub async fn check(identity: &Identity, session: u64, action: BulkAction, targets: &[HostContainerSummary], dry_run: bool, confirm: Option<&str>) -> Result<(), Held> {
+    if dry_run {
+        return Err(Held { affected: Some(affected(targets).await), ..Default::default() });
+    }
+    check_protected(identity, action, targets).await.map_err(Held::refused)?;
+    
+    if !action.destructive() {
+        return Ok(());
+    }
+    
+    let keys = targets.iter().map(|target| (target.host.clone(), target.container.id.clone().unwrap_or_default())).collect();
+    match redeem(session, action, keys, confirm)? {
+        None => Ok(()),
+        Some((token, expires_at)) => Err(Held {
+            affected: Some(affected(targets).await),
+            confirm: Some(token),
+            expires_at: Some(expires_at),
+            error: None
+        })
+    }
+}
+
+/// Refuses the action when a target is protected and `identity` is not an authenticated admin
+pub async fn check_protected(identity: &Identity, action: impl Serialize + Debug, targets: &[HostContainerSummary]) -> Result<(), String> {
+    if identity.authenticated && identity.role == Role::Admin {
+        return Ok(());
+    }
+    let Some(target) = targets.iter().find(|target| protected(&target.container)) else {
+        return Ok(());
+    };
+    
+    let error = format!("Container {} is protected, an authenticated admin is required", name(&target.container).unwrap_or(&target.host));
+    refuse(identity, action, &error).await;
+    Err(error)
+}
+
+/// Consumes the `confirm` token of a challenge for the same request, or issues a new token with its expiry
+fn redeem(session: u64, action: BulkAction, targets: Vec<(String, String)>, confirm: Option<&str>) -> Result<Option<(String, u64)>, Held> {
+    let now = now();
+    let mut challenges = CHALLENGES.lock().unwrap();
+    challenges.retain(|_, challenge| challenge.expires_at > now);
+    
+    if let Some(token) = confirm {
+        // Left in place for another stream, so that a leaked token cannot be spent by someone else
+        let matches = challenges.get(token).is_some_and(|challenge| challenge.session == session && challenge.action == action && challenge.targets == targets);
+        return match matches {
+            true => {
+                challenges.remove(token);
+                Ok(None)
+            },
+            false => Err(Held::refused("Confirmation token is unknown, expired or for other containers, send the request again without it".to_string()))
+        };
+    }
+    
+    let token = token().map_err(Held::refused)?;
+    let expires_at = now + TOKEN_TTL;
+    challenges.insert(token.clone(), Challenge {
+        session,
+        action,
+        targets,
+        expires_at
+    });
+    Ok(Some((token, expires_at)))
+}
+
+/// [`check`] for the container `id` of `host`, resolved like Docker does from its name, ID or ID prefix.
+/// One inspect tells whether it is protected, the host is only listed to describe compose dependents.
+pub async fn check_container(identity: &Identity, session: u64, action: BulkAction, host: Option<&str>, id: &str, dry_run: bool, confirm: Option<&str>) -> Result<(), Held> {
+    let container = docker::get_container(host, id).await.map_err(|e| Held::refused(e.to_string()))?;
+    let target = HostContainerSummary {
+        host: hosts::name_or_default(host),
+        container: summary(container)
+    };
+    
+    check(identity, session, action, std::slice::from_ref(&target), dry_run, confirm).await
+}
+
+/// Fields of a listing that an inspect also carries
+fn summary(container: ContainerInspectResponse) -> ContainerSummary {
+    let config = container.config.unwrap_or_default();
+    ContainerSummary {
+        id: container.id,
+        names: container.name.map(|name| vec![name]),
+        image: config.image,
+        image_id: container.image,
+        state: container.state.and_then(|state| state.status).map(|status| status.to_string()),
+        labels: config.labels,
+        mounts: container.mounts,
+        ..Default::default()
+    }
+}
+
+fn protected(container: &ContainerSummary) -> bool {
+    container.labels.as_ref()
+        .and_then(|labels| labels.get(PROTECTED_LABEL))
+        .is_some_and(|value| value != "false")
+}
+
+fn name(container: &ContainerSummary) -> Option<&str> {
+    container.names.as_ref()?.first().map(|name| name.trim_start_matches('/'))
+}
+
+fn label<'a>(container: &'a ContainerSummary, key: &str) -> Option<&'a String> {
+    container.labels.as_ref()?.get(key)
+}
+
+/// Describes the targets, with the compose services depending on them among the containers of their host
+async fn affected(targets: &[HostContainerSummary]) -> Vec<AffectedContainer> {
+    let mut neighbours: HashMap<&str, Vec<ContainerSummary>> = HashMap::new();
+    for target in targets {
+        if label(&target.container, COMPOSE_PROJECT_LABEL).is_none() || neighbours.contains_key(target.host.as_str()) {
+            continue;
+        }
+        let containers = docker::get_containers(Some(&target.host), &ContainerFilters::default()).await.unwrap_or_else(|e| {
+            tracing::error!(host = target.host, "Failed to list containers: {:?}", e);
+            Vec::new()
+        });
+        neighbours.insert(&target.host, containers);
+    }
+    
+    targets.iter().map(|target| {
+        let container = &target.container;
+        let project = label(container, COMPOSE_PROJECT_LABEL);
+        let service = label(container, COMPOSE_SERVICE_LABEL);
+        
+        let mut dependents: Vec<String> = match (project, service) {
+            (Some(project), Some(service)) => neighbours.get(target.host.as_str()).into_iter().flatten()
+                .filter(|other| label(other, COMPOSE_PROJECT_LABEL) == Some(project))
+                .filter(|other| label(other, COMPOSE_DEPENDS_ON_LABEL).is_some_and(|depends_on| {
+                    // `service:condition:restart` entries, comma separated
+                    depends_on.split(',').any(|entry| entry.split(':').next() == Some(service.as_str()))
+                }))
+                .filter_map(|other| label(other, COMPOSE_SERVICE_LABEL).cloned())
+                .collect(),
+            _ => Vec::new()
+        };
+        dependents.sort();
+        dependents.dedup();
+        
+        AffectedContainer {
+            host: target.host.clone(),
+            container_id: container.id.clone().unwrap_or_default(),
+            name: name(container).map(str::to_string),
+            state: container.state.clone(),
+            protected: protected(container),
+            project: project.cloned(),
+            service: service.cloned(),
+            dependents,
+            volumes: container.mounts.iter().flatten()
+                .filter(|mount| mount.typ == Some(MountPointTypeEnum::VOLUME))
+                .filter_map(|mount| mount.name.clone())
+                .collect()
+        }
+    }).collect()
+}
+
+/// Logs the refused action and records it in the audit log
+async fn refuse(identity: &Identity, action: impl Serialize + Debug, error: &str) {
+    tracing::warn!(target: "audit", identity = identity.name, action = ?action, "{}", error);
+    
+    let details = serde_json::json!({ "action": action, "role": identity.role, "error": error });
+    if let Err(e) = store::audit::append("action.refused", Some(&identity.name), &details).await {
+        tracing::error!(identity = identity.name, "Failed to audit refused action: {:?}", e);
+    }
+}
+
+/// 128 bits from the operating system CSPRNG, hex encoded
+fn token() -> Result<String, String> {
+    let mut bytes = [0; 16];
+    default_provider().secure_random.fill(&mut bytes).map_err(|e| format!("Failed to generate a confirmation token: {:?}", e))?;
+    
+    Ok(bytes.iter().fold(String::with_capacity(32), |mut token, byte| {
+        let _ = write!(token, "{:02x}", byte);
+        token
+    }))
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default()
+}
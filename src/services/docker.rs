@@ -1,11 +1,10 @@
-use std::{thread::sleep, time::Duration};
+use std::{cmp::Ordering, sync::Arc, time::Duration};
 
-use bollard::{errors::Error, secret::{ContainerInspectResponse, ContainerSummary}, system::EventsOptions, Docker};
-use futures::StreamExt;
+use bollard::{container::Stats, errors::Error, secret::{ContainerInspectResponse, ContainerSummary, EventMessage}};
+use futures::{future::join_all, StreamExt};
 use serde_json::json;
-use tokio::sync::broadcast;
 
-use crate::{events::{docker::{DockerEvent, DockerStatusData}, Event}, serializers::SendEvent};
+use crate::{events::{docker::{ContainerFilters, ContainerSelector, ContainerSort, DockerEvent, DockerStatusData, HostContainerSummary, SortField, SortOrder}, Event}, serializers::SendEvent, services::{agents, alerts, backend::{DockerBackend, ExecOutput}, bus::EventBus, event_history, hosts, metrics}};
 
 const INTERVAL: Duration = Duration::from_secs(10);
 
@@ -21,141 +20,291 @@ pub fn format_docker_event_value(s: &str) -> String {
     }
 }
 
-pub fn get_docker_client() -> Result<Docker, Error> {
-    Docker::connect_with_socket_defaults()
+/// Starts one event listener per configured host, agents forward their own events
+pub fn listen_all_docker_events(bus: EventBus) {
+    for host in hosts::configured_names() {
+        tokio::spawn(listen_docker_events(bus.clone(), host));
+    }
 }
 
-pub async fn listen_docker_events(mut tx: broadcast::Sender<String>) {
-    let docker = loop {
-        match get_docker_client() {
-            Ok(client) => break client,
-            Err(error) => {
-                log::error!("Failed to connect to Docker, retrying in {:?}: {:?}", INTERVAL, error);
-                tx.send_event(Event::Docker(DockerEvent::DockerStatus {
-                    data: DockerStatusData {
-                        status: Some(0)
-                    }
-                })).await;
-                sleep(INTERVAL);
-            }
+/// Publishes a raw event of a host: it feeds the alerts, is broadcast as a `DockerEventMessage` for the
+/// history and the `dockerEvents` topic, then as its typed event (`DockerContainerStart`...)
+pub async fn publish_docker_event(bus: &mut EventBus, host: &str, event: &EventMessage) {
+    alerts::observe_docker_event(bus, host, event).await;
+    bus.send_event(Event::Docker(DockerEvent::DockerEventMessage { data: event_history::to_record(host, event) })).await;
+    
+    let (Some(typ), Some(action)) = (event.typ, event.action.as_deref()) else {
+        tracing::warn!(host, "Ignoring Docker event without a type or an action: {:?}", event);
+        return;
+    };
+    let event_action = format!("Docker{}{}", format_docker_event_value(typ.as_ref()), format_docker_event_value(action));
+    let mut data = json!(&event.actor);
+    data["host"] = json!(host);
+    let event_json = json!({
+        "type": event_action,
+        "data": data
+    }).to_string();
+    let docker_event: Event = match serde_json::from_str(&event_json) {
+        Ok(docker_event) => docker_event,
+        Err(error) => {
+            tracing::error!(host, "Failed to parse Docker event [{}]: {:?}", event_action, error);
+            return;
         }
     };
     
-    let options = Some(EventsOptions::<String>::default());
-    let mut events = docker.events(options);
-    
-    while let Some(event) = events.next().await {
-        match event {
-            Ok(event) => {                
-                let event_action = format!("Docker{}{}", format_docker_event_value(&event.typ.clone().unwrap().to_string()), format_docker_event_value(&event.action.clone().unwrap().to_string()));
-                let event_json = json!({
-                    "type": event_action,
-                    "data": &event.actor
-                }).to_string();
-                let docker_event: Event = match serde_json::from_str(&event_json) {
-                    Ok(docker_event) => docker_event,
-                    Err(error) => {
-                        log::error!("Failed to parse Docker event [{}]: {:?}", event_action, error);
-                        continue;
+    tracing::info!(host, event = docker_event.name(), container_id = docker_event.container_id(), "Received Docker event");
+    
+    bus.send_event(docker_event).await;
+}
+
+/// Forwards the events of a host to the bus, tagged with the host name, reconnecting when the stream ends
+pub async fn listen_docker_events(mut bus: EventBus, host: String) {
+    loop {
+        let docker = match get_backend(Some(&host)) {
+            Ok(client) => client,
+            Err(error) => {
+                tracing::error!(host, "Failed to connect to Docker, retrying in {:?}: {:?}", INTERVAL, error);
+                bus.send_event(Event::Docker(DockerEvent::DockerStatus {
+                    data: DockerStatusData {
+                        status: Some(0),
+                        host: Some(host.clone())
                     }
-                };
-                
-                log::info!("Received Docker event: {:?}", docker_event);
-                
-                tx.send_event(docker_event).await;
+                })).await;
+                tokio::time::sleep(INTERVAL).await;
+                continue;
             }
-            Err(error) => {
-                log::error!("Failed to receive Docker event: {:?}", error);
+        };
+        
+        let mut events = docker.events();
+        
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(event) => publish_docker_event(&mut bus, &host, &event).await,
+                Err(error) => {
+                    tracing::error!(host, "Failed to receive Docker event: {:?}", error);
+                    metrics::docker_error("events");
+                    break;
+                }
             }
         }
+        
+        tracing::warn!(host, "Docker event stream ended, reconnecting in {:?}", INTERVAL);
+        tokio::time::sleep(INTERVAL).await;
     }
 }
 
-pub async fn ping() -> i8 {
-    let docker = get_docker_client();
+/// Resolves the host to the backend serving it, or to the agent registered under its name
+pub fn get_backend(host: Option<&str>) -> Result<Arc<dyn DockerBackend>, Error> {
+    let name = hosts::name_or_default(host);
+    if let Some(backend) = hosts::backend(&name).inspect_err(|_| metrics::docker_error("connect"))? {
+        return Ok(backend);
+    }
+    
+    agents::get(&name).map(|agent| agent as Arc<dyn DockerBackend>).ok_or_else(|| Error::IOError {
+        err: std::io::Error::new(std::io::ErrorKind::NotFound, format!("Unknown Docker host {:?}", name))
+    })
+}
+
+pub async fn ping(host: Option<&str>) -> i8 {
+    let docker = get_backend(host);
     
     match docker {
         Ok(docker) => {
             match docker.ping().await {
                 Ok(_) => 1,
-                Err(_) => 2
+                Err(_) => {
+                    metrics::docker_error("ping");
+                    2
+                }
             }
         },
         Err(_) => 0
     }
 }
 
-pub async fn get_containers() -> Result<Vec<ContainerSummary>, Error> {
-    let options = Some(bollard::container::ListContainersOptions::<String> {
-        all: true,
-        ..Default::default()
-    });
-    
-    let docker = get_docker_client();
+pub async fn get_containers(host: Option<&str>, filters: &ContainerFilters) -> Result<Vec<ContainerSummary>, Error> {
+    let docker = get_backend(host);
     
     match docker {
         Ok(docker) => {
-            match docker.list_containers(options).await {
+            match docker.list_containers(filters).await {
                 Ok(containers) => Ok(containers),
-                Err(error) => Err(error)
+                Err(error) => {
+                    metrics::docker_error("list_containers");
+                    Err(error)
+                }
             }
         },
         Err(error) => Err(error)
     }
 }
 
-pub async fn get_container(id: &str) -> Result<ContainerInspectResponse, Error> {
-    let docker = get_docker_client();
+/// Lists the containers of the requested host, or of every host for `"*"`, matching `filters`.
+/// When merging, unreachable hosts are logged and skipped.
+pub async fn get_host_containers(host: Option<&str>, filters: &ContainerFilters) -> Result<Vec<HostContainerSummary>, Error> {
+    let names = hosts::resolve(host);
+    let merge = names.len() > 1;
+    let mut containers = Vec::new();
+    
+    let results = join_all(names.iter().map(|name| get_containers(Some(name), filters))).await;
+    
+    for (name, result) in names.iter().zip(results) {
+        match result {
+            Ok(host_containers) => containers.extend(host_containers.into_iter().map(|container| HostContainerSummary {
+                host: name.clone(),
+                container
+            })),
+            Err(error) if merge => tracing::error!(host = name, "Failed to list containers: {:?}", error),
+            Err(error) => return Err(error)
+        }
+    }
+    
+    Ok(containers)
+}
+
+/// Containers of the selector's host(s) it matches
+pub async fn select_containers(selector: &ContainerSelector) -> Result<Vec<HostContainerSummary>, Error> {
+    let containers = get_host_containers(selector.host.as_deref(), &ContainerFilters::default()).await?;
+    
+    Ok(containers.into_iter().filter(|summary| selector.matches(&summary.container)).collect())
+}
+
+/// Orders containers by the sort keys, then by host and ID so pages are stable
+pub fn sort_containers(containers: &mut [HostContainerSummary], sort: &[ContainerSort]) {
+    containers.sort_by(|a, b| {
+        sort.iter()
+            .map(|key| {
+                let ordering = match key.by {
+                    SortField::Name => container_name(&a.container).cmp(container_name(&b.container)),
+                    SortField::Created => a.container.created.cmp(&b.container.created),
+                    SortField::State => a.container.state.cmp(&b.container.state),
+                    SortField::Image => a.container.image.cmp(&b.container.image),
+                    SortField::Host => a.host.cmp(&b.host)
+                };
+                match key.order {
+                    SortOrder::Asc => ordering,
+                    SortOrder::Desc => ordering.reverse()
+                }
+            })
+            .fold(Ordering::Equal, Ordering::then)
+            .then_with(|| (&a.host, &a.container.id).cmp(&(&b.host, &b.container.id)))
+    });
+}
+
+fn container_name(container: &ContainerSummary) -> &str {
+    container.names.iter().flatten().next().map(|name| name.trim_start_matches('/')).unwrap_or_default()
+}
+
+pub async fn get_container(host: Option<&str>, id: &str) -> Result<ContainerInspectResponse, Error> {
+    let docker = get_backend(host);
     
     match docker {
         Ok(docker) => {
-            match docker.inspect_container(id, None).await {
+            match docker.inspect_container(id).await {
                 Ok(container) => Ok(container),
-                Err(error) => Err(error)
+                Err(error) => {
+                    metrics::docker_error("inspect_container");
+                    Err(error)
+                }
+            }
+        },
+        Err(error) => Err(error)
+    }
+}
+
+pub async fn start_container(host: Option<&str>, id: &str) -> Result<(), Error> {
+    let docker = get_backend(host);
+    
+    match docker {
+        Ok(docker) => {
+            match docker.start_container(id).await {
+                Ok(_) => Ok(()),
+                Err(error) => {
+                    metrics::docker_error("start_container");
+                    Err(error)
+                }
+            }
+        },
+        Err(error) => Err(error)
+    }
+}
+
+pub async fn stop_container(host: Option<&str>, id: &str) -> Result<(), Error> {
+    let docker = get_backend(host);
+    
+    match docker {
+        Ok(docker) => {
+            match docker.stop_container(id).await {
+                Ok(_) => Ok(()),
+                Err(error) => {
+                    metrics::docker_error("stop_container");
+                    Err(error)
+                }
             }
         },
         Err(error) => Err(error)
     }
 }
 
-pub async fn start_container(id: &str) -> Result<(), Error> {
-    let docker = get_docker_client();
+pub async fn restart_container(host: Option<&str>, id: &str) -> Result<(), Error> {
+    let docker = get_backend(host);
     
     match docker {
         Ok(docker) => {
-            match docker.start_container(id, None::<bollard::container::StartContainerOptions<String>>).await {
+            match docker.restart_container(id).await {
                 Ok(_) => Ok(()),
-                Err(error) => Err(error)
+                Err(error) => {
+                    metrics::docker_error("restart_container");
+                    Err(error)
+                }
             }
         },
         Err(error) => Err(error)
     }
 }
 
-pub async fn stop_container(id: &str) -> Result<(), Error> {
-    let docker = get_docker_client();
+pub async fn pause_container(host: Option<&str>, id: &str) -> Result<(), Error> {
+    let docker = get_backend(host);
     
     match docker {
         Ok(docker) => {
-            match docker.stop_container(id, None).await {
+            match docker.pause_container(id).await {
                 Ok(_) => Ok(()),
-                Err(error) => Err(error)
+                Err(error) => {
+                    metrics::docker_error("pause_container");
+                    Err(error)
+                }
             }
         },
         Err(error) => Err(error)
     }
 }
 
-pub async fn restart_container(id: &str) -> Result<(), Error> {
-    let docker = get_docker_client();
+pub async fn remove_container(host: Option<&str>, id: &str) -> Result<(), Error> {
+    let docker = get_backend(host);
     
     match docker {
         Ok(docker) => {
-            match docker.restart_container(id, None).await {
+            match docker.remove_container(id).await {
                 Ok(_) => Ok(()),
-                Err(error) => Err(error)
+                Err(error) => {
+                    metrics::docker_error("remove_container");
+                    Err(error)
+                }
             }
         },
         Err(error) => Err(error)
     }
-}
\ No newline at end of file
+}
+
+pub async fn get_container_stats(host: Option<&str>, id: &str, one_shot: bool) -> Result<Stats, Error> {
+    let docker = get_backend(host)?;
+    
+    docker.container_stats(id, one_shot).await.inspect_err(|_| metrics::docker_error("stats"))
+}
+
+pub async fn exec_container(host: Option<&str>, id: &str, command: Vec<String>) -> Result<ExecOutput, Error> {
+    let docker = get_backend(host)?;
+    
+    docker.exec_container(id, command).await.inspect_err(|_| metrics::docker_error("exec_container"))
+}
@@ -0,0 +1,107 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bollard::secret::{EventActor, EventMessage};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{config, events::{docker::{DockerEvent, DockerEventHistoryData, DockerEventRecord}, Event}, services::{bus::EventBus, store}};
+
+const DEFAULT_LIMIT: usize = 100;
+
+pub const MAX_LIMIT: usize = 1000;
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Flattens a raw event of a host for the history and the `dockerEvents` topic
+pub fn to_record(host: &str, event: &EventMessage) -> DockerEventRecord {
+    let time_nano = event.time_nano.unwrap_or_else(|| event.time.unwrap_or_default() * 1_000_000_000);
+    
+    DockerEventRecord {
+        id: None,
+        host: host.to_string(),
+        typ: event.typ.map(|typ| typ.to_string()).unwrap_or_default(),
+        action: event.action.clone().unwrap_or_default(),
+        actor_id: event.actor.as_ref().and_then(|actor| actor.id.clone()),
+        attributes: event.actor.as_ref().and_then(|actor| actor.attributes.clone()).unwrap_or_default(),
+        time: event.time.unwrap_or(time_nano / 1_000_000_000),
+        time_nano
+    }
+}
+
+/// Raw event back from its record, as forwarded by agents
+pub fn to_message(record: &DockerEventRecord) -> EventMessage {
+    EventMessage {
+        typ: record.typ.parse().ok(),
+        action: Some(record.action.clone()),
+        actor: Some(EventActor {
+            id: record.actor_id.clone(),
+            attributes: Some(record.attributes.clone())
+        }),
+        time: Some(record.time),
+        time_nano: Some(record.time_nano),
+        ..Default::default()
+    }
+}
+
+/// Stores the raw events published on the bus, those of the configured hosts and those forwarded by agents,
+/// and drops the ones older than the retention
+pub async fn record_event_history(bus: EventBus) {
+    let retention = config::get().event_history.retention;
+    if retention == 0 {
+        return;
+    }
+    
+    let mut rx = bus.subscribe();
+    let mut prune = tokio::time::interval(PRUNE_INTERVAL);
+    
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Ok(event) => {
+                    if let Event::Docker(DockerEvent::DockerEventMessage { data }) = event.as_ref()
+                        && let Err(e) = store::events::record(data).await {
+                        tracing::error!(host = data.host, "Failed to record Docker event: {:?}", e);
+                    }
+                },
+                Err(RecvError::Lagged(skipped)) => tracing::warn!("Event history lagged, {} events dropped", skipped),
+                Err(RecvError::Closed) => break
+            },
+            _ = prune.tick() => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default();
+                match store::events::prune(now.saturating_sub(retention) as i64).await {
+                    Ok(0) => {},
+                    Ok(pruned) => tracing::debug!("Pruned {} Docker events", pruned),
+                    Err(e) => tracing::error!("Failed to prune Docker events: {:?}", e)
+                }
+            }
+        }
+    }
+}
+
+/// Answers a `DockerEventHistory` request with a page of events
+pub async fn query(request: &DockerEventHistoryData) -> Result<DockerEventHistoryData, String> {
+    if config::get().event_history.retention == 0 {
+        return Err("Event history is disabled".to_string());
+    }
+    if let (Some(from), Some(to)) = (request.from, request.to)
+        && from >= to {
+        return Err("`from` must be before `to`".to_string());
+    }
+    
+    let limit = request.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let (events, next_cursor) = store::events::query(request, limit).await
+        .map_err(|e| format!("Failed to query Docker events: {}", e))?;
+    
+    Ok(DockerEventHistoryData {
+        host: request.host.clone(),
+        container: request.container.clone(),
+        actions: request.actions.clone(),
+        types: request.types.clone(),
+        from: request.from,
+        to: request.to,
+        limit: Some(limit),
+        cursor: request.cursor,
+        events: Some(events),
+        next_cursor,
+        error: None
+    })
+}
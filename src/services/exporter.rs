@@ -0,0 +1,137 @@
+use std::{collections::HashMap, sync::{LazyLock, Mutex}};
+
+use bollard::container::CPUStats;
+use futures::{stream, StreamExt};
+use prometheus::{opts, GaugeVec, IntCounterVec, IntGaugeVec, Registry};
+
+use crate::{events::docker::{ContainerFilters, HostContainerSummary, COMPOSE_PROJECT_LABEL}, services::{docker, hosts, metrics, telemetry}};
+
+const LABELS: [&str; 4] = ["host", "name", "image", "project"];
+
+/// Containers inspected and sampled at once during a scrape
+const SCRAPE_CONCURRENCY: usize = 8;
+
+/// CPU counters of each running container at the previous scrape, keyed on host and ID. Stats are sampled
+/// once per scrape, the CPU usage is measured between two scrapes.
+static PREVIOUS_CPU: LazyLock<Mutex<HashMap<(String, String), CPUStats>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct ContainerMetrics {
+    state: IntGaugeVec,
+    restarts: IntGaugeVec,
+    cpu: GaugeVec,
+    memory_usage: GaugeVec,
+    memory_limit: GaugeVec,
+    network_rx: IntCounterVec,
+    network_tx: IntCounterVec
+}
+
+impl ContainerMetrics {
+    fn register(registry: &Registry) -> prometheus::Result<Self> {
+        let metrics = Self {
+            state: IntGaugeVec::new(opts!("container_state", "Current state of the container, 1 for the active state"), &["host", "name", "image", "project", "state"])?,
+            restarts: IntGaugeVec::new(opts!("container_restart_count", "Number of times Docker restarted the container"), &LABELS)?,
+            cpu: GaugeVec::new(opts!("container_cpu_usage_percent", "CPU usage of the container, 100 per core"), &LABELS)?,
+            memory_usage: GaugeVec::new(opts!("container_memory_usage_bytes", "Memory used by the container, page cache excluded"), &LABELS)?,
+            memory_limit: GaugeVec::new(opts!("container_memory_limit_bytes", "Memory limit of the container"), &LABELS)?,
+            network_rx: IntCounterVec::new(opts!("container_network_receive_bytes_total", "Bytes received on every network of the container"), &LABELS)?,
+            network_tx: IntCounterVec::new(opts!("container_network_transmit_bytes_total", "Bytes sent on every network of the container"), &LABELS)?
+        };
+        
+        registry.register(Box::new(metrics.state.clone()))?;
+        registry.register(Box::new(metrics.restarts.clone()))?;
+        registry.register(Box::new(metrics.cpu.clone()))?;
+        registry.register(Box::new(metrics.memory_usage.clone()))?;
+        registry.register(Box::new(metrics.memory_limit.clone()))?;
+        registry.register(Box::new(metrics.network_rx.clone()))?;
+        registry.register(Box::new(metrics.network_tx.clone()))?;
+        
+        Ok(metrics)
+    }
+}
+
+fn container_labels(summary: &HostContainerSummary) -> [String; 4] {
+    let container = &summary.container;
+    let name = container.names.as_ref()
+        .and_then(|names| names.first())
+        .map(|name| name.trim_start_matches('/').to_string())
+        .unwrap_or_default();
+    let project = container.labels.as_ref()
+        .and_then(|labels| labels.get(COMPOSE_PROJECT_LABEL))
+        .cloned()
+        .unwrap_or_default();
+    
+    [summary.host.clone(), name, container.image.clone().unwrap_or_default(), project]
+}
+
+/// Collects per-container metrics from Docker and renders them in the Prometheus text format.
+/// A fresh registry is used on every scrape so removed containers disappear from the output.
+pub async fn render_container_metrics() -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let registry = Registry::new();
+    let container_metrics = ContainerMetrics::register(&registry)?;
+    
+    let containers = docker::get_host_containers(Some(hosts::ALL_HOSTS), &ContainerFilters::default()).await?;
+    
+    let details: Vec<_> = stream::iter(containers)
+        .map(|summary| async move {
+            let host = Some(summary.host.as_str());
+            let id = summary.container.id.as_deref().unwrap_or_default();
+            let inspect = docker::get_container(host, id).await.ok();
+            let stats = match summary.container.state.as_deref() {
+                Some("running") => docker::get_container_stats(host, id, true).await.ok(),
+                _ => None
+            };
+            (summary, inspect, stats)
+        })
+        .buffer_unordered(SCRAPE_CONCURRENCY)
+        .collect()
+        .await;
+    
+    let mut previous_cpu = PREVIOUS_CPU.lock().unwrap();
+    let mut current_cpu = HashMap::new();
+    
+    for (summary, inspect, stats) in details {
+        let labels = container_labels(&summary);
+        let labels = labels.each_ref().map(String::as_str);
+        let state = summary.container.state.as_deref().unwrap_or("unknown");
+        
+        container_metrics.state.with_label_values(&[labels[0], labels[1], labels[2], labels[3], state]).set(1);
+        
+        if let Some(restart_count) = inspect.and_then(|inspect| inspect.restart_count) {
+            container_metrics.restarts.with_label_values(&labels).set(restart_count);
+        }
+        
+        let Some(mut stats) = stats else {
+            continue;
+        };
+        let key = (summary.host.clone(), summary.container.id.clone().unwrap_or_default());
+        let measured = match previous_cpu.remove(&key) {
+            Some(previous) => {
+                stats.precpu_stats = previous;
+                true
+            },
+            None => false
+        };
+        current_cpu.insert(key, stats.cpu_stats.clone());
+        let stats = telemetry::container_stats(&summary.host, &stats);
+        
+        // Left out on the first scrape of a container, until there is a previous sample to compare with
+        if let Some(cpu) = stats.cpu_percent.filter(|_| measured) {
+            container_metrics.cpu.with_label_values(&labels).set(cpu);
+        }
+        if let Some(usage) = stats.memory_usage {
+            container_metrics.memory_usage.with_label_values(&labels).set(usage as f64);
+        }
+        if let Some(limit) = stats.memory_limit {
+            container_metrics.memory_limit.with_label_values(&labels).set(limit as f64);
+        }
+        if let Some(rx) = stats.network_rx {
+            container_metrics.network_rx.with_label_values(&labels).inc_by(rx);
+        }
+        if let Some(tx) = stats.network_tx {
+            container_metrics.network_tx.with_label_values(&labels).inc_by(tx);
+        }
+    }
+    *previous_cpu = current_cpu;
+    
+    Ok(metrics::render(&registry))
+}
@@ -0,0 +1,149 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{config::{self, MetricsHistoryConfig}, events::{docker::DockerContainerStatsData, metrics::{MetricSeries, MetricsQueryData}, system::SystemLoadData}, services::{hosts, store::{self, metrics::Point}, telemetry}};
+
+/// Series of the machine the server runs on
+const HOST_SERIES: &str = "host";
+
+/// Points returned per series at most, the step grows to fit the range
+pub const MAX_POINTS: u64 = 1000;
+
+const DEFAULT_RANGE: u64 = 3600;
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default()
+}
+
+fn container_series(host: &str, name: &str) -> String {
+    format!("container/{}/{}", host, name)
+}
+
+/// Resolutions available, finest first: the raw samples then the rollups, as `(step, retention)`
+fn tiers(config: &MetricsHistoryConfig) -> Vec<(u64, u64)> {
+    let mut tiers = vec![(config.interval, config.retention)];
+    tiers.extend(config.rollups.iter().map(|rollup| (rollup.step, rollup.retention)));
+    tiers.sort_by_key(|(step, _)| *step);
+    tiers
+}
+
+fn host_points(load: SystemLoadData) -> Vec<Point> {
+    let point = |metric, value: Option<f64>| value.map(|value| Point { series: HOST_SERIES.to_string(), metric, value });
+    
+    [
+        point("load1", load.load1),
+        point("load5", load.load5),
+        point("load15", load.load15),
+        point("memoryTotal", load.memory_total.map(|value| value as f64)),
+        point("memoryAvailable", load.memory_available.map(|value| value as f64))
+    ].into_iter().flatten().collect()
+}
+
+fn container_points(stats: DockerContainerStatsData) -> Vec<Point> {
+    let (Some(host), Some(name)) = (stats.host, stats.name) else {
+        return Vec::new();
+    };
+    let series = container_series(&host, &name);
+    let point = |metric, value: Option<f64>| value.map(|value| Point { series: series.clone(), metric, value });
+    
+    [
+        point("cpuPercent", stats.cpu_percent),
+        point("memoryUsage", stats.memory_usage.map(|value| value as f64)),
+        point("memoryLimit", stats.memory_limit.map(|value| value as f64)),
+        point("networkRx", stats.network_rx.map(|value| value as f64)),
+        point("networkTx", stats.network_tx.map(|value| value as f64))
+    ].into_iter().flatten().collect()
+}
+
+/// Samples the host and the running containers into the store every `interval` seconds,
+/// then rolls the samples up and drops the expired points
+pub async fn record_history() {
+    let config = &config::get().metrics_history;
+    if config.interval == 0 {
+        return;
+    }
+    
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    
+    loop {
+        interval.tick().await;
+        let time = now() / config.interval * config.interval;
+        
+        let mut points = Vec::new();
+        if let Some(load) = telemetry::read_host_load().await {
+            points.extend(host_points(load));
+        }
+        match telemetry::sample_containers().await {
+            Ok(stats) => points.extend(stats.into_iter().flat_map(container_points)),
+            Err(e) => tracing::debug!("Failed to sample containers for the metrics history: {:?}", e)
+        }
+        
+        if let Err(e) = store::metrics::insert(config.interval, time, points).await {
+            tracing::error!("Failed to store metrics: {:?}", e);
+        }
+        
+        for rollup in &config.rollups {
+            if let Err(e) = store::metrics::rollup(config.interval, rollup.step, time).await {
+                tracing::error!(step = rollup.step, "Failed to roll metrics up: {:?}", e);
+            }
+        }
+        
+        for (step, retention) in tiers(config) {
+            match store::metrics::prune(step, time.saturating_sub(retention)).await {
+                Ok(0) => {},
+                Ok(pruned) => tracing::debug!(step, "Pruned {} metric points", pruned),
+                Err(e) => tracing::error!(step, "Failed to prune metrics: {:?}", e)
+            }
+        }
+    }
+}
+
+/// Answers a `MetricsQuery` from the finest resolution still covering the start of the range,
+/// averaged to the requested step
+pub async fn query(request: &MetricsQueryData) -> Result<MetricsQueryData, String> {
+    let config = &config::get().metrics_history;
+    if config.interval == 0 {
+        return Err("Metrics history is disabled".to_string());
+    }
+    
+    let now = now();
+    let to = request.to.unwrap_or(now);
+    let from = request.from.unwrap_or(to.saturating_sub(DEFAULT_RANGE));
+    if from >= to {
+        return Err("`from` must be before `to`".to_string());
+    }
+    
+    let tiers = tiers(config);
+    let covering: Vec<(u64, u64)> = tiers.iter().copied().filter(|(_, retention)| now.saturating_sub(*retention) <= from).collect();
+    let requested = request.step.unwrap_or(0).max((to - from).div_ceil(MAX_POINTS));
+    let (tier, _) = covering.iter()
+        .rfind(|(step, _)| *step <= requested)
+        .or(covering.first())
+        .or(tiers.iter().max_by_key(|(_, retention)| *retention))
+        .copied()
+        .unwrap_or((config.interval, config.retention));
+    let step = requested.div_ceil(tier).max(1) * tier;
+    
+    let series = match &request.container {
+        Some(container) => container_series(&hosts::name_or_default(request.host.as_deref()), container),
+        None => HOST_SERIES.to_string()
+    };
+    
+    let points = store::metrics::query(tier, series, from / step * step, to, step).await
+        .map_err(|e| format!("Failed to query metrics: {}", e))?;
+    let series = points.into_iter()
+        .filter(|(metric, _)| request.metrics.as_ref().is_none_or(|metrics| metrics.contains(metric)))
+        .map(|(metric, points)| MetricSeries { metric, points })
+        .collect();
+    
+    Ok(MetricsQueryData {
+        host: request.host.clone(),
+        container: request.container.clone(),
+        metrics: request.metrics.clone(),
+        from: Some(from),
+        to: Some(to),
+        step: Some(step),
+        series: Some(series),
+        error: None
+    })
+}
@@ -0,0 +1,158 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, process::Stdio, sync::{Arc, LazyLock, RwLock}, time::Duration};
+
+use bollard::{errors::Error, Docker, API_DEFAULT_VERSION};
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+
+use crate::{config::{self, HostConfig}, services::{agents, backend::DockerBackend}};
+
+/// Default name of the local socket host, see `hostName`
+pub const DEFAULT_HOST: &str = "local";
+
+/// Host value selecting every configured host at once
+pub const ALL_HOSTS: &str = "*";
+
+const TUNNEL_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Backends serving hosts by name: Docker clients of the configured hosts once connected, or
+/// the backends set with [`set_backend`]
+static BACKENDS: LazyLock<RwLock<HashMap<String, Arc<dyn DockerBackend>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Names of the configured hosts, in configuration order
+pub fn configured_names() -> Vec<String> {
+    let config = config::get();
+    if config.hosts.is_empty() {
+        return vec![config.host_name.clone()];
+    }
+    config.hosts.iter().map(|host| host.name.clone()).collect()
+}
+
+/// Names of the configured hosts followed by the connected agents
+pub fn names() -> Vec<String> {
+    let mut names = configured_names();
+    for agent in agents::names() {
+        if !names.contains(&agent) {
+            names.push(agent);
+        }
+    }
+    names
+}
+
+/// Host used by requests without a `host` field: the first configured one
+pub fn default_name() -> String {
+    configured_names().swap_remove(0)
+}
+
+pub fn name_or_default(host: Option<&str>) -> String {
+    host.map(str::to_string).unwrap_or_else(default_name)
+}
+
+/// Resolves the optional `host` field of a request into the host names it targets
+pub fn resolve(host: Option<&str>) -> Vec<String> {
+    match host {
+        Some(ALL_HOSTS) => names(),
+        Some(host) => vec![host.to_string()],
+        None => vec![default_name()]
+    }
+}
+
+/// Serves the host `name` with `backend` instead of a Docker client of its configuration
+pub fn set_backend(name: &str, backend: Arc<dyn DockerBackend>) {
+    BACKENDS.write().unwrap().insert(name.to_string(), backend);
+}
+
+/// Backend serving the host `name`, connecting to the configured host the first time
+pub fn backend(name: &str) -> Result<Option<Arc<dyn DockerBackend>>, Error> {
+    if let Some(backend) = BACKENDS.read().unwrap().get(name) {
+        return Ok(Some(backend.clone()));
+    }
+    if !configured_names().iter().any(|configured| configured == name) {
+        return Ok(None);
+    }
+    
+    let docker: Arc<dyn DockerBackend> = Arc::new(connect(Some(name))?);
+    set_backend(name, docker.clone());
+    Ok(Some(docker))
+}
+
+fn find(name: &str) -> Option<&'static HostConfig> {
+    config::get().hosts.iter().find(|host| host.name == name)
+}
+
+/// Builds a client for the named host, the default one when `None`
+pub fn connect(name: Option<&str>) -> Result<Docker, Error> {
+    let config = config::get();
+    let name = name_or_default(name);
+    
+    if config.hosts.is_empty() && name == config.host_name {
+        return Docker::connect_with_socket_defaults();
+    }
+    
+    let Some(host) = find(&name) else {
+        return Err(Error::IOError {
+            err: std::io::Error::new(std::io::ErrorKind::NotFound, format!("Unknown Docker host {:?}", name))
+        });
+    };
+    
+    let url = host.url.as_str();
+    if url.starts_with("ssh://") {
+        let socket = tunnel_socket(&host.name);
+        return Docker::connect_with_socket(&socket.to_string_lossy(), host.timeout, API_DEFAULT_VERSION);
+    }
+    
+    match &host.tls {
+        Some(tls) => Docker::connect_with_ssl(url, Path::new(&tls.key), Path::new(&tls.certificate), Path::new(&tls.ca), host.timeout, API_DEFAULT_VERSION),
+        None if url.starts_with("tcp://") || url.starts_with("http://") => Docker::connect_with_http(url, host.timeout, API_DEFAULT_VERSION),
+        None => Docker::connect_with_socket(url, host.timeout, API_DEFAULT_VERSION)
+    }
+}
+
+fn tunnel_socket(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("admin-api-{}.sock", name))
+}
+
+/// Keeps one SSH tunnel per `ssh://` host, forwarding a local socket to the remote Docker socket
+pub fn open_tunnels(shutdown: CancellationToken) {
+    for host in config::get().hosts.iter().filter(|host| host.url.starts_with("ssh://")) {
+        tokio::spawn(supervise_tunnel(host, shutdown.clone()));
+    }
+}
+
+async fn supervise_tunnel(host: &'static HostConfig, shutdown: CancellationToken) {
+    let destination = host.url.trim_start_matches("ssh://");
+    let (destination, port) = match destination.rsplit_once(':') {
+        Some((destination, port)) => (destination, Some(port)),
+        None => (destination, None)
+    };
+    let socket = tunnel_socket(&host.name);
+    
+    loop {
+        let mut command = Command::new("ssh");
+        command
+            .args(["-nNT", "-o", "ExitOnForwardFailure=yes", "-o", "StreamLocalBindUnlink=yes", "-o", "BatchMode=yes"])
+            .arg("-L")
+            .arg(format!("{}:{}", socket.to_string_lossy(), host.remote_socket))
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        if let Some(port) = port {
+            command.args(["-p", port]);
+        }
+        command.arg(destination);
+        
+        match command.spawn() {
+            Ok(mut child) => {
+                tracing::info!(host = host.name, "SSH tunnel opened");
+                tokio::select! {
+                    status = child.wait() => tracing::warn!(host = host.name, "SSH tunnel exited: {:?}", status),
+                    _ = shutdown.cancelled() => return
+                }
+            },
+            Err(e) => tracing::error!(host = host.name, "Failed to start SSH tunnel: {:?}", e)
+        }
+        
+        tokio::select! {
+            _ = tokio::time::sleep(TUNNEL_RETRY_INTERVAL) => {},
+            _ = shutdown.cancelled() => return
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Authoritative state of the containers of every host, kept from the Docker event stream: each
+//! change gets a version and is broadcast as a `DockerContainerPatch`, the latest patches are kept
+//! so a reconnecting client only receives what it missed.
+
+use std::{collections::{BTreeSet, HashMap, VecDeque}, sync::{LazyLock, Mutex}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use bollard::secret::ContainerSummary;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+use crate::{events::{docker::{ContainerFilters, ContainerPatch, DockerContainerSyncData, DockerEvent, HostContainerSummary, PatchOp}, Event}, serializers::SendEvent, services::{bus::EventBus, docker, hosts}};
+
+/// Docker sends several events per action (`create`, `attach`, `start`...), one listing covers them
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Full listing of every host, catching what the event streams missed while disconnected
+const RESYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Patches kept for reconnecting clients, older versions get a snapshot
+const MAX_PATCHES: usize = 1000;
+
+static INVENTORY: LazyLock<Mutex<Inventory>> = LazyLock::new(|| Mutex::new(Inventory::new()));
+
+struct Inventory {
+    /// Start of this server run, versions of another run are meaningless
+    epoch: u64,
+    version: u64,
+    /// Containers by host, then ID
+    hosts: HashMap<String, HashMap<String, ContainerSummary>>,
+    patches: VecDeque<ContainerPatch>
+}
+
+impl Inventory {
+    fn new() -> Self {
+        Self {
+            epoch: SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_millis() as u64).unwrap_or_default(),
+            version: 0,
+            hosts: HashMap::new(),
+            patches: VecDeque::new()
+        }
+    }
+    
+    /// Replaces the containers of `host` by a fresh listing, returning the patches leading there
+    fn apply(&mut self, host: &str, containers: Vec<ContainerSummary>) -> Vec<ContainerPatch> {
+        let mut previous = self.hosts.remove(host).unwrap_or_default();
+        let mut current = HashMap::new();
+        let mut patches = Vec::new();
+        
+        for container in containers {
+            let Some(id) = container.id.clone() else {
+                continue;
+            };
+            let op = match previous.remove(&id) {
+                None => Some(PatchOp::Add),
+                Some(old) if !same(&old, &container) => Some(PatchOp::Update),
+                Some(_) => None
+            };
+            if let Some(op) = op {
+                patches.push(self.patch(op, host, &id, Some(Box::new(container.clone()))));
+            }
+            current.insert(id, container);
+        }
+        for id in previous.into_keys() {
+            patches.push(self.patch(PatchOp::Remove, host, &id, None));
+        }
+        
+        self.hosts.insert(host.to_string(), current);
+        patches
+    }
+    
+    fn patch(&mut self, op: PatchOp, host: &str, id: &str, container: Option<Box<ContainerSummary>>) -> ContainerPatch {
+        self.version += 1;
+        let patch = ContainerPatch {
+            version: self.version,
+            op,
+            host: host.to_string(),
+            id: id.to_string(),
+            container
+        };
+        
+        self.patches.push_back(patch.clone());
+        if self.patches.len() > MAX_PATCHES {
+            self.patches.pop_front();
+        }
+        patch
+    }
+    
+    /// Patches after `since`, if they are all still kept
+    fn delta(&self, epoch: Option<u64>, since: Option<u64>) -> Option<Vec<ContainerPatch>> {
+        let since = since.filter(|since| epoch == Some(self.epoch) && *since <= self.version)?;
+        if since < self.version && self.patches.front().is_none_or(|patch| patch.version > since + 1) {
+            return None;
+        }
+        
+        Some(self.patches.iter().filter(|patch| patch.version > since).cloned().collect())
+    }
+    
+    fn snapshot(&self) -> Vec<HostContainerSummary> {
+        let mut containers: Vec<HostContainerSummary> = self.hosts.iter()
+            .flat_map(|(host, containers)| containers.values().map(|container| HostContainerSummary {
+                host: host.clone(),
+                container: container.clone()
+            }))
+            .collect();
+        containers.sort_by(|a, b| (&a.host, &a.container.id).cmp(&(&b.host, &b.container.id)));
+        containers
+    }
+}
+
+/// Whether two listings of a container differ, ignoring `Status`: its "Up 5 minutes" text changes on every listing
+fn same(old: &ContainerSummary, new: &ContainerSummary) -> bool {
+    ContainerSummary { status: None, ..old.clone() } == ContainerSummary { status: None, ..new.clone() }
+}
+
+/// Answers a `DockerContainerSync` request with the patches after `since`, or a snapshot
+pub fn sync(request: &DockerContainerSyncData) -> DockerContainerSyncData {
+    let inventory = INVENTORY.lock().unwrap();
+    let patches = inventory.delta(request.epoch, request.since);
+    
+    DockerContainerSyncData {
+        since: patches.as_ref().and(request.since),
+        epoch: Some(inventory.epoch),
+        version: Some(inventory.version),
+        containers: if patches.is_none() { Some(inventory.snapshot()) } else { None },
+        patches
+    }
+}
+
+/// Lists the containers of `host` and broadcasts the changes
+async fn refresh(bus: &mut EventBus, host: &str) {
+    let containers = match docker::get_containers(Some(host), &ContainerFilters::default()).await {
+        Ok(containers) => containers,
+        Err(e) => {
+            tracing::debug!(host, "Failed to list containers, keeping the last known state: {:?}", e);
+            return;
+        }
+    };
+    
+    let patches = INVENTORY.lock().unwrap().apply(host, containers);
+    for patch in patches {
+        bus.send_event(Event::Docker(DockerEvent::DockerContainerPatch { data: patch })).await;
+    }
+}
+
+/// Host whose containers changed, for container events
+fn changed_host(event: &Event) -> Option<String> {
+    match event {
+        Event::Docker(DockerEvent::DockerEventMessage { data }) if data.typ == "container" => Some(data.host.clone()),
+        _ => None
+    }
+}
+
+/// Every host, including those only known from past listings such as disconnected agents
+fn all_hosts() -> BTreeSet<String> {
+    let mut names: BTreeSet<String> = hosts::names().into_iter().collect();
+    names.extend(INVENTORY.lock().unwrap().hosts.keys().cloned());
+    names
+}
+
+pub async fn watch_containers(mut bus: EventBus) {
+    let mut rx = bus.subscribe();
+    let mut resync = tokio::time::interval(RESYNC_INTERVAL);
+    
+    loop {
+        let mut changed = BTreeSet::new();
+        tokio::select! {
+            event = rx.recv() => match event {
+                Ok(event) => match changed_host(&event) {
+                    Some(host) => {
+                        changed.insert(host);
+                    },
+                    None => continue
+                },
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Container inventory lagged, {} events dropped, listing every host", skipped);
+                    changed = all_hosts();
+                },
+                Err(RecvError::Closed) => break
+            },
+            _ = resync.tick() => changed = all_hosts()
+        }
+        
+        tokio::time::sleep(DEBOUNCE).await;
+        loop {
+            match rx.try_recv() {
+                Ok(event) => changed.extend(changed_host(&event)),
+                Err(TryRecvError::Lagged(_)) => changed = all_hosts(),
+                Err(_) => break
+            }
+        }
+        
+        for host in changed {
+            refresh(&mut bus, &host).await;
+        }
+    }
+}
+
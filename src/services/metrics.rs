@@ -0,0 +1,61 @@
+use std::sync::LazyLock;
+
+use prometheus::{histogram_opts, opts, Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge, Registry, TextEncoder};
+
+/// Registry of the server's own metrics, exposed on `/metrics` of the admin port
+pub static REGISTRY: LazyLock<Registry> = LazyLock::new(|| Registry::new_custom(Some("admin_api".to_string()), None).expect("valid registry prefix"));
+
+pub static SESSIONS_ACTIVE: LazyLock<IntGauge> = LazyLock::new(|| register(
+    IntGauge::with_opts(opts!("sessions_active", "Number of open WebTransport connections"))
+));
+
+pub static EVENTS_RECEIVED: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(opts!("events_received_total", "Events received from clients"), &["type"])
+));
+
+pub static HANDLER_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| register(
+    HistogramVec::new(histogram_opts!("handler_duration_seconds", "Time spent handling a client event"), &["type"])
+));
+
+pub static DOCKER_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(opts!("docker_errors_total", "Failed Docker API calls"), &["operation"])
+));
+
+pub static BROADCAST_LAGGED: LazyLock<IntCounter> = LazyLock::new(|| register(
+    IntCounter::with_opts(opts!("broadcast_lagged_total", "Broadcast events dropped because a session lagged behind"))
+));
+
+pub static BYTES_SENT: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(opts!("bytes_sent_total", "Bytes sent to clients"), &["channel"])
+));
+
+pub static NOTIFICATIONS: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(opts!("notifications_total", "Notifications per channel and outcome (sent, failed, dropped, rate_limited)"), &["channel", "outcome"])
+));
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(collector: prometheus::Result<T>) -> T {
+    let collector = collector.expect("valid metric definition");
+    if let Err(e) = REGISTRY.register(Box::new(collector.clone())) {
+        tracing::error!("Failed to register metric: {:?}", e);
+    }
+    collector
+}
+
+pub fn docker_error(operation: &str) {
+    DOCKER_ERRORS.with_label_values(&[operation]).inc();
+}
+
+/// Renders every registered metric in the Prometheus text format
+pub fn render(registry: &Registry) -> String {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&registry.gather(), &mut buffer) {
+        tracing::error!("Failed to encode metrics: {:?}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+/// Registers the metrics without labels so they are exported before their first update
+pub fn init() {
+    LazyLock::force(&SESSIONS_ACTIVE);
+    LazyLock::force(&BROADCAST_LAGGED);
+}
@@ -1 +1,18 @@
-pub mod docker;
\ No newline at end of file
+pub mod agents;
+pub mod alerts;
+pub mod auth;
+pub mod backend;
+pub mod bulk;
+pub mod bus;
+pub mod confirm;
+pub mod docker;
+pub mod event_history;
+pub mod exporter;
+pub mod history;
+pub mod hosts;
+pub mod inventory;
+pub mod metrics;
+pub mod notifiers;
+pub mod scheduler;
+pub mod store;
+pub mod telemetry;
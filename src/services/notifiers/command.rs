@@ -0,0 +1,69 @@
+use std::{error::Error, process::Stdio, time::Duration};
+
+use async_trait::async_trait;
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use super::{Notification, Notifier};
+
+/// Local program receiving the notification as JSON on stdin, and its main fields as
+/// `NOTIFICATION_*` environment variables
+pub struct CommandNotifier {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration
+}
+
+impl CommandNotifier {
+    pub fn new(command: &[String], timeout: Duration) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let Some((program, args)) = command.split_first() else {
+            return Err("Command notifier needs a program to run".into());
+        };
+        
+        Ok(Self {
+            program: program.clone(),
+            args: args.to_vec(),
+            timeout
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for CommandNotifier {
+    async fn notify(&self, notification: &Notification) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env("NOTIFICATION_KIND", &notification.kind)
+            .env("NOTIFICATION_TITLE", &notification.title)
+            .env("NOTIFICATION_MESSAGE", &notification.message)
+            .env("NOTIFICATION_HOST", notification.host.as_deref().unwrap_or_default())
+            .env("NOTIFICATION_CONTAINER_ID", notification.container_id.as_deref().unwrap_or_default())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        
+        // A program not reading its input blocks the write, the timeout covers it as well
+        let input = serde_json::to_vec(notification)?;
+        let run = async {
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(&input).await?;
+            }
+            child.wait().await
+        };
+        
+        let status = match tokio::time::timeout(self.timeout, run).await {
+            Ok(status) => status?,
+            Err(_) => {
+                if let Err(e) = child.kill().await {
+                    tracing::warn!(program = self.program, "Failed to kill notification command: {:?}", e);
+                }
+                return Err(format!("Command did not finish within {:?}", self.timeout).into());
+            }
+        };
+        
+        match status.success() {
+            true => Ok(()),
+            false => Err(format!("Command exited with {}", status).into())
+        }
+    }
+}
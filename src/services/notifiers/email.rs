@@ -0,0 +1,67 @@
+use std::{error::Error, time::Duration};
+
+use async_trait::async_trait;
+use lettre::{message::{header::ContentType, Mailbox}, transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::{NotifierConfig, SmtpSecurity};
+
+use super::{Notification, Notifier};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Plain text email through an SMTP relay
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>
+}
+
+impl EmailNotifier {
+    pub fn new(config: &NotifierConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let NotifierConfig::Email { host, port, security, username, password, from, to } = config else {
+            return Err("Not an email channel".into());
+        };
+        
+        let mut builder = match security {
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpSecurity::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?
+        };
+        if let Some(port) = port {
+            builder = builder.port(*port);
+        }
+        if let (Some(username), Some(password)) = (username, password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        
+        Ok(Self {
+            transport: builder.timeout(Some(TIMEOUT)).build(),
+            from: from.parse()?,
+            to: to.iter().map(|address| address.parse()).collect::<Result<_, _>>()?
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    async fn notify(&self, notification: &Notification) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(&notification.title)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        
+        let body = format!(
+            "{}\n\nKind: {}\nHost: {}\nContainer: {}\n",
+            notification.message,
+            notification.kind,
+            notification.host.as_deref().unwrap_or("-"),
+            notification.container_name.as_deref().or(notification.container_id.as_deref()).unwrap_or("-")
+        );
+        
+        self.transport.send(message.body(body)?).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use std::{collections::VecDeque, error::Error, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+
+use crate::{config::{self, ChannelConfig, NotifierConfig}, events::{alert::{AlertEvent, AlertState}, docker::DockerEvent, Event}, services::{bus::EventBus, metrics}};
+
+pub mod command;
+pub mod email;
+pub mod webhook;
+
+#[cfg(test)]
+mod tests;
+
+/// Notifications waiting for a channel, newer ones are dropped past this
+const QUEUE_SIZE: usize = 100;
+
+/// Message sent to the channels, built from an alert transition or a container action
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    /// `alert.firing`, `alert.resolved`, `container.start`, `container.stop` or `container.restart`
+    pub kind: String,
+    
+    pub title: String,
+    
+    pub message: String,
+    
+    pub host: Option<String>,
+    
+    pub container_id: Option<String>,
+    
+    pub container_name: Option<String>,
+    
+    /// UNIX timestamp in seconds
+    pub timestamp: u64
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, notification: &Notification) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+fn build(config: &NotifierConfig) -> Result<Box<dyn Notifier>, Box<dyn Error + Send + Sync>> {
+    Ok(match config {
+        NotifierConfig::Webhook { url, template, headers } => Box::new(webhook::WebhookNotifier::new(url, webhook::Payload::Template(template.clone()), headers)?),
+        NotifierConfig::Slack { url } => Box::new(webhook::WebhookNotifier::new(url, webhook::Payload::Slack, &Default::default())?),
+        NotifierConfig::Discord { url } => Box::new(webhook::WebhookNotifier::new(url, webhook::Payload::Discord, &Default::default())?),
+        NotifierConfig::Email { .. } => Box::new(email::EmailNotifier::new(config)?),
+        NotifierConfig::Command { command, timeout } => Box::new(command::CommandNotifier::new(command, Duration::from_secs(*timeout))?)
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default()
+}
+
+/// Notification for the events worth one: alert transitions and container lifecycle actions
+fn notification(event: &Event) -> Option<Notification> {
+    let (kind, title, message, host, container_id, container_name) = match event {
+        Event::Alert(AlertEvent::AlertChanged { data }) => {
+            let (kind, state) = match data.state {
+                AlertState::Firing => ("alert.firing", "FIRING"),
+                AlertState::Resolved => ("alert.resolved", "RESOLVED")
+            };
+            let subject = data.container_name.as_deref().or(data.container_id.as_deref()).unwrap_or(&data.host);
+            (kind, format!("[{}] {} on {}", state, data.rule, subject), data.message.clone(), Some(data.host.clone()), data.container_id.clone(), data.container_name.clone())
+        },
+        Event::Docker(DockerEvent::DockerContainerStart { data }) => ("container.start", "Container started".to_string(), String::new(), data.host.clone(), data.container_id.clone(), None),
+        Event::Docker(DockerEvent::DockerContainerStop { data }) => ("container.stop", "Container stopped".to_string(), String::new(), data.host.clone(), data.container_id.clone(), None),
+        Event::Docker(DockerEvent::DockerContainerRestart { data }) => ("container.restart", "Container restarted".to_string(), String::new(), data.host.clone(), data.container_id.clone(), None),
+        _ => return None
+    };
+    
+    let message = match message.is_empty() {
+        true => format!("{} {} on {}", title, container_id.as_deref().unwrap_or("?"), host.as_deref().unwrap_or("?")),
+        false => message
+    };
+    
+    Some(Notification {
+        kind: kind.to_string(),
+        title,
+        message,
+        host,
+        container_id,
+        container_name,
+        timestamp: now()
+    })
+}
+
+fn routes(channel: &ChannelConfig, notification: &Notification) -> bool {
+    let kind = channel.events.iter().any(|pattern| match pattern.strip_suffix('*') {
+        Some(prefix) => notification.kind.starts_with(prefix),
+        None => *pattern == notification.kind
+    });
+    let host = channel.hosts.is_empty() || notification.host.as_ref().is_some_and(|host| channel.hosts.contains(host));
+    
+    kind && host
+}
+
+/// Routes the notifications built from the bus to the configured channels, each delivering
+/// its own queue with retries and rate limiting
+pub async fn watch_notifications(bus: EventBus) {
+    let mut channels = Vec::new();
+    for channel in &config::get().notifications {
+        match build(&channel.notifier) {
+            Ok(notifier) => {
+                let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+                tokio::spawn(deliver(channel, notifier, rx));
+                channels.push((channel, tx));
+            },
+            Err(e) => tracing::error!(channel = channel.name, "Failed to set up notification channel: {:?}", e)
+        }
+    }
+    if channels.is_empty() {
+        return;
+    }
+    
+    let mut rx = bus.subscribe();
+    loop {
+        let event = match rx.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!("Notifications lagged, {} events dropped", skipped);
+                continue;
+            },
+            Err(RecvError::Closed) => break
+        };
+        
+        let Some(notification) = notification(&event) else {
+            continue;
+        };
+        
+        for (channel, tx) in channels.iter().filter(|(channel, _)| routes(channel, &notification)) {
+            if tx.try_send(notification.clone()).is_err() {
+                tracing::warn!(channel = channel.name, kind = notification.kind, "Notification queue full, dropping notification");
+                metrics::NOTIFICATIONS.with_label_values(&[&channel.name, "dropped"]).inc();
+            }
+        }
+    }
+}
+
+async fn deliver(channel: &'static ChannelConfig, notifier: Box<dyn Notifier>, mut rx: mpsc::Receiver<Notification>) {
+    let mut sent: VecDeque<Instant> = VecDeque::new();
+    let period = Duration::from_secs(channel.rate_limit.per);
+    
+    while let Some(notification) = rx.recv().await {
+        while sent.front().is_some_and(|sent_at| sent_at.elapsed() > period) {
+            sent.pop_front();
+        }
+        if sent.len() >= channel.rate_limit.max {
+            tracing::warn!(channel = channel.name, kind = notification.kind, "Notification rate limit reached, dropping notification");
+            metrics::NOTIFICATIONS.with_label_values(&[&channel.name, "rate_limited"]).inc();
+            continue;
+        }
+        sent.push_back(Instant::now());
+        
+        let mut delay = Duration::from_secs(channel.retry_delay);
+        for attempt in 0..=channel.retries {
+            match notifier.notify(&notification).await {
+                Ok(_) => {
+                    tracing::debug!(channel = channel.name, kind = notification.kind, "Notification sent");
+                    metrics::NOTIFICATIONS.with_label_values(&[&channel.name, "sent"]).inc();
+                    break;
+                },
+                Err(e) if attempt < channel.retries => {
+                    tracing::warn!(channel = channel.name, attempt, "Failed to send notification, retrying in {:?}: {:?}", delay, e);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                },
+                Err(e) => {
+                    tracing::error!(channel = channel.name, kind = notification.kind, "Failed to send notification: {:?}", e);
+                    metrics::NOTIFICATIONS.with_label_values(&[&channel.name, "failed"]).inc();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Channels against local stand-ins: an HTTP listener for webhooks, a minimal SMTP server for emails
+//! and a shell script for commands
+
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use axum::{extract::State, http::{HeaderMap, StatusCode}, routing::post, Json, Router};
+use serde_json::{json, Value};
+use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, net::TcpListener, sync::{mpsc, oneshot}};
+
+use super::*;
+use crate::config::SmtpSecurity;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Request {
+    headers: HeaderMap,
+    body: Value,
+    at: Instant
+}
+
+struct Endpoint {
+    /// Requests answered with a 500 before the endpoint accepts them
+    failures: Mutex<usize>,
+    requests: mpsc::UnboundedSender<Request>
+}
+
+async fn receive(State(endpoint): State<Arc<Endpoint>>, headers: HeaderMap, Json(body): Json<Value>) -> StatusCode {
+    let _ = endpoint.requests.send(Request { headers, body, at: Instant::now() });
+    
+    let mut failures = endpoint.failures.lock().unwrap();
+    match *failures {
+        0 => StatusCode::OK,
+        _ => {
+            *failures -= 1;
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// HTTP endpoint failing its first `failures` requests, with the requests it received
+async fn http_stand_in(failures: usize) -> (String, mpsc::UnboundedReceiver<Request>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let endpoint = Arc::new(Endpoint { failures: Mutex::new(failures), requests: tx });
+    let app = Router::new().route("/hook", post(receive)).with_state(endpoint);
+    
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (url, rx)
+}
+
+/// SMTP server accepting one message, with its envelope recipients and data
+async fn smtp_stand_in() -> (u16, oneshot::Receiver<(Vec<String>, String)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = oneshot::channel();
+    
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut recipients = Vec::new();
+        let mut data = String::new();
+        
+        writer.write_all(b"220 stand-in ESMTP\r\n").await.unwrap();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let command = line.to_uppercase();
+            let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                b"250 stand-in\r\n"
+            } else if command.starts_with("RCPT TO:") {
+                recipients.push(line[8..].trim().to_string());
+                b"250 OK\r\n"
+            } else if command.starts_with("DATA") {
+                writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n").await.unwrap();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line == "." {
+                        break;
+                    }
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                b"250 Queued\r\n"
+            } else if command.starts_with("QUIT") {
+                writer.write_all(b"221 Bye\r\n").await.unwrap();
+                break;
+            } else {
+                b"250 OK\r\n"
+            };
+            writer.write_all(reply).await.unwrap();
+        }
+        let _ = tx.send((recipients, data));
+    });
+    (port, rx)
+}
+
+fn notification() -> Notification {
+    Notification {
+        kind: "alert.firing".to_string(),
+        title: "[FIRING] cpu on web".to_string(),
+        message: "CPU above 90%".to_string(),
+        host: Some("prod".to_string()),
+        container_id: Some("abc123".to_string()),
+        container_name: Some("web".to_string()),
+        timestamp: 1700000000
+    }
+}
+
+fn channel(config: Value) -> ChannelConfig {
+    let mut channel = json!({ "name": "test", "kind": "command", "command": ["true"] });
+    channel.as_object_mut().unwrap().extend(config.as_object().unwrap().clone());
+    serde_json::from_value(channel).unwrap()
+}
+
+async fn next(requests: &mut mpsc::UnboundedReceiver<Request>) -> Request {
+    tokio::time::timeout(TIMEOUT, requests.recv()).await.expect("No request received").unwrap()
+}
+
+#[test]
+fn templates_take_the_notification_fields() {
+    let template = json!({
+        "summary": "{{ title }}: {{message}}",
+        "at": "{{timestamp}}",
+        "tags": ["{{host}}", "{{missing}}", "container {{containerName}}{{missing}}"],
+        "fixed": 3
+    });
+    
+    assert_eq!(webhook::render(&template, &json!(notification())), json!({
+        "summary": "[FIRING] cpu on web: CPU above 90%",
+        "at": 1700000000,
+        "tags": ["prod", null, "container web"],
+        "fixed": 3
+    }));
+}
+
+#[tokio::test]
+async fn webhooks_post_the_rendered_template_with_their_headers() {
+    let (url, mut requests) = http_stand_in(0).await;
+    let headers = HashMap::from([("Authorization".to_string(), "Bearer secret".to_string())]);
+    
+    let notifier = webhook::WebhookNotifier::new(&url, webhook::Payload::Template(Some(json!({ "text": "{{kind}} on {{host}}" }))), &headers).unwrap();
+    notifier.notify(&notification()).await.unwrap();
+    let request = next(&mut requests).await;
+    assert_eq!(request.body, json!({ "text": "alert.firing on prod" }));
+    assert_eq!(request.headers["authorization"], "Bearer secret");
+    
+    let notifier = webhook::WebhookNotifier::new(&url, webhook::Payload::Template(None), &HashMap::new()).unwrap();
+    notifier.notify(&notification()).await.unwrap();
+    assert_eq!(next(&mut requests).await.body, json!(notification()));
+}
+
+#[tokio::test]
+async fn slack_and_discord_get_their_payload_shapes() {
+    let (url, mut requests) = http_stand_in(0).await;
+    
+    build(&NotifierConfig::Slack { url: url.clone() }).unwrap().notify(&notification()).await.unwrap();
+    assert_eq!(next(&mut requests).await.body, json!({ "text": "*[FIRING] cpu on web*\nCPU above 90%" }));
+    
+    build(&NotifierConfig::Discord { url }).unwrap().notify(&notification()).await.unwrap();
+    assert_eq!(next(&mut requests).await.body, json!({ "content": "**[FIRING] cpu on web**\nCPU above 90%" }));
+}
+
+#[tokio::test]
+async fn failed_webhooks_are_reported() {
+    let (url, _requests) = http_stand_in(1).await;
+    
+    let notifier = build(&NotifierConfig::Slack { url }).unwrap();
+    assert!(notifier.notify(&notification()).await.is_err());
+    assert!(notifier.notify(&notification()).await.is_ok());
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_with_backoff() {
+    let (url, mut requests) = http_stand_in(2).await;
+    let channel: &'static ChannelConfig = Box::leak(Box::new(channel(json!({ "kind": "slack", "url": url, "retries": 3, "retryDelay": 1 }))));
+    
+    let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+    tx.send(notification()).await.unwrap();
+    drop(tx);
+    tokio::time::timeout(TIMEOUT, deliver(channel, build(&channel.notifier).unwrap(), rx)).await.expect("Delivery did not finish");
+    
+    let attempts: Vec<Instant> = std::iter::from_fn(|| requests.try_recv().ok()).map(|request| request.at).collect();
+    assert_eq!(attempts.len(), 3, "two failures then a success");
+    assert!(attempts[1] - attempts[0] >= Duration::from_secs(1));
+    assert!(attempts[2] - attempts[1] >= Duration::from_secs(2), "the delay doubles");
+}
+
+#[tokio::test]
+async fn deliveries_past_the_rate_limit_are_dropped() {
+    let (url, mut requests) = http_stand_in(0).await;
+    let channel: &'static ChannelConfig = Box::leak(Box::new(channel(json!({ "kind": "slack", "url": url, "rateLimit": { "max": 2, "per": 60 } }))));
+    
+    let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+    for _ in 0..5 {
+        tx.send(notification()).await.unwrap();
+    }
+    drop(tx);
+    tokio::time::timeout(TIMEOUT, deliver(channel, build(&channel.notifier).unwrap(), rx)).await.expect("Delivery did not finish");
+    
+    assert_eq!(std::iter::from_fn(|| requests.try_recv().ok()).count(), 2);
+}
+
+#[test]
+fn channels_route_by_kind_and_host() {
+    let alerts = channel(json!({ "events": ["alert.*"], "hosts": ["prod"] }));
+    let stops = channel(json!({ "events": ["container.stop"] }));
+    let on = |kind: &str, host: Option<&str>| Notification { kind: kind.to_string(), host: host.map(str::to_string), ..notification() };
+    
+    assert!(routes(&alerts, &on("alert.firing", Some("prod"))));
+    assert!(routes(&alerts, &on("alert.resolved", Some("prod"))));
+    assert!(!routes(&alerts, &on("alert.firing", Some("dev"))));
+    assert!(!routes(&alerts, &on("alert.firing", None)));
+    assert!(!routes(&alerts, &on("container.stop", Some("prod"))));
+    
+    assert!(routes(&stops, &on("container.stop", Some("dev"))));
+    assert!(routes(&stops, &on("container.stop", None)));
+    assert!(!routes(&stops, &on("container.start", Some("dev"))));
+    assert!(!routes(&stops, &on("container.stopped", Some("dev"))));
+}
+
+#[tokio::test]
+async fn emails_are_sent_through_the_relay() {
+    let (port, message) = smtp_stand_in().await;
+    let config = NotifierConfig::Email {
+        host: "127.0.0.1".to_string(),
+        port: Some(port),
+        security: SmtpSecurity::None,
+        username: None,
+        password: None,
+        from: "Admin API <admin@example.com>".to_string(),
+        to: vec!["ops@example.com".to_string(), "oncall@example.com".to_string()]
+    };
+    
+    build(&config).unwrap().notify(&notification()).await.unwrap();
+    let (recipients, data) = tokio::time::timeout(TIMEOUT, message).await.expect("No email received").unwrap();
+    
+    assert_eq!(recipients, ["<ops@example.com>", "<oncall@example.com>"]);
+    assert!(data.contains("Subject: [FIRING] cpu on web"), "{}", data);
+    assert!(data.contains("CPU above 90%"), "{}", data);
+    assert!(data.contains("Host: prod"), "{}", data);
+    assert!(data.contains("Container: web"), "{}", data);
+}
+
+#[tokio::test]
+async fn commands_get_the_notification_on_stdin_and_in_their_environment() {
+    let output = std::env::temp_dir().join(format!("admin-api-notification-{}", std::process::id()));
+    let script = r#"{ cat; echo; echo "$NOTIFICATION_KIND|$NOTIFICATION_TITLE|$NOTIFICATION_MESSAGE|$NOTIFICATION_HOST|$NOTIFICATION_CONTAINER_ID"; } > "$0""#;
+    let command = ["sh", "-c", script, output.to_str().unwrap()].map(str::to_string);
+    
+    build(&NotifierConfig::Command { command: command.to_vec(), timeout: 5 }).unwrap().notify(&notification()).await.unwrap();
+    let written = std::fs::read_to_string(&output).unwrap();
+    let _ = std::fs::remove_file(&output);
+    
+    let (stdin, env) = written.trim_end().split_once('\n').unwrap();
+    assert_eq!(serde_json::from_str::<Value>(stdin).unwrap(), json!(notification()));
+    assert_eq!(env, "alert.firing|[FIRING] cpu on web|CPU above 90%|prod|abc123");
+}
+
+#[tokio::test]
+async fn failing_or_hanging_commands_are_reported() {
+    let failing = build(&NotifierConfig::Command { command: ["sh", "-c", "cat > /dev/null; exit 3"].map(str::to_string).to_vec(), timeout: 5 }).unwrap();
+    assert!(failing.notify(&notification()).await.unwrap_err().to_string().contains("exit status: 3"));
+    
+    let hanging = build(&NotifierConfig::Command { command: ["sleep", "10"].map(str::to_string).to_vec(), timeout: 1 }).unwrap();
+    assert!(hanging.notify(&notification()).await.unwrap_err().to_string().contains("did not finish"));
+}
+
+#[tokio::test]
+async fn command_not_reading_its_input_is_killed_on_timeout() {
+    let pid_file = std::env::temp_dir().join(format!("notifier-pid-{}", std::process::id()));
+    let script = format!("echo $$ > {}; exec sleep 30", pid_file.display());
+    let stuck = build(&NotifierConfig::Command { command: vec!["sh".to_string(), "-c".to_string(), script], timeout: 1 }).unwrap();
+    // Larger than a pipe buffer, the write cannot complete
+    let mut notification = notification();
+    notification.container_name = Some("x".repeat(1 << 20));
+    
+    let error = tokio::time::timeout(TIMEOUT, stuck.notify(&notification)).await.expect("The write was not bounded by the timeout").unwrap_err();
+    assert!(error.to_string().contains("did not finish"), "{}", error);
+    
+    let pid = std::fs::read_to_string(&pid_file).unwrap();
+    let _ = std::fs::remove_file(&pid_file);
+    assert!(!std::path::Path::new(&format!("/proc/{}", pid.trim())).exists(), "the command was left running");
+}
@@ -0,0 +1,97 @@
+use std::{collections::HashMap, error::Error, time::Duration};
+
+use async_trait::async_trait;
+use reqwest::{header::{HeaderMap, HeaderName, HeaderValue}, Client};
+use serde_json::{json, Value};
+
+use super::{Notification, Notifier};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub enum Payload {
+    /// The notification itself when `None`
+    Template(Option<Value>),
+    Slack,
+    Discord
+}
+
+/// JSON POST to an HTTP endpoint
+pub struct WebhookNotifier {
+    client: Client,
+    url: String,
+    payload: Payload
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str, payload: Payload, headers: &HashMap<String, String>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut default_headers = HeaderMap::new();
+        for (name, value) in headers {
+            default_headers.insert(HeaderName::try_from(name.as_str())?, HeaderValue::try_from(value.as_str())?);
+        }
+        
+        let client = Client::builder()
+            .timeout(TIMEOUT)
+            .default_headers(default_headers)
+            .build()?;
+        
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            payload
+        })
+    }
+    
+    fn body(&self, notification: &Notification) -> Value {
+        match &self.payload {
+            Payload::Template(Some(template)) => render(template, &json!(notification)),
+            Payload::Template(None) => json!(notification),
+            Payload::Slack => json!({ "text": format!("*{}*\n{}", notification.title, notification.message) }),
+            Payload::Discord => json!({ "content": format!("**{}**\n{}", notification.title, notification.message) })
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, notification: &Notification) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.client.post(&self.url)
+            .json(&self.body(notification))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Fills the `{{field}}` placeholders of a template with the notification fields.
+/// A string made of a single placeholder takes the JSON value of the field.
+pub fn render(template: &Value, fields: &Value) -> Value {
+    match template {
+        Value::String(text) => {
+            if let Some(field) = text.strip_prefix("{{").and_then(|text| text.strip_suffix("}}"))
+                && !field.contains("{{") {
+                return fields.get(field.trim()).cloned().unwrap_or(Value::Null);
+            }
+            
+            let mut rendered = String::new();
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("{{") {
+                let Some(end) = rest[start..].find("}}") else {
+                    break;
+                };
+                rendered.push_str(&rest[..start]);
+                match fields.get(rest[start + 2..start + end].trim()) {
+                    Some(Value::String(value)) => rendered.push_str(value),
+                    Some(Value::Null) | None => {},
+                    Some(value) => rendered.push_str(&value.to_string())
+                }
+                rest = &rest[start + end + 2..];
+            }
+            rendered.push_str(rest);
+            Value::String(rendered)
+        },
+        Value::Array(values) => Value::Array(values.iter().map(|value| render(value, fields)).collect()),
+        Value::Object(map) => Value::Object(map.iter().map(|(key, value)| (key.clone(), render(value, fields))).collect()),
+        value => value.clone()
+    }
+}
@@ -0,0 +1,283 @@
+use std::{collections::HashMap, str::FromStr, sync::{atomic::{AtomicU64, Ordering}, LazyLock, Mutex}, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+use crate::{events::{docker::HostContainerSummary, schedule::{Schedule, ScheduleAction, ScheduleEvent, ScheduleResult, ScheduleRunData}, system::Identity, Event}, serializers::SendEvent, services::{auth, bus::EventBus, confirm, docker, store}};
+
+/// Longest sleep of the scheduler when no schedule is due
+const IDLE_INTERVAL: Duration = Duration::from_secs(3600);
+
+static SCHEDULES: LazyLock<Mutex<Vec<Schedule>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// When each schedule was created or last updated, it is not due for the times before
+static SAVED: LazyLock<Mutex<HashMap<String, DateTime<Utc>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Wakes the scheduler up when the schedules change
+static CHANGED: Notify = Notify::const_new();
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Parses a 5 fields cron expression, or a 6 or 7 fields one starting with seconds
+fn parse(expression: &str) -> Result<cron::Schedule, String> {
+    let expression = match expression.split_whitespace().count() {
+        5 => format!("0 {}", expression),
+        _ => expression.to_string()
+    };
+    
+    cron::Schedule::from_str(&expression).map_err(|e| format!("Invalid cron expression: {}", e))
+}
+
+fn next_run(schedule: &Schedule, after: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if schedule.enabled == Some(false) {
+        return None;
+    }
+    
+    parse(&schedule.cron).ok()?.after(after).next()
+}
+
+/// Start of the times a schedule may be due for: the last check, or its last save when later
+fn due_after(schedule: &Schedule, checked: &DateTime<Utc>) -> DateTime<Utc> {
+    let saved = schedule.id.as_ref().and_then(|id| SAVED.lock().unwrap().get(id).copied());
+    saved.map_or(*checked, |saved| saved.max(*checked))
+}
+
+fn validate(schedule: &Schedule) -> Result<(), String> {
+    parse(&schedule.cron)?;
+    
+    if schedule.action == ScheduleAction::Exec && schedule.command.as_ref().is_none_or(Vec::is_empty) {
+        return Err("The exec action needs a command".to_string());
+    }
+    if schedule.target.containers.is_empty() && schedule.target.labels.is_empty() {
+        return Err("The target selects no container, list containers or labels".to_string());
+    }
+    
+    Ok(())
+}
+
+/// Refuses a schedule acting on protected containers `identity` may not act on. Targets that cannot be
+/// listed now are checked when the schedule runs.
+async fn check_targets(schedule: &Schedule, identity: &Identity) -> Result<(), String> {
+    match docker::select_containers(&schedule.target).await {
+        Ok(targets) => confirm::check_protected(identity, schedule.action, &targets).await,
+        Err(e) => {
+            tracing::warn!(schedule = schedule.id, "Failed to resolve schedule targets, they are checked when it runs: {:?}", e);
+            Ok(())
+        }
+    }
+}
+
+fn new_id() -> String {
+    format!("{:x}{:04x}", Utc::now().timestamp_millis(), NEXT_ID.fetch_add(1, Ordering::Relaxed) & 0xffff)
+}
+
+/// Stored schedules, with their next run
+pub fn list() -> Vec<Schedule> {
+    let now = Utc::now();
+    SCHEDULES.lock().unwrap().iter()
+        .cloned()
+        .map(|mut schedule| {
+            schedule.next_run = next_run(&schedule, &now).map(|next| next.timestamp() as u64);
+            schedule
+        })
+        .collect()
+}
+
+pub async fn create(mut schedule: Schedule, identity: &Identity) -> Result<Schedule, String> {
+    validate(&schedule)?;
+    check_targets(&schedule, identity).await?;
+    
+    schedule.id = Some(new_id());
+    schedule.owner = Some(identity.name.clone());
+    schedule.enabled = Some(schedule.enabled.unwrap_or(true));
+    schedule.next_run = None;
+    schedule.last_run = None;
+    store::schedules::save(&schedule).await.map_err(|e| format!("Failed to store schedule: {}", e))?;
+    SAVED.lock().unwrap().insert(schedule.id.clone().unwrap_or_default(), Utc::now());
+    SCHEDULES.lock().unwrap().push(schedule.clone());
+    
+    CHANGED.notify_one();
+    tracing::info!(schedule = schedule.id, cron = schedule.cron, "Schedule created");
+    schedule.next_run = next_run(&schedule, &Utc::now()).map(|next| next.timestamp() as u64);
+    Ok(schedule)
+}
+
+pub async fn update(mut schedule: Schedule, identity: &Identity) -> Result<Schedule, String> {
+    let Some(id) = schedule.id.clone() else {
+        return Err("Missing schedule id".to_string());
+    };
+    validate(&schedule)?;
+    check_targets(&schedule, identity).await?;
+    
+    let Some(last_run) = SCHEDULES.lock().unwrap().iter()
+        .find(|current| current.id.as_ref() == Some(&id))
+        .map(|current| current.last_run.clone()) else {
+        return Err(format!("Unknown schedule {}", id));
+    };
+    schedule.enabled = Some(schedule.enabled.unwrap_or(true));
+    schedule.owner = Some(identity.name.clone());
+    schedule.next_run = None;
+    schedule.last_run = last_run;
+    store::schedules::save(&schedule).await.map_err(|e| format!("Failed to store schedule: {}", e))?;
+    SAVED.lock().unwrap().insert(id.clone(), Utc::now());
+    
+    if let Some(current) = SCHEDULES.lock().unwrap().iter_mut().find(|current| current.id.as_ref() == Some(&id)) {
+        *current = schedule.clone();
+    }
+    
+    CHANGED.notify_one();
+    tracing::info!(schedule = id, cron = schedule.cron, "Schedule updated");
+    schedule.next_run = next_run(&schedule, &Utc::now()).map(|next| next.timestamp() as u64);
+    Ok(schedule)
+}
+
+pub async fn delete(id: &str) -> Result<(), String> {
+    match store::schedules::delete(id).await {
+        Ok(true) => {},
+        Ok(false) => return Err(format!("Unknown schedule {}", id)),
+        Err(e) => return Err(format!("Failed to delete schedule: {}", e))
+    }
+    SCHEDULES.lock().unwrap().retain(|schedule| schedule.id.as_deref() != Some(id));
+    SAVED.lock().unwrap().remove(id);
+    
+    CHANGED.notify_one();
+    tracing::info!(schedule = id, "Schedule deleted");
+    Ok(())
+}
+
+async fn load() {
+    let schedules = match store::schedules::list().await {
+        Ok(schedules) => schedules,
+        Err(e) => {
+            tracing::error!("Failed to load schedules: {:?}", e);
+            return;
+        }
+    };
+    
+    for schedule in schedules.iter().filter(|schedule| validate(schedule).is_err()) {
+        tracing::warn!(schedule = schedule.id, "Stored schedule is invalid and will not run");
+    }
+    tracing::info!("Loaded {} schedules", schedules.len());
+    *SCHEDULES.lock().unwrap() = schedules;
+}
+
+/// Runs the schedules when they are due until shutdown
+pub async fn run_scheduler(bus: EventBus, shutdown: CancellationToken) {
+    load().await;
+    let mut checked = Utc::now();
+    
+    loop {
+        let next = SCHEDULES.lock().unwrap().iter()
+            .filter_map(|schedule| next_run(schedule, &due_after(schedule, &checked)))
+            .min();
+        let wait = match next {
+            Some(next) => (next - Utc::now()).to_std().unwrap_or_default().min(IDLE_INTERVAL),
+            None => IDLE_INTERVAL
+        };
+        
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {},
+            // Runs due since the last check are still collected on the next wake up, saved schedules
+            // only from their save on
+            _ = CHANGED.notified() => continue,
+            _ = shutdown.cancelled() => break
+        }
+        
+        let now = Utc::now();
+        let due: Vec<Schedule> = SCHEDULES.lock().unwrap().iter()
+            .filter(|schedule| next_run(schedule, &due_after(schedule, &checked)).is_some_and(|next| next <= now))
+            .cloned()
+            .collect();
+        checked = now;
+        
+        for schedule in due {
+            tokio::spawn(run(bus.clone(), schedule));
+        }
+    }
+}
+
+async fn run(mut bus: EventBus, schedule: Schedule) {
+    let Some(id) = schedule.id.clone() else {
+        return;
+    };
+    let started_at = Utc::now().timestamp() as u64;
+    // The owner as currently configured, a revoked admin no longer acts on protected containers
+    let owner = schedule.owner.as_deref().and_then(auth::client).unwrap_or_else(auth::anonymous);
+    
+    let (results, error) = match docker::select_containers(&schedule.target).await {
+        Ok(targets) => (join_all(targets.iter().map(|target| apply(&schedule, &owner, target))).await, None),
+        Err(e) => {
+            tracing::error!(schedule = id, "Failed to resolve schedule targets: {:?}", e);
+            (Vec::new(), Some(e.to_string()))
+        }
+    };
+    
+    let run = ScheduleRunData {
+        schedule_id: id.clone(),
+        action: schedule.action,
+        started_at,
+        finished_at: Utc::now().timestamp() as u64,
+        results,
+        error
+    };
+    audit(&run).await;
+    
+    if let Some(schedule) = SCHEDULES.lock().unwrap().iter_mut().find(|schedule| schedule.id.as_ref() == Some(&id)) {
+        schedule.last_run = Some(run.clone());
+    }
+    if let Err(e) = store::schedules::set_last_run(&run).await {
+        tracing::error!(schedule = id, "Failed to store schedule run: {:?}", e);
+    }
+    
+    bus.send_event(Event::Schedule(ScheduleEvent::ScheduleRun { data: run })).await;
+}
+
+async fn apply(schedule: &Schedule, owner: &Identity, target: &HostContainerSummary) -> ScheduleResult {
+    let host = Some(target.host.as_str());
+    let id = target.container.id.clone().unwrap_or_default();
+    
+    let protected = confirm::check_protected(owner, schedule.action, std::slice::from_ref(target)).await;
+    let (result, exec) = match schedule.action {
+        _ if protected.is_err() => (protected, None),
+        ScheduleAction::Start => (docker::start_container(host, &id).await.map_err(|e| e.to_string()), None),
+        ScheduleAction::Stop => (docker::stop_container(host, &id).await.map_err(|e| e.to_string()), None),
+        ScheduleAction::Restart => (docker::restart_container(host, &id).await.map_err(|e| e.to_string()), None),
+        ScheduleAction::Exec => {
+            let command = schedule.command.clone().unwrap_or_default();
+            match docker::exec_container(host, &id, command).await {
+                Ok(output) if output.exit_code == Some(0) => (Ok(()), Some(output)),
+                Ok(output) => (Err(format!("Command exited with {:?}", output.exit_code)), Some(output)),
+                Err(e) => (Err(e.to_string()), None)
+            }
+        }
+    };
+    
+    ScheduleResult {
+        host: target.host.clone(),
+        container_id: id,
+        name: target.container.names.as_ref().and_then(|names| names.first()).map(|name| name.trim_start_matches('/').to_string()),
+        success: result.is_ok(),
+        error: result.err(),
+        exec
+    }
+}
+
+/// Logs the run and records it in the audit log
+async fn audit(run: &ScheduleRunData) {
+    let failures = run.results.iter().filter(|result| !result.success).count();
+    tracing::info!(
+        target: "audit",
+        schedule = run.schedule_id,
+        action = ?run.action,
+        targets = run.results.len(),
+        failures,
+        error = run.error,
+        "Scheduled action ran"
+    );
+    
+    if let Err(e) = store::audit::append("schedule.run", Some(&run.schedule_id), run).await {
+        tracing::error!(schedule = run.schedule_id, "Failed to audit schedule run: {:?}", e);
+    }
+}
@@ -0,0 +1,40 @@
+use rusqlite::params;
+
+use crate::events::alert::{Alert, AlertState};
+
+use super::{call, StoreResult};
+
+/// Records an alert transition
+pub async fn record(alert: &Alert) -> StoreResult<()> {
+    let id = alert.id.clone();
+    let state = match alert.state {
+        AlertState::Firing => "firing",
+        AlertState::Resolved => "resolved"
+    };
+    let time = alert.resolved_at.unwrap_or(alert.fired_at) as i64;
+    let alert = serde_json::to_string(alert)?;
+    
+    call(move |connection| {
+        connection.execute(
+            "INSERT INTO alert_history (alert_id, state, time, alert) VALUES (?1, ?2, ?3, ?4)",
+            params![id, state, time, alert]
+        )?;
+        Ok(())
+    }).await
+}
+
+/// Most recently resolved alerts first
+pub async fn resolved(limit: usize) -> StoreResult<Vec<Alert>> {
+    call(move |connection| {
+        let mut statement = connection.prepare(
+            "SELECT alert FROM alert_history WHERE state = 'resolved' ORDER BY time DESC, id DESC LIMIT ?1"
+        )?;
+        let rows = statement.query_map(params![limit as i64], |row| row.get::<_, String>(0))?;
+        
+        let mut alerts = Vec::new();
+        for row in rows {
+            alerts.push(serde_json::from_str(&row?)?);
+        }
+        Ok(alerts)
+    }).await
+}
@@ -0,0 +1,20 @@
+use rusqlite::params;
+use serde::Serialize;
+
+use super::{call, StoreResult};
+
+/// Appends an entry to the audit log: what happened (`kind`, e.g. `schedule.run`), to what (`subject`,
+/// such as a schedule ID) and the details as JSON
+pub async fn append<T: Serialize>(kind: &str, subject: Option<&str>, details: &T) -> StoreResult<()> {
+    let kind = kind.to_string();
+    let subject = subject.map(str::to_string);
+    let details = serde_json::to_string(details)?;
+    
+    call(move |connection| {
+        connection.execute(
+            "INSERT INTO audit (time, kind, subject, details) VALUES (unixepoch(), ?1, ?2, ?3)",
+            params![kind, subject, details]
+        )?;
+        Ok(())
+    }).await
+}
@@ -0,0 +1,113 @@
+use rusqlite::{params, types::ToSql};
+
+use crate::events::docker::{DockerEventHistoryData, DockerEventRecord};
+
+use super::{call, StoreResult};
+
+/// Container IDs shorter than this only match in full
+const MIN_ID_PREFIX: usize = 12;
+
+/// Stores an event, returning its ID
+pub async fn record(event: &DockerEventRecord) -> StoreResult<i64> {
+    let event = event.clone();
+    let attributes = serde_json::to_string(&event.attributes)?;
+    
+    call(move |connection| {
+        connection.execute(
+            "INSERT INTO docker_events (time, time_nano, host, type, action, actor_id, name, attributes)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                event.time, event.time_nano, event.host, event.typ, event.action, event.actor_id,
+                event.attributes.get("name"), attributes
+            ]
+        )?;
+        Ok(connection.last_insert_rowid())
+    }).await
+}
+
+pub async fn prune(before: i64) -> StoreResult<usize> {
+    call(move |connection| {
+        Ok(connection.execute("DELETE FROM docker_events WHERE time < ?1", params![before])?)
+    }).await
+}
+
+/// Events matching the filters of the request, most recent first, along with the cursor of the next page
+pub async fn query(filter: &DockerEventHistoryData, limit: usize) -> StoreResult<(Vec<DockerEventRecord>, Option<i64>)> {
+    let mut conditions = Vec::new();
+    let mut values: Vec<Box<dyn ToSql + Send>> = Vec::new();
+    let mut bind = |value: Box<dyn ToSql + Send>| {
+        values.push(value);
+        format!("?{}", values.len())
+    };
+    
+    if let Some(host) = &filter.host {
+        conditions.push(format!("host = {}", bind(Box::new(host.clone()))));
+    }
+    if let Some(container) = &filter.container {
+        let index = bind(Box::new(container.clone()));
+        let condition = match container.len() >= MIN_ID_PREFIX {
+            true => format!("(name = {0} OR substr(actor_id, 1, length({0})) = {0})", index),
+            false => format!("(name = {0} OR actor_id = {0})", index)
+        };
+        conditions.push(condition);
+    }
+    for (column, list) in [("action", &filter.actions), ("type", &filter.types)] {
+        if let Some(list) = list {
+            let indexes: Vec<String> = list.iter().map(|value| bind(Box::new(value.clone()))).collect();
+            conditions.push(format!("{} IN ({})", column, indexes.join(", ")));
+        }
+    }
+    if let Some(from) = filter.from {
+        conditions.push(format!("time >= {}", bind(Box::new(from))));
+    }
+    if let Some(to) = filter.to {
+        conditions.push(format!("time < {}", bind(Box::new(to))));
+    }
+    if let Some(cursor) = filter.cursor {
+        conditions.push(format!("id < {}", bind(Box::new(cursor))));
+    }
+    
+    let mut sql = "SELECT id, time, time_nano, host, type, action, actor_id, attributes FROM docker_events".to_string();
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    // One more row tells whether there is a next page
+    sql.push_str(&format!(" ORDER BY id DESC LIMIT {}", bind(Box::new(limit as i64 + 1))));
+    
+    call(move |connection| {
+        let mut statement = connection.prepare(&sql)?;
+        let rows = statement.query_map(
+            rusqlite::params_from_iter(values.iter().map(|value| value.as_ref() as &dyn ToSql)),
+            |row| Ok((
+                DockerEventRecord {
+                    id: Some(row.get(0)?),
+                    time: row.get(1)?,
+                    time_nano: row.get(2)?,
+                    host: row.get(3)?,
+                    typ: row.get(4)?,
+                    action: row.get(5)?,
+                    actor_id: row.get(6)?,
+                    attributes: Default::default()
+                },
+                row.get::<_, String>(7)?
+            ))
+        )?;
+        
+        let mut events = Vec::new();
+        for row in rows {
+            let (mut event, attributes) = row?;
+            event.attributes = serde_json::from_str(&attributes)?;
+            events.push(event);
+        }
+        
+        let next_cursor = match events.len() > limit {
+            true => {
+                events.truncate(limit);
+                events.last().and_then(|event| event.id)
+            },
+            false => None
+        };
+        Ok((events, next_cursor))
+    }).await
+}
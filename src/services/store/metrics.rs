@@ -0,0 +1,76 @@
+use rusqlite::params;
+
+use super::{call, StoreResult};
+
+/// Value of a metric of a series at a point in time
+pub struct Point {
+    pub series: String,
+    pub metric: &'static str,
+    pub value: f64
+}
+
+pub async fn insert(step: u64, time: u64, points: Vec<Point>) -> StoreResult<()> {
+    call(move |connection| {
+        let transaction = connection.transaction()?;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT OR REPLACE INTO metric_points (step, series, metric, time, value) VALUES (?1, ?2, ?3, ?4, ?5)"
+            )?;
+            for point in points {
+                statement.execute(params![step as i64, point.series, point.metric, time as i64, point.value])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }).await
+}
+
+/// Averages the points of `source` into the `step` buckets ended before `until`, starting again
+/// from the last bucket written
+pub async fn rollup(source: u64, step: u64, until: u64) -> StoreResult<()> {
+    call(move |connection| {
+        let until = (until / step * step) as i64;
+        connection.execute(
+            "INSERT OR REPLACE INTO metric_points (step, series, metric, time, value)
+            SELECT ?2, series, metric, (time / ?2) * ?2 AS bucket, AVG(value) FROM metric_points
+            WHERE step = ?1
+                AND time >= COALESCE((SELECT MAX(time) FROM metric_points WHERE step = ?2), 0)
+                AND time < ?3
+            GROUP BY series, metric, bucket",
+            params![source as i64, step as i64, until]
+        )?;
+        Ok(())
+    }).await
+}
+
+pub async fn prune(step: u64, before: u64) -> StoreResult<usize> {
+    call(move |connection| {
+        Ok(connection.execute("DELETE FROM metric_points WHERE step = ?1 AND time < ?2", params![step as i64, before as i64])?)
+    }).await
+}
+
+/// Points of a series between `from` (inclusive) and `to` (exclusive), averaged per `bucket` seconds
+/// and grouped by metric
+pub async fn query(step: u64, series: String, from: u64, to: u64, bucket: u64) -> StoreResult<Vec<(String, Vec<(u64, f64)>)>> {
+    call(move |connection| {
+        let mut statement = connection.prepare(
+            "SELECT metric, (time / ?5) * ?5 AS bucket, AVG(value) FROM metric_points
+            WHERE step = ?1 AND series = ?2 AND time >= ?3 AND time < ?4
+            GROUP BY metric, bucket ORDER BY metric, bucket"
+        )?;
+        let rows = statement.query_map(
+            params![step as i64, series, from as i64, to as i64, bucket as i64],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, f64>(2)?))
+        )?;
+        
+        let mut series: Vec<(String, Vec<(u64, f64)>)> = Vec::new();
+        for row in rows {
+            let (metric, time, value) = row?;
+            match series.last_mut() {
+                Some((current, points)) if *current == metric => points.push((time as u64, value)),
+                _ => series.push((metric, vec![(time as u64, value)]))
+            }
+        }
+        Ok(series)
+    }).await
+}
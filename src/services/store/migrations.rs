@@ -0,0 +1,72 @@
+use rusqlite::Connection;
+
+use super::StoreResult;
+
+/// Schema changes, applied in order once each. `user_version` records how many already ran,
+/// so entries must never be edited or reordered, only appended.
+const MIGRATIONS: &[&str] = &[
+    // 1: schedules, audit log and alert history
+    "CREATE TABLE schedules (
+        id TEXT PRIMARY KEY,
+        created_at INTEGER NOT NULL,
+        definition TEXT NOT NULL,
+        last_run TEXT
+    );
+    CREATE TABLE audit (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        time INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        subject TEXT,
+        details TEXT NOT NULL
+    );
+    CREATE INDEX audit_kind_time ON audit (kind, time);
+    CREATE TABLE alert_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        alert_id TEXT NOT NULL,
+        state TEXT NOT NULL,
+        time INTEGER NOT NULL,
+        alert TEXT NOT NULL
+    );
+    CREATE INDEX alert_history_time ON alert_history (time);",
+    // 2: metrics history, one row per step (raw interval or rollup), series, metric and point
+    "CREATE TABLE metric_points (
+        step INTEGER NOT NULL,
+        series TEXT NOT NULL,
+        metric TEXT NOT NULL,
+        time INTEGER NOT NULL,
+        value REAL NOT NULL,
+        PRIMARY KEY (step, series, metric, time)
+    ) WITHOUT ROWID;",
+    // 3: Docker event history, the name of the actor is copied out of its attributes for filtering
+    "CREATE TABLE docker_events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        time INTEGER NOT NULL,
+        time_nano INTEGER NOT NULL,
+        host TEXT NOT NULL,
+        type TEXT NOT NULL,
+        action TEXT NOT NULL,
+        actor_id TEXT,
+        name TEXT,
+        attributes TEXT NOT NULL
+    );
+    CREATE INDEX docker_events_time ON docker_events (time);
+    CREATE INDEX docker_events_actor ON docker_events (actor_id);
+    CREATE INDEX docker_events_name ON docker_events (name);"
+];
+
+pub fn apply(connection: &mut Connection) -> StoreResult<()> {
+    let version: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        return Err(format!("Database schema version {} is newer than this server ({})", version, MIGRATIONS.len()).into());
+    }
+    
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index + 1)?;
+        transaction.commit()?;
+        tracing::info!("Applied database migration {}", index + 1);
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::{error::Error, path::Path, sync::{Arc, Mutex, OnceLock}, time::Duration};
+
+use rusqlite::Connection;
+
+pub mod alerts;
+pub mod audit;
+pub mod events;
+pub mod metrics;
+pub mod migrations;
+pub mod schedules;
+
+const DATABASE_FILE: &str = "admin-api.db";
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type StoreResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+static STORE: OnceLock<Arc<Mutex<Connection>>> = OnceLock::new();
+
+/// Opens the database of the data directory, creating both if needed, and applies the pending migrations
+pub fn open(data_dir: &str) -> StoreResult<()> {
+    std::fs::create_dir_all(data_dir)?;
+    let path = Path::new(data_dir).join(DATABASE_FILE);
+    
+    let mut connection = Connection::open(&path)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    connection.pragma_update(None, "journal_mode", "WAL")?;
+    connection.pragma_update(None, "foreign_keys", true)?;
+    migrations::apply(&mut connection)?;
+    
+    if STORE.set(Arc::new(Mutex::new(connection))).is_err() {
+        return Err("Store already opened".into());
+    }
+    
+    tracing::info!(path = %path.display(), "Store opened");
+    Ok(())
+}
+
+/// Runs a query on the blocking pool, SQLite calls being synchronous
+pub async fn call<T, F>(query: F) -> StoreResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut Connection) -> StoreResult<T> + Send + 'static
+{
+    let Some(store) = STORE.get().cloned() else {
+        return Err("Store is not open".into());
+    };
+    
+    tokio::task::spawn_blocking(move || {
+        let mut connection = store.lock().map_err(|_| "Store connection poisoned")?;
+        query(&mut connection)
+    }).await?
+}
@@ -0,0 +1,57 @@
+use rusqlite::params;
+
+use crate::events::schedule::{Schedule, ScheduleRunData};
+
+use super::{call, StoreResult};
+
+/// Every schedule, in creation order
+pub async fn list() -> StoreResult<Vec<Schedule>> {
+    call(|connection| {
+        let mut statement = connection.prepare("SELECT definition, last_run FROM schedules ORDER BY created_at, id")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)))?;
+        
+        let mut schedules = Vec::new();
+        for row in rows {
+            let (definition, last_run) = row?;
+            let mut schedule: Schedule = serde_json::from_str(&definition)?;
+            schedule.last_run = last_run.as_deref().map(serde_json::from_str).transpose()?;
+            schedules.push(schedule);
+        }
+        Ok(schedules)
+    }).await
+}
+
+/// Inserts or replaces the definition of a schedule, keeping its last run
+pub async fn save(schedule: &Schedule) -> StoreResult<()> {
+    let Some(id) = schedule.id.clone() else {
+        return Err("Schedule without id".into());
+    };
+    let mut definition = schedule.clone();
+    definition.next_run = None;
+    definition.last_run = None;
+    let definition = serde_json::to_string(&definition)?;
+    
+    call(move |connection| {
+        connection.execute(
+            "INSERT INTO schedules (id, created_at, definition) VALUES (?1, unixepoch(), ?2)
+            ON CONFLICT (id) DO UPDATE SET definition = excluded.definition",
+            params![id, definition]
+        )?;
+        Ok(())
+    }).await
+}
+
+pub async fn delete(id: &str) -> StoreResult<bool> {
+    let id = id.to_string();
+    call(move |connection| Ok(connection.execute("DELETE FROM schedules WHERE id = ?1", params![id])? > 0)).await
+}
+
+pub async fn set_last_run(run: &ScheduleRunData) -> StoreResult<()> {
+    let id = run.schedule_id.clone();
+    let run = serde_json::to_string(run)?;
+    
+    call(move |connection| {
+        connection.execute("UPDATE schedules SET last_run = ?2 WHERE id = ?1", params![id, run])?;
+        Ok(())
+    }).await
+}
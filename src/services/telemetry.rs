@@ -0,0 +1,150 @@
+use std::{sync::atomic::{AtomicUsize, Ordering}, time::Duration};
+
+use bollard::{container::{MemoryStatsStats, Stats}, errors::Error};
+use futures::future::join_all;
+
+use crate::{events::{docker::{ContainerFilters, DockerContainerStatsData, DockerEvent}, system::{SystemEvent, SystemLoadData, Topic}, Event}, serializers::SendEvent, services::{bus::EventBus, docker, hosts}};
+
+const INTERVAL: Duration = Duration::from_secs(2);
+
+/// Live subscriptions per sampled topic, a topic is only sampled while somebody listens
+static HOST_LOAD_SUBSCRIBERS: AtomicUsize = AtomicUsize::new(0);
+static CONTAINER_STATS_SUBSCRIBERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counter of the topic, `None` for the topics broadcast regardless (Docker events and patches)
+fn subscribers(topic: Topic) -> Option<&'static AtomicUsize> {
+    match topic {
+        Topic::HostLoad => Some(&HOST_LOAD_SUBSCRIBERS),
+        Topic::ContainerStats => Some(&CONTAINER_STATS_SUBSCRIBERS),
+        Topic::DockerEvents | Topic::Containers => None
+    }
+}
+
+fn subscribed(topic: Topic) -> bool {
+    subscribers(topic).is_some_and(|count| count.load(Ordering::Relaxed) > 0)
+}
+
+pub fn add_subscriber(topic: Topic) {
+    if let Some(count) = subscribers(topic) {
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn remove_subscriber(topic: Topic) {
+    if let Some(count) = subscribers(topic) {
+        count.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub async fn sample_telemetry(mut bus: EventBus) {
+    let mut interval = tokio::time::interval(INTERVAL);
+    
+    loop {
+        interval.tick().await;
+        
+        if subscribed(Topic::HostLoad) {
+            match read_host_load().await {
+                Some(load) => bus.send_event(Event::System(SystemEvent::SystemLoad { data: load })).await,
+                None => tracing::trace!("Host load is not available on this platform")
+            }
+        }
+        
+        if !subscribed(Topic::ContainerStats) {
+            continue;
+        }
+        let stats = match sample_containers().await {
+            Ok(stats) => stats,
+            Err(error) => {
+                tracing::error!("Failed to list containers for telemetry: {:?}", error);
+                continue;
+            }
+        };
+        
+        for stats in stats {
+            bus.send_event(Event::Docker(DockerEvent::DockerContainerStats { data: stats })).await;
+        }
+    }
+}
+
+/// Stats of the running containers of every host, the containers failing are logged and skipped
+pub async fn sample_containers() -> Result<Vec<DockerContainerStatsData>, Error> {
+    let containers = docker::get_host_containers(Some(hosts::ALL_HOSTS), &ContainerFilters::default()).await?;
+    
+    let running = containers.iter()
+        .filter(|summary| summary.container.state.as_deref() == Some("running"))
+        .filter_map(|summary| Some((summary.host.as_str(), summary.container.id.as_deref()?)));
+    
+    let stats = join_all(running.map(|(host, id)| async move {
+        (host, docker::get_container_stats(Some(host), id, false).await)
+    })).await;
+    
+    Ok(stats.into_iter()
+        .filter_map(|(host, stats)| match stats {
+            Ok(stats) => Some(container_stats(host, &stats)),
+            Err(error) => {
+                tracing::warn!(host, "Failed to get container stats: {:?}", error);
+                None
+            }
+        })
+        .collect())
+}
+
+pub fn container_stats(host: &str, stats: &Stats) -> DockerContainerStatsData {
+    let cpu_delta = stats.cpu_stats.cpu_usage.total_usage as f64 - stats.precpu_stats.cpu_usage.total_usage as f64;
+    let system_delta = stats.cpu_stats.system_cpu_usage.unwrap_or(0) as f64 - stats.precpu_stats.system_cpu_usage.unwrap_or(0) as f64;
+    let online_cpus = stats.cpu_stats.online_cpus
+        .or_else(|| stats.cpu_stats.cpu_usage.percpu_usage.as_ref().map(|usage| usage.len() as u64))
+        .unwrap_or(1) as f64;
+    let cpu_percent = if cpu_delta > 0.0 && system_delta > 0.0 {
+        Some(cpu_delta / system_delta * online_cpus * 100.0)
+    } else {
+        Some(0.0)
+    };
+    
+    // Page cache is reclaimable, `docker stats` leaves it out as well
+    let cache = match stats.memory_stats.stats {
+        Some(MemoryStatsStats::V1(memory)) => memory.total_inactive_file,
+        Some(MemoryStatsStats::V2(memory)) => memory.inactive_file,
+        None => 0
+    };
+    let memory_usage = stats.memory_stats.usage.map(|usage| usage.saturating_sub(cache));
+    
+    let (network_rx, network_tx) = match &stats.networks {
+        Some(networks) => (
+            Some(networks.values().map(|network| network.rx_bytes).sum()),
+            Some(networks.values().map(|network| network.tx_bytes).sum())
+        ),
+        None => (None, None)
+    };
+    
+    DockerContainerStatsData {
+        container_id: Some(stats.id.clone()),
+        name: Some(stats.name.trim_start_matches('/').to_string()),
+        cpu_percent,
+        memory_usage,
+        memory_limit: stats.memory_stats.limit,
+        network_rx,
+        network_tx,
+        host: Some(host.to_string())
+    }
+}
+
+pub async fn read_host_load() -> Option<SystemLoadData> {
+    let loadavg = tokio::fs::read_to_string("/proc/loadavg").await.ok()?;
+    let mut loads = loadavg.split_whitespace().map(|value| value.parse::<f64>().ok());
+    
+    let meminfo = tokio::fs::read_to_string("/proc/meminfo").await.unwrap_or_default();
+    let meminfo_value = |key: &str| meminfo.lines()
+        .find(|line| line.starts_with(key))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(|kilobytes| kilobytes * 1024);
+    
+    Some(SystemLoadData {
+        load1: loads.next().flatten(),
+        load5: loads.next().flatten(),
+        load15: loads.next().flatten(),
+        memory_total: meminfo_value("MemTotal:"),
+        memory_available: meminfo_value("MemAvailable:")
+    })
+}
@@ -0,0 +1,73 @@
+use serde_json::{json, Value};
+
+use super::{TestClient, AGENT_TOKEN, HOST};
+
+/// Client registered as the agent of `host`
+async fn agent(host: &str) -> TestClient {
+    let mut agent = TestClient::connect().await;
+    agent.send(json!({ "type": "AgentRegister", "data": { "hostId": host, "token": AGENT_TOKEN } })).await;
+    let answer = agent.expect("AgentRegister", |_| true).await;
+    assert_eq!(answer["data"]["accepted"], true);
+    agent
+}
+
+fn status(host: &'static str) -> impl Fn(&Value) -> bool {
+    move |event| event["data"]["host"] == host
+}
+
+#[tokio::test]
+async fn agents_only_broadcast_docker_events() {
+    let mut agent = agent("edge-spoof").await;
+    let mut client = TestClient::connect().await;
+    client.send(json!({ "type": "SystemCertificate", "data": {} })).await;
+    client.expect("SystemCertificate", |_| true).await;
+    
+    agent.send(json!({ "type": "SystemCertificate", "data": { "hash": "spoofed" } })).await;
+    agent.send(json!({ "type": "DockerStatus", "data": { "status": 1, "host": "edge-spoof" } })).await;
+    let events = client.until("DockerStatus", status("edge-spoof")).await;
+    
+    assert!(events.iter().all(|event| event["type"] != "SystemCertificate"), "{:?}", events);
+}
+
+#[tokio::test]
+async fn agent_events_are_published_under_its_host() {
+    let mut agent = agent("edge-events").await;
+    let mut client = TestClient::connect().await;
+    client.send(json!({ "type": "SystemSubscribe", "data": { "topic": "dockerEvents", "channel": "stream" } })).await;
+    client.send(json!({ "type": "DockerContainerSync", "data": {} })).await;
+    client.expect("DockerContainerSync", |_| true).await;
+    
+    agent.send(json!({ "type": "DockerEventMessage", "data": {
+        "host": "local",
+        "type": "container",
+        "action": "start",
+        "actorId": "e0010000000000000000",
+        "attributes": { "name": "edge-web" },
+        "time": 1700000000,
+        "timeNano": 1700000000000000000i64
+    } })).await;
+    
+    let record = client.expect("DockerEventMessage", |event| event["data"]["actorId"] == "e0010000000000000000").await;
+    assert_eq!(record["data"]["host"], "edge-events");
+    let start = client.expect("DockerContainerStart", |event| event["data"]["containerId"] == "e0010000000000000000").await;
+    assert_eq!(start["data"]["host"], "edge-events");
+    
+    // The inventory refreshes the agent's host, not the one the event was tagged with
+    let request = agent.expect("AgentRequest", |event| event["data"]["call"]["call"] == "ListContainers").await;
+    agent.send(json!({ "type": "AgentResponse", "data": {
+        "requestId": request["data"]["requestId"],
+        "result": { "result": "Containers", "value": [{ "Id": "e0010000000000000000", "Names": ["/edge-web"], "State": "running" }] }
+    } })).await;
+    let patch = client.expect("DockerContainerPatch", |event| event["data"]["id"] == "e0010000000000000000").await;
+    assert_eq!(patch["data"]["host"], "edge-events");
+}
+
+#[tokio::test]
+async fn agents_cannot_take_over_a_configured_host() {
+    let mut agent = TestClient::connect().await;
+    
+    agent.send(json!({ "type": "AgentRegister", "data": { "hostId": HOST, "token": AGENT_TOKEN } })).await;
+    let answer = agent.expect("AgentRegister", |_| true).await;
+    
+    assert_eq!(answer["data"]["accepted"], false);
+}
@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use super::{server, TestClient};
+
+fn bulk(id: &'static str) -> impl Fn(&Value) -> bool {
+    move |event| event["data"]["id"] == id
+}
+
+#[tokio::test]
+async fn bulk_action_streams_progress_then_a_summary() {
+    let docker = &server().await.docker;
+    docker.add_container("b0010000000000000000", "bulk-a", "running", &[("suite", "bulk-stop")]);
+    docker.add_container("b0020000000000000000", "bulk-b", "running", &[("suite", "bulk-stop")]);
+    docker.add_container("b0030000000000000000", "bulk-c", "running", &[("suite", "bulk-stop")]);
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerBulk", "data": {
+        "id": "stop-stack",
+        "action": "stop",
+        "selector": { "labels": { "suite": "bulk-stop" } },
+        "concurrency": 2
+    } })).await;
+    let challenge = client.expect("DockerContainerBulk", bulk("stop-stack")).await;
+    client.send(json!({ "type": "DockerContainerBulk", "data": {
+        "id": "stop-stack",
+        "action": "stop",
+        "selector": { "labels": { "suite": "bulk-stop" } },
+        "concurrency": 2,
+        "confirm": challenge["data"]["confirm"]
+    } })).await;
+    let events = client.until("DockerContainerBulk", bulk("stop-stack")).await;
+    
+    let progress: Vec<&Value> = events.iter().filter(|event| event["type"] == "DockerContainerBulkProgress" && event["data"]["id"] == "stop-stack").collect();
+    assert_eq!(progress.iter().map(|event| event["data"]["done"].as_u64().unwrap_or_default()).collect::<Vec<_>>(), [1, 2, 3]);
+    assert!(progress.iter().all(|event| event["data"]["total"] == 3 && event["data"]["result"]["success"] == true));
+    
+    let summary = &events.last().unwrap()["data"];
+    assert_eq!(summary["succeeded"], 3);
+    assert_eq!(summary["failed"], 0);
+    let ids: Vec<&str> = summary["results"].as_array().into_iter().flatten().filter_map(|result| result["containerId"].as_str()).collect();
+    assert_eq!(ids, ["b0010000000000000000", "b0020000000000000000", "b0030000000000000000"]);
+    assert_eq!(docker.state("bulk-b").as_deref(), Some("exited"));
+}
+
+#[tokio::test]
+async fn bulk_action_follows_the_requested_order() {
+    let docker = &server().await.docker;
+    docker.add_container("b0040000000000000000", "order-a", "exited", &[("suite", "bulk-order")]);
+    docker.add_container("b0050000000000000000", "order-b", "exited", &[("suite", "bulk-order")]);
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerBulk", "data": {
+        "id": "ordered",
+        "action": "start",
+        "host": "local",
+        "containers": ["order-a", "order-b"],
+        "order": [{ "by": "name", "order": "desc" }],
+        "concurrency": 1
+    } })).await;
+    let events = client.until("DockerContainerBulk", bulk("ordered")).await;
+    
+    let names: Vec<&str> = events.iter()
+        .filter(|event| event["type"] == "DockerContainerBulkProgress" && event["data"]["id"] == "ordered")
+        .filter_map(|event| event["data"]["result"]["name"].as_str())
+        .collect();
+    assert_eq!(names, ["order-b", "order-a"]);
+    assert_eq!(docker.state("order-a").as_deref(), Some("running"));
+}
+
+#[tokio::test]
+async fn failures_are_reported_per_container() {
+    let docker = &server().await.docker;
+    docker.add_container("b0060000000000000000", "remove-running", "running", &[]);
+    docker.add_container("b0070000000000000000", "remove-exited", "exited", &[]);
+    let mut client = TestClient::connect().await;
+    
+    let mut request = json!({ "type": "DockerContainerBulk", "data": { "id": "cleanup", "action": "remove", "containers": ["remove-running", "remove-exited"] } });
+    client.send(request.clone()).await;
+    let challenge = client.expect("DockerContainerBulk", bulk("cleanup")).await;
+    request["data"]["confirm"] = challenge["data"]["confirm"].clone();
+    client.send(request).await;
+    let summary = client.expect("DockerContainerBulk", bulk("cleanup")).await;
+    
+    assert_eq!(summary["data"]["succeeded"], 1);
+    assert_eq!(summary["data"]["failed"], 1);
+    assert_eq!(summary["data"]["results"][0]["success"], false);
+    assert!(summary["data"]["results"][0]["error"].as_str().is_some_and(|error| error.contains("container is running")));
+    assert_eq!(docker.state("remove-exited"), None);
+}
+
+#[tokio::test]
+async fn unknown_targets_fail_the_whole_request() {
+    let docker = &server().await.docker;
+    docker.add_container("b0080000000000000000", "typo-web", "running", &[]);
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerBulk", "data": { "id": "typo", "action": "stop", "containers": ["typo-web", "typo-wbe"] } })).await;
+    let events = client.until("DockerContainerBulk", bulk("typo")).await;
+    
+    assert_eq!(events.last().unwrap()["data"]["error"], "No such container: typo-wbe");
+    assert!(events.iter().all(|event| event["type"] != "DockerContainerBulkProgress" || event["data"]["id"] != "typo"));
+    assert_eq!(docker.state("typo-web").as_deref(), Some("running"));
+}
+
+#[tokio::test]
+async fn broadcasts_reach_the_client_while_its_bulk_action_runs() {
+    let docker = &server().await.docker;
+    docker.add_container("b0090000000000000000", "slow-start", "exited", &[]);
+    docker.add_container("b00a0000000000000000", "meanwhile", "exited", &[]);
+    docker.delay("b0090000000000000000", Duration::from_secs(2));
+    let mut client = TestClient::connect().await;
+    let mut other = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerBulk", "data": {
+        "id": "slow",
+        "action": "start",
+        "host": "local",
+        "containers": ["slow-start"]
+    } })).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    other.send(json!({ "type": "DockerContainerStart", "data": { "containerId": "meanwhile" } })).await;
+    
+    let events = client.until("DockerContainerBulk", bulk("slow")).await;
+    let meanwhile = events.iter().position(|event| event["type"] == "DockerContainerStart" && event["data"]["containerId"] == "b00a0000000000000000");
+    let progress = events.iter().position(|event| event["type"] == "DockerContainerBulkProgress");
+    assert!(meanwhile.is_some() && meanwhile < progress, "the start broadcast waited for the bulk action: {:?}", events);
+    assert_eq!(events.last().unwrap()["data"]["succeeded"], 1);
+}
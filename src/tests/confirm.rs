@@ -0,0 +1,106 @@
+use serde_json::{json, Value};
+
+use super::{server, TestClient, ADMIN_TOKEN, CLIENT_TOKEN};
+
+fn stop(id: &'static str) -> impl Fn(&Value) -> bool {
+    move |event| event["data"]["containerId"] == id
+}
+
+#[tokio::test]
+async fn stop_is_challenged_with_what_it_affects() {
+    let docker = &server().await.docker;
+    docker.add_container("c0010000000000000000", "shop-db", "running", &[("com.docker.compose.project", "confirm-shop"), ("com.docker.compose.service", "db")]);
+    docker.add_container("c0020000000000000000", "shop-web", "running", &[("com.docker.compose.project", "confirm-shop"), ("com.docker.compose.service", "web"), ("com.docker.compose.depends_on", "db:service_started:false")]);
+    docker.add_container("c0030000000000000000", "shop-worker", "running", &[("com.docker.compose.project", "confirm-shop"), ("com.docker.compose.service", "worker"), ("com.docker.compose.depends_on", "cache:service_started:false,db:service_healthy:true")]);
+    docker.add_volume("c0010000000000000000", "shop-data");
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerStop", "data": { "containerId": "shop-db" } })).await;
+    let challenge = client.expect("DockerContainerStop", stop("shop-db")).await;
+    
+    let affected = &challenge["data"]["affected"][0];
+    assert_eq!(affected["containerId"], "c0010000000000000000");
+    assert_eq!(affected["service"], "db");
+    assert_eq!(affected["dependents"], json!(["web", "worker"]));
+    assert_eq!(affected["volumes"], json!(["shop-data"]));
+    assert!(challenge["data"]["expiresAt"].is_u64());
+    assert_eq!(docker.state("shop-db").as_deref(), Some("running"));
+    
+    client.send(json!({ "type": "DockerContainerStop", "data": { "containerId": "shop-db", "confirm": "guessed" } })).await;
+    let refused = client.expect("DockerContainerStop", stop("shop-db")).await;
+    assert!(refused["data"]["error"].as_str().is_some_and(|error| error.starts_with("Confirmation token is unknown")));
+    
+    let token = &challenge["data"]["confirm"];
+    client.send(json!({ "type": "DockerContainerStop", "data": { "containerId": "shop-db", "confirm": token } })).await;
+    client.expect("DockerContainerStop", |event| event["data"]["containerId"] == "c0010000000000000000").await;
+    assert_eq!(docker.state("shop-db").as_deref(), Some("exited"));
+    
+    client.send(json!({ "type": "DockerContainerStop", "data": { "containerId": "shop-db", "confirm": token } })).await;
+    let reused = client.expect("DockerContainerStop", stop("shop-db")).await;
+    assert!(reused["data"]["error"].is_string());
+}
+
+#[tokio::test]
+async fn dry_run_describes_without_acting() {
+    let docker = &server().await.docker;
+    docker.add_container("c0040000000000000000", "dry-a", "exited", &[("suite", "dry-run")]);
+    docker.add_container("c0050000000000000000", "dry-b", "exited", &[("suite", "dry-run")]);
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerBulk", "data": { "id": "dry", "action": "remove", "selector": { "labels": { "suite": "dry-run" } }, "dryRun": true } })).await;
+    let answer = client.expect("DockerContainerBulk", |event| event["data"]["id"] == "dry").await;
+    
+    let names: Vec<&str> = answer["data"]["affected"].as_array().into_iter().flatten().filter_map(|affected| affected["name"].as_str()).collect();
+    assert_eq!(names, ["dry-a", "dry-b"]);
+    assert!(answer["data"]["confirm"].is_null());
+    assert!(answer["data"]["results"].is_null());
+    assert_eq!(docker.state("dry-a").as_deref(), Some("exited"));
+}
+
+#[tokio::test]
+async fn protected_containers_require_the_admin_role() {
+    let docker = &server().await.docker;
+    docker.add_container("c0060000000000000000", "vault", "exited", &[("admin-api.protected", "true")]);
+    let mut operator = TestClient::connect().await;
+    operator.send(json!({ "type": "Hello", "data": { "version": 2, "token": CLIENT_TOKEN } })).await;
+    operator.expect("Hello", |_| true).await;
+    
+    operator.send(json!({ "type": "DockerContainerStart", "data": { "containerId": "vault" } })).await;
+    let refused = operator.expect("DockerContainerStart", stop("vault")).await;
+    assert_eq!(refused["data"]["error"], "Container vault is protected, an authenticated admin is required");
+    assert_eq!(docker.state("vault").as_deref(), Some("exited"));
+    
+    operator.send(json!({ "type": "DockerContainerStart", "data": { "containerId": "vault", "dryRun": true } })).await;
+    let described = operator.expect("DockerContainerStart", stop("vault")).await;
+    assert_eq!(described["data"]["affected"][0]["protected"], true);
+    assert!(described["data"]["error"].is_null(), "a dry run reports protection without refusing");
+    
+    let mut admin = TestClient::connect().await;
+    admin.send(json!({ "type": "Hello", "data": { "version": 2, "token": ADMIN_TOKEN } })).await;
+    admin.expect("Hello", |_| true).await;
+    admin.send(json!({ "type": "DockerContainerStart", "data": { "containerId": "vault" } })).await;
+    admin.expect("DockerContainerStart", |event| event["data"]["containerId"] == "c0060000000000000000").await;
+    assert_eq!(docker.state("vault").as_deref(), Some("running"));
+}
+
+#[tokio::test]
+async fn tokens_are_only_redeemed_on_their_stream() {
+    let docker = &server().await.docker;
+    docker.add_container("c0070000000000000000", "bound", "running", &[]);
+    let mut issued = TestClient::connect().await;
+    let mut other = TestClient::connect().await;
+    
+    issued.send(json!({ "type": "DockerContainerStop", "data": { "containerId": "bound" } })).await;
+    let challenge = issued.expect("DockerContainerStop", stop("bound")).await;
+    let token = &challenge["data"]["confirm"];
+    assert!(token.as_str().is_some_and(|token| token.len() == 32 && token.chars().all(|c| c.is_ascii_hexdigit())));
+    
+    other.send(json!({ "type": "DockerContainerStop", "data": { "containerId": "bound", "confirm": token } })).await;
+    let refused = other.expect("DockerContainerStop", stop("bound")).await;
+    assert!(refused["data"]["error"].is_string());
+    assert_eq!(docker.state("bound").as_deref(), Some("running"));
+    
+    issued.send(json!({ "type": "DockerContainerStop", "data": { "containerId": "bound", "confirm": token } })).await;
+    issued.expect("DockerContainerStop", |event| event["data"]["containerId"] == "c0070000000000000000").await;
+    assert_eq!(docker.state("bound").as_deref(), Some("exited"));
+}
@@ -0,0 +1,45 @@
+use serde_json::json;
+
+use crate::events::{registry::{self, DecodeError}, Event};
+
+use super::TestClient;
+
+#[test]
+fn events_are_routed_on_their_type() {
+    let event = registry::decode_event(json!({ "type": "AlertList", "data": {} })).expect("AlertList should decode");
+    assert!(matches!(event, Event::Alert(_)));
+    let event = registry::decode_event(json!({ "type": "AgentRegister", "data": { "hostId": "edge" } })).expect("AgentRegister should decode");
+    assert!(matches!(event, Event::Agent(_)));
+    
+    assert!(matches!(registry::decode_event(json!({ "type": "DockerContainerExplode" })), Err(DecodeError::UnknownType(typ)) if typ == "DockerContainerExplode"));
+    assert!(matches!(registry::decode_event(json!({ "type": 3 })), Err(DecodeError::Malformed(_))));
+    assert!(matches!(registry::decode_event(json!([])), Err(DecodeError::Malformed(_))));
+    let error = registry::decode_event(json!({ "type": "MetricsQuery", "data": { "from": "yesterday" } })).expect_err("from is a number");
+    assert!(matches!(error, DecodeError::Invalid { typ: "MetricsQuery", .. }), "unexpected error {}", error);
+}
+
+#[tokio::test]
+async fn rejected_events_are_reported() {
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerExplode", "data": {} })).await;
+    let error = client.expect("SystemError", |_| true).await;
+    assert_eq!(error["data"]["code"], "unknownType");
+    assert_eq!(error["data"]["event"], "DockerContainerExplode");
+    assert_eq!(error["data"]["error"], "unknown event type `DockerContainerExplode`");
+    
+    client.send(json!({ "type": "DockerContainerStart", "data": { "containerId": 5 } })).await;
+    let error = client.expect("SystemError", |_| true).await;
+    assert_eq!(error["data"]["code"], "invalidEvent");
+    assert_eq!(error["data"]["event"], "DockerContainerStart");
+    assert!(error["data"]["error"].as_str().is_some_and(|message| message.starts_with("invalid `DockerContainerStart` event: invalid type: integer `5`")), "unexpected error {}", error);
+    
+    client.send(json!({ "data": {} })).await;
+    let error = client.expect("SystemError", |_| true).await;
+    assert_eq!(error["data"]["code"], "malformed");
+    assert_eq!(error["data"]["event"], json!(null));
+    
+    // The stream is still usable
+    client.send(json!({ "type": "DockerStatus", "data": {} })).await;
+    client.expect("DockerStatus", |_| true).await;
+}
@@ -0,0 +1,207 @@
+use serde_json::{json, Value};
+
+use super::{server, TestClient, UNREACHABLE_HOST};
+
+fn has_id(id: &'static str) -> impl Fn(&Value) -> bool {
+    move |event| event["data"]["containerId"] == id
+}
+
+#[tokio::test]
+async fn list_returns_the_containers_of_the_host() {
+    server().await.docker.add_container("11110000000000000000", "list-web", "running", &[("tier", "web")]);
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerList", "data": {} })).await;
+    let list = client.expect("DockerContainerList", |_| true).await;
+    
+    let containers = list["data"]["containers"].as_array().expect("containers should be listed");
+    let container = containers.iter().find(|container| container["Id"] == "11110000000000000000").expect("container should be listed");
+    assert_eq!(container["Names"], json!(["/list-web"]));
+    assert_eq!(container["State"], "running");
+    assert_eq!(container["Labels"]["tier"], "web");
+    assert_eq!(container["host"], "local");
+}
+
+#[tokio::test]
+async fn list_on_an_unreachable_host_reports_its_status() {
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerList", "data": { "host": UNREACHABLE_HOST } })).await;
+    let events = client.until("DockerContainerList", |_| true).await;
+    
+    let status = events.iter().find(|event| event["type"] == "DockerStatus").expect("status should be reported");
+    assert_eq!(status["data"], json!({ "status": 2, "host": UNREACHABLE_HOST }));
+    assert_eq!(events.last().unwrap()["data"]["containers"], json!([]));
+}
+
+#[tokio::test]
+async fn inspect_returns_the_container() {
+    server().await.docker.add_container("22220000000000000000", "inspect-db", "running", &[]);
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerInspect", "data": { "containerId": "inspect-db" } })).await;
+    let inspect = client.expect("DockerContainerInspect", |event| event["data"]["containerId"] == "inspect-db").await;
+    
+    assert_eq!(inspect["data"]["container"]["Id"], "22220000000000000000");
+    assert_eq!(inspect["data"]["container"]["Name"], "/inspect-db");
+    assert_eq!(inspect["data"]["container"]["State"]["Status"], "running");
+}
+
+#[tokio::test]
+async fn list_returns_the_requested_fields() {
+    server().await.docker.add_container("11120000000000000000", "list-projected", "running", &[("tier", "web")]);
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerList", "data": { "fields": ["Names"] } })).await;
+    let list = client.expect("DockerContainerList", |event| event["data"]["fields"] == json!(["Names"])).await;
+    let container = list["data"]["containers"].as_array().and_then(|containers| containers.iter().find(|container| container["Id"] == "11120000000000000000"));
+    assert_eq!(container, Some(&json!({ "host": "local", "Id": "11120000000000000000", "Names": ["/list-projected"] })));
+    
+    client.send(json!({ "type": "DockerContainerList", "data": { "projection": "summary", "fields": ["Image"] } })).await;
+    let list = client.expect("DockerContainerList", |event| event["data"]["projection"] == "summary").await;
+    let container = list["data"]["containers"].as_array().and_then(|containers| containers.iter().find(|container| container["Id"] == "11120000000000000000"));
+    assert_eq!(container, Some(&json!({
+        "host": "local",
+        "Id": "11120000000000000000",
+        "Names": ["/list-projected"],
+        "Image": "alpine:latest",
+        "State": "running",
+        "Labels": { "tier": "web" }
+    })));
+}
+
+#[tokio::test]
+async fn inspect_returns_the_requested_fields() {
+    server().await.docker.add_container("22230000000000000000", "inspect-projected", "exited", &[("tier", "db")]);
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerInspect", "data": { "containerId": "inspect-projected", "fields": ["State.Status", "Config.Labels", "Missing.Field"] } })).await;
+    let inspect = client.expect("DockerContainerInspect", |event| event["data"]["containerId"] == "inspect-projected").await;
+    
+    assert_eq!(inspect["data"]["container"], json!({
+        "Id": "22230000000000000000",
+        "State": { "Status": "exited" },
+        "Config": { "Labels": { "tier": "db" } }
+    }));
+}
+
+fn names(list: &Value) -> Vec<&str> {
+    list["data"]["containers"].as_array().into_iter().flatten()
+        .filter_map(|container| container["Names"][0].as_str())
+        .collect()
+}
+
+#[tokio::test]
+async fn list_filters_sorts_and_pages() {
+    let docker = &server().await.docker;
+    docker.add_container("11130000000000000000", "page-a", "running", &[("suite", "paging"), ("com.docker.compose.project", "shop")]);
+    docker.add_container("11140000000000000000", "page-b", "exited", &[("suite", "paging"), ("com.docker.compose.project", "blog")]);
+    docker.add_container("11150000000000000000", "page-c", "running", &[("suite", "paging")]);
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerList", "data": { "filters": { "label": ["suite=paging"], "status": ["running"] } } })).await;
+    let list = client.expect("DockerContainerList", |event| event["data"]["filters"]["status"] == json!(["running"])).await;
+    assert_eq!(names(&list), ["/page-a", "/page-c"]);
+    
+    client.send(json!({ "type": "DockerContainerList", "data": { "filters": { "label": ["suite"], "project": ["shop", "blog"] } } })).await;
+    let list = client.expect("DockerContainerList", |event| event["data"]["filters"]["project"].is_array()).await;
+    assert_eq!(names(&list), ["/page-a", "/page-b"]);
+    
+    docker.add_container("11170000000000000000", "unsorted-z", "running", &[("suite", "unsorted")]);
+    docker.add_container("11160000000000000000", "unsorted-y", "running", &[("suite", "unsorted")]);
+    client.send(json!({ "type": "DockerContainerList", "data": { "filters": { "label": ["suite=unsorted"] } } })).await;
+    let list = client.expect("DockerContainerList", |event| event["data"]["filters"]["label"] == json!(["suite=unsorted"])).await;
+    assert_eq!(names(&list), ["/unsorted-z", "/unsorted-y"], "the daemon order is kept without sort keys nor paging");
+    
+    let request = json!({ "filters": { "label": ["suite=paging"] }, "sort": [{ "by": "name", "order": "desc" }], "limit": 2 });
+    client.send(json!({ "type": "DockerContainerList", "data": request })).await;
+    let first = client.expect("DockerContainerList", |event| event["data"]["limit"] == 2).await;
+    assert_eq!(names(&first), ["/page-c", "/page-b"]);
+    assert_eq!(first["data"]["total"], 3);
+    assert_eq!(first["data"]["nextCursor"], 2);
+    
+    let mut request = request;
+    request["cursor"] = first["data"]["nextCursor"].clone();
+    client.send(json!({ "type": "DockerContainerList", "data": request })).await;
+    let last = client.expect("DockerContainerList", |event| event["data"]["cursor"] == 2).await;
+    assert_eq!(names(&last), ["/page-a"]);
+    assert_eq!(last["data"]["nextCursor"], json!(null));
+}
+
+#[tokio::test]
+async fn failed_requests_leave_the_session_usable() {
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerInspect", "data": { "containerId": "missing" } })).await;
+    client.send(json!({ "type": "DockerContainerStart", "data": { "containerId": "missing" } })).await;
+    client.send(json!({ "type": "DockerContainerStats", "data": { "containerId": "missing" } })).await;
+    client.send(json!({ "type": "DockerStatus", "data": {} })).await;
+    let events = client.until("DockerStatus", |event| event["data"]["host"] == "local").await;
+    
+    assert!(events.iter().all(|event| event["type"] != "DockerContainerInspect" && event["type"] != "DockerContainerStats"));
+    assert_eq!(events.last().unwrap()["data"]["status"], 1);
+}
+
+#[tokio::test]
+async fn lifecycle_requests_change_the_container_state() {
+    let docker = &server().await.docker;
+    docker.add_container("33330000000000000000", "lifecycle-worker", "exited", &[]);
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerStart", "data": { "containerId": "33330000000000000000" } })).await;
+    client.expect("DockerContainerStart", has_id("33330000000000000000")).await;
+    assert_eq!(docker.state("33330000000000000000").as_deref(), Some("running"));
+    
+    client.send(json!({ "type": "DockerContainerStop", "data": { "containerId": "33330000000000000000" } })).await;
+    let challenge = client.expect("DockerContainerStop", has_id("33330000000000000000")).await;
+    assert_eq!(docker.state("33330000000000000000").as_deref(), Some("running"));
+    
+    client.send(json!({ "type": "DockerContainerStop", "data": { "containerId": "33330000000000000000", "confirm": challenge["data"]["confirm"] } })).await;
+    client.expect("DockerContainerStop", |event| event["data"]["containerId"] == "33330000000000000000" && event["data"]["confirm"].is_null()).await;
+    assert_eq!(docker.state("33330000000000000000").as_deref(), Some("exited"));
+    
+    client.send(json!({ "type": "DockerContainerRestart", "data": { "containerId": "33330000000000000000" } })).await;
+    client.expect("DockerContainerRestart", has_id("33330000000000000000")).await;
+    assert_eq!(docker.state("33330000000000000000").as_deref(), Some("running"));
+}
+
+#[tokio::test]
+async fn stats_are_computed_from_the_daemon_sample() {
+    server().await.docker.add_container("44440000000000000000", "stats-cache", "running", &[]);
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerStats", "data": { "containerId": "44440000000000000000" } })).await;
+    let stats = client.expect("DockerContainerStats", has_id("44440000000000000000")).await;
+    
+    assert_eq!(stats["data"]["name"], "stats-cache");
+    assert_eq!(stats["data"]["cpuPercent"], 20.0);
+    assert_eq!(stats["data"]["memoryUsage"], 64 * 1024 * 1024);
+    assert_eq!(stats["data"]["networkRx"], 1000);
+    assert_eq!(stats["data"]["host"], "local");
+}
+
+#[tokio::test]
+async fn daemon_events_are_broadcast_to_every_client() {
+    server().await.docker.add_container("55550000000000000000", "broadcast-api", "exited", &[("team", "core")]);
+    let mut actor = TestClient::connect().await;
+    let mut watcher = TestClient::connect().await;
+    
+    // Raw events are opt-in, the status answer confirms the subscription went through
+    watcher.send(json!({ "type": "SystemSubscribe", "data": { "topic": "dockerEvents", "channel": "stream" } })).await;
+    watcher.send(json!({ "type": "DockerStatus", "data": {} })).await;
+    watcher.expect("DockerStatus", |_| true).await;
+    
+    actor.send(json!({ "type": "DockerContainerStart", "data": { "containerId": "55550000000000000000" } })).await;
+    
+    let seen_by_actor = actor.until("DockerContainerStart", has_id("55550000000000000000")).await;
+    assert_eq!(seen_by_actor.last().unwrap()["data"]["host"], "local");
+    assert!(seen_by_actor.iter().all(|event| event["type"] != "DockerEventMessage"));
+    
+    let seen_by_watcher = watcher.until("DockerContainerStart", has_id("55550000000000000000")).await;
+    let raw = seen_by_watcher.iter()
+        .find(|event| event["type"] == "DockerEventMessage" && event["data"]["actorId"] == "55550000000000000000")
+        .expect("raw event should be delivered to the subscriber");
+    assert_eq!(raw["data"]["action"], "start");
+    assert_eq!(raw["data"]["type"], "container");
+    assert_eq!(raw["data"]["attributes"]["team"], "core");
+}
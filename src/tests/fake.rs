@@ -0,0 +1,250 @@
+use std::{collections::HashMap, sync::{atomic::{AtomicBool, Ordering}, Mutex}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use async_trait::async_trait;
+use bollard::{container::Stats, errors::Error, secret::{ContainerConfig, ContainerInspectResponse, ContainerState, ContainerStateStatusEnum, ContainerSummary, EventActor, EventMessage, EventMessageTypeEnum, MountPoint, MountPointTypeEnum}};
+use futures::{stream::{self, BoxStream}, StreamExt};
+use serde_json::json;
+use tokio::sync::broadcast;
+
+use crate::{events::docker::ContainerFilters, services::backend::{DockerBackend, ExecOutput}};
+
+/// In-memory Docker host: containers are plain summaries, lifecycle calls update their state
+/// and emit the events a daemon would
+pub struct FakeDocker {
+    containers: Mutex<Vec<ContainerSummary>>,
+    events: broadcast::Sender<EventMessage>,
+    reachable: AtomicBool,
+    /// Time lifecycle calls take, per container ID
+    delays: Mutex<HashMap<String, Duration>>
+}
+
+impl FakeDocker {
+    pub fn new() -> Self {
+        Self {
+            containers: Mutex::new(Vec::new()),
+            events: broadcast::channel(64).0,
+            reachable: AtomicBool::new(true),
+            delays: Mutex::new(HashMap::new())
+        }
+    }
+    
+    /// Host whose every call fails, as when the daemon is down
+    pub fn unreachable() -> Self {
+        let docker = Self::new();
+        docker.reachable.store(false, Ordering::Relaxed);
+        docker
+    }
+    
+    pub fn add_container(&self, id: &str, name: &str, state: &str, labels: &[(&str, &str)]) {
+        self.containers.lock().unwrap().push(ContainerSummary {
+            id: Some(id.to_string()),
+            names: Some(vec![format!("/{}", name)]),
+            image: Some("alpine:latest".to_string()),
+            state: Some(state.to_string()),
+            labels: Some(labels.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()),
+            ..Default::default()
+        });
+    }
+    
+    /// Mounts the named volume in the container
+    pub fn add_volume(&self, id: &str, volume: &str) {
+        for summary in self.containers.lock().unwrap().iter_mut().filter(|summary| summary.id.as_deref() == Some(id)) {
+            summary.mounts.get_or_insert_default().push(MountPoint {
+                typ: Some(MountPointTypeEnum::VOLUME),
+                name: Some(volume.to_string()),
+                ..Default::default()
+            });
+        }
+    }
+    
+    /// Makes the lifecycle calls on the container take `delay`, as a slow stop would
+    pub fn delay(&self, id: &str, delay: Duration) {
+        self.delays.lock().unwrap().insert(id.to_string(), delay);
+    }
+    
+    /// Whether the server subscribed to the events
+    pub fn listening(&self) -> bool {
+        self.events.receiver_count() > 0
+    }
+    
+    pub fn state(&self, id: &str) -> Option<String> {
+        self.find(id).ok()?.state
+    }
+    
+    fn check(&self) -> Result<(), Error> {
+        match self.reachable.load(Ordering::Relaxed) {
+            true => Ok(()),
+            false => Err(Error::DockerResponseServerError { status_code: 500, message: "Docker daemon is down".to_string() })
+        }
+    }
+    
+    /// Matches the full ID, a prefix of it or the name, like the daemon
+    fn find(&self, id: &str) -> Result<ContainerSummary, Error> {
+        self.check()?;
+        self.containers.lock().unwrap().iter()
+            .find(|container| {
+                container.id.as_deref().is_some_and(|container_id| container_id.starts_with(id))
+                    || container.names.iter().flatten().any(|name| name.trim_start_matches('/') == id)
+            })
+            .cloned()
+            .ok_or_else(|| Error::DockerResponseServerError { status_code: 404, message: format!("No such container: {}", id) })
+    }
+    
+    async fn transition(&self, id: &str, state: &str, actions: &[&str]) -> Result<(), Error> {
+        let container = self.find(id)?;
+        let delay = container.id.as_ref().and_then(|id| self.delays.lock().unwrap().get(id).copied());
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
+        let id = container.id.clone().unwrap_or_default();
+        
+        for summary in self.containers.lock().unwrap().iter_mut().filter(|summary| summary.id.as_deref() == Some(&id)) {
+            summary.state = Some(state.to_string());
+        }
+        
+        let mut attributes = container.labels.clone().unwrap_or_default();
+        attributes.insert("name".to_string(), container.names.iter().flatten().next().map(|name| name.trim_start_matches('/').to_string()).unwrap_or_default());
+        attributes.insert("image".to_string(), container.image.clone().unwrap_or_default());
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        
+        for action in actions {
+            let mut attributes = attributes.clone();
+            if *action == "die" {
+                attributes.insert("exitCode".to_string(), "0".to_string());
+            }
+            // Nobody listening is fine, the server may not have subscribed yet
+            let _ = self.events.send(EventMessage {
+                typ: Some(EventMessageTypeEnum::CONTAINER),
+                action: Some(action.to_string()),
+                actor: Some(EventActor { id: Some(id.clone()), attributes: Some(attributes) }),
+                time: Some(time.as_secs() as i64),
+                time_nano: Some(time.as_nanos() as i64),
+                ..Default::default()
+            });
+        }
+        
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DockerBackend for FakeDocker {
+    async fn ping(&self) -> Result<(), Error> {
+        self.check()
+    }
+    
+    async fn list_containers(&self, filters: &ContainerFilters) -> Result<Vec<ContainerSummary>, Error> {
+        self.check()?;
+        Ok(self.containers.lock().unwrap().iter().filter(|container| filters.matches(container)).cloned().collect())
+    }
+    
+    async fn inspect_container(&self, id: &str) -> Result<ContainerInspectResponse, Error> {
+        let container = self.find(id)?;
+        let status = match container.state.as_deref() {
+            Some("running") => ContainerStateStatusEnum::RUNNING,
+            Some("paused") => ContainerStateStatusEnum::PAUSED,
+            _ => ContainerStateStatusEnum::EXITED
+        };
+        
+        Ok(ContainerInspectResponse {
+            id: container.id,
+            name: container.names.and_then(|names| names.into_iter().next()),
+            state: Some(ContainerState {
+                status: Some(status),
+                running: Some(status == ContainerStateStatusEnum::RUNNING),
+                ..Default::default()
+            }),
+            config: Some(ContainerConfig {
+                image: container.image,
+                labels: container.labels,
+                ..Default::default()
+            }),
+            mounts: container.mounts,
+            ..Default::default()
+        })
+    }
+    
+    async fn start_container(&self, id: &str) -> Result<(), Error> {
+        self.transition(id, "running", &["start"]).await
+    }
+    
+    async fn stop_container(&self, id: &str) -> Result<(), Error> {
+        self.transition(id, "exited", &["kill", "die", "stop"]).await
+    }
+    
+    async fn restart_container(&self, id: &str) -> Result<(), Error> {
+        self.transition(id, "running", &["kill", "die", "stop", "start", "restart"]).await
+    }
+    
+    async fn pause_container(&self, id: &str) -> Result<(), Error> {
+        self.transition(id, "paused", &["pause"]).await
+    }
+    
+    async fn remove_container(&self, id: &str) -> Result<(), Error> {
+        let container = self.find(id)?;
+        if container.state.as_deref() == Some("running") {
+            return Err(Error::DockerResponseServerError { status_code: 409, message: format!("cannot remove container {}: container is running", id) });
+        }
+        self.transition(id, "removing", &["destroy"]).await?;
+        self.containers.lock().unwrap().retain(|summary| summary.id != container.id);
+        Ok(())
+    }
+    
+    async fn container_stats(&self, id: &str, _one_shot: bool) -> Result<Stats, Error> {
+        let container = self.find(id)?;
+        let cpu = |total: u64, system: u64| json!({
+            "cpu_usage": { "total_usage": total, "usage_in_usermode": total, "usage_in_kernelmode": 0 },
+            "system_cpu_usage": system,
+            "online_cpus": 2,
+            "throttling_data": { "periods": 0, "throttled_periods": 0, "throttled_time": 0 }
+        });
+        
+        let stats = json!({
+            "read": "2025-01-01T00:00:01Z",
+            "preread": "2025-01-01T00:00:00Z",
+            "num_procs": 0,
+            "pids_stats": {},
+            "networks": { "eth0": { "rx_bytes": 1000, "tx_bytes": 500, "rx_packets": 0, "tx_packets": 0, "rx_errors": 0, "tx_errors": 0, "rx_dropped": 0, "tx_dropped": 0 } },
+            "memory_stats": { "usage": 64 * 1024 * 1024, "limit": 256 * 1024 * 1024 },
+            "blkio_stats": {},
+            "cpu_stats": cpu(200, 2000),
+            "precpu_stats": cpu(100, 1000),
+            "storage_stats": {},
+            "name": container.names.iter().flatten().next().cloned().unwrap_or_default(),
+            "id": container.id.unwrap_or_default()
+        });
+        
+        serde_json::from_value(stats).map_err(Error::from)
+    }
+    
+    async fn exec_container(&self, id: &str, command: Vec<String>) -> Result<ExecOutput, Error> {
+        self.find(id)?;
+        Ok(ExecOutput {
+            exit_code: Some(0),
+            output: command.join(" ")
+        })
+    }
+    
+    fn events(&self) -> BoxStream<'static, Result<EventMessage, Error>> {
+        if self.check().is_err() {
+            return stream::empty().boxed();
+        }
+        
+        stream::unfold(self.events.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((Ok(event), rx)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None
+                }
+            }
+        }).boxed()
+    }
+}
+
+impl Default for FakeDocker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
@@ -0,0 +1,96 @@
+use serde_json::json;
+
+use super::{TestClient, CLIENT_TOKEN, TIMEOUT};
+
+#[tokio::test]
+async fn hello_announces_the_server_capabilities() {
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "Hello", "data": { "version": 2, "software": "tests/1.0" } })).await;
+    let hello = client.expect("Hello", |_| true).await;
+    
+    assert_eq!(hello["data"]["version"], 2);
+    assert_eq!(hello["data"]["minVersion"], 1);
+    assert_eq!(hello["data"]["error"], json!(null));
+    assert_eq!(hello["data"]["identity"], json!({ "name": "anonymous", "role": "operator" }));
+    assert_eq!(hello["data"]["limits"]["maxFrameSize"], 16 * 1024 * 1024);
+    let events = hello["data"]["events"].as_array().expect("events should be listed");
+    assert!(events.contains(&json!("DockerContainerList")) && events.contains(&json!("MetricsQuery")));
+    assert!(!events.contains(&json!("AgentRegister")));
+    assert!(!hello["data"]["deprecations"].as_array().unwrap_or(&Vec::new()).is_empty());
+    
+    client.send(json!({ "type": "DockerStatus", "data": {} })).await;
+    client.expect("DockerStatus", |_| true).await;
+}
+
+#[tokio::test]
+async fn hello_applies_the_requested_features() {
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "Hello", "data": {
+        "version": 2,
+        "features": { "subscriptions": [{ "topic": "dockerEvents", "channel": "stream" }, { "topic": "hostLoad" }] }
+    } })).await;
+    let hello = client.expect("Hello", |_| true).await;
+    
+    assert_eq!(hello["data"]["features"]["encoding"], "json");
+    assert_eq!(hello["data"]["features"]["compression"], "none");
+    assert_eq!(hello["data"]["features"]["subscriptions"], json!([
+        { "topic": "dockerEvents", "channel": "stream" },
+        { "topic": "hostLoad", "channel": "datagram" }
+    ]));
+}
+
+#[tokio::test]
+async fn token_authenticates_the_client() {
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "Hello", "data": { "version": 2, "token": CLIENT_TOKEN } })).await;
+    let hello = client.expect("Hello", |_| true).await;
+    
+    assert_eq!(hello["data"]["identity"], json!({ "name": "ci", "role": "operator" }));
+}
+
+#[tokio::test]
+async fn version_one_is_still_accepted() {
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "Hello", "data": {} })).await;
+    let hello = client.expect("Hello", |_| true).await;
+    
+    assert_eq!(hello["data"]["error"], json!(null));
+    assert_eq!(hello["data"]["version"], 2);
+}
+
+async fn assert_rejected(hello: serde_json::Value, error: &str) {
+    let mut client = TestClient::connect().await;
+    
+    client.send(hello).await;
+    let answer = client.expect("Hello", |_| true).await;
+    
+    assert!(answer["data"]["error"].as_str().is_some_and(|message| message.contains(error)), "unexpected answer {}", answer);
+    assert_eq!(answer["data"]["version"], 2);
+    assert_eq!(client.recv(TIMEOUT).await, None, "the stream should be closed");
+}
+
+#[tokio::test]
+async fn incompatible_version_is_rejected() {
+    assert_rejected(json!({ "type": "Hello", "data": { "version": 99 } }), "Unsupported protocol version 99").await;
+}
+
+#[tokio::test]
+async fn invalid_token_is_rejected() {
+    assert_rejected(json!({ "type": "Hello", "data": { "version": 2, "token": "wrong" } }), "Invalid token").await;
+}
+
+#[tokio::test]
+async fn hello_after_a_request_is_rejected() {
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerStatus", "data": {} })).await;
+    client.expect("DockerStatus", |_| true).await;
+    client.send(json!({ "type": "Hello", "data": { "version": 2 } })).await;
+    let answer = client.expect("Hello", |_| true).await;
+    
+    assert_eq!(answer["data"]["error"], "`Hello` must be the first event of the stream");
+}
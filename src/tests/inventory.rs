@@ -0,0 +1,52 @@
+use serde_json::{json, Value};
+
+use super::{server, TestClient};
+
+fn patches_of(id: &'static str) -> impl Fn(&Value) -> bool {
+    move |event| event["data"]["id"] == id
+}
+
+#[tokio::test]
+async fn sync_sends_a_snapshot_then_patches() {
+    server().await.docker.add_container("33350000000000000000", "sync-web", "exited", &[]);
+    let mut client = TestClient::connect().await;
+    
+    client.send(json!({ "type": "DockerContainerSync", "data": {} })).await;
+    let sync = client.expect("DockerContainerSync", |_| true).await;
+    assert!(sync["data"]["version"].is_u64());
+    assert!(sync["data"]["containers"].is_array());
+    assert_eq!(sync["data"]["patches"], json!(null));
+    
+    client.send(json!({ "type": "DockerContainerStart", "data": { "containerId": "sync-web" } })).await;
+    let patch = client.expect("DockerContainerPatch", |event| patches_of("33350000000000000000")(event) && event["data"]["container"]["State"] == "running").await;
+    assert_eq!(patch["data"]["host"], "local");
+    assert!(patch["data"]["op"] == "add" || patch["data"]["op"] == "update");
+    assert!(patch["data"]["version"].as_u64() > sync["data"]["version"].as_u64());
+}
+
+#[tokio::test]
+async fn reconnecting_clients_get_the_delta() {
+    server().await.docker.add_container("33340000000000000000", "sync-db", "exited", &[]);
+    let mut client = TestClient::connect().await;
+    client.send(json!({ "type": "DockerContainerSync", "data": {} })).await;
+    let sync = client.expect("DockerContainerSync", |_| true).await;
+    client.send(json!({ "type": "DockerContainerStart", "data": { "containerId": "sync-db" } })).await;
+    client.expect("DockerContainerPatch", patches_of("33340000000000000000")).await;
+    drop(client);
+    
+    let mut client = TestClient::connect().await;
+    client.send(json!({ "type": "DockerContainerSync", "data": { "since": sync["data"]["version"], "epoch": sync["data"]["epoch"] } })).await;
+    let delta = client.expect("DockerContainerSync", |_| true).await;
+    
+    assert_eq!(delta["data"]["containers"], json!(null));
+    assert_eq!(delta["data"]["since"], sync["data"]["version"]);
+    let patches = delta["data"]["patches"].as_array().expect("patches should be sent");
+    assert!(patches.iter().any(|patch| patch["id"] == "33340000000000000000" && patch["container"]["State"] == "running"));
+    assert!(patches.iter().all(|patch| patch["version"].as_u64() > sync["data"]["version"].as_u64()));
+    
+    // Versions of another server run are not comparable
+    client.send(json!({ "type": "DockerContainerSync", "data": { "since": sync["data"]["version"], "epoch": 1 } })).await;
+    let snapshot = client.expect("DockerContainerSync", |event| event["data"]["containers"].is_array()).await;
+    let containers = snapshot["data"]["containers"].as_array().expect("containers should be listed");
+    assert!(containers.iter().any(|container| container["Id"] == "33340000000000000000" && container["host"] == "local"));
+}
@@ -0,0 +1,187 @@
+//! End-to-end tests: a server runs in-process on fake Docker hosts, test clients speak the
+//! event protocol to it over WebTransport
+
+use std::{sync::{Arc, OnceLock}, time::Duration};
+
+use rustls::crypto::{ring::default_provider, CryptoProvider};
+use serde_json::Value;
+use tokio_util::sync::CancellationToken;
+use wtransport::{tls::{Sha256Digest, Sha256DigestFmt}, ClientConfig, Connection, Endpoint, RecvStream, SendStream};
+
+use crate::{config::{self, AlertsConfig, Config, EventHistoryConfig, MetricsHistoryConfig, TlsConfig}, events::system::Role, services::hosts, webtransport};
+
+use fake::FakeDocker;
+
+mod agent;
+mod bulk;
+mod confirm;
+mod dispatch;
+mod docker_protocol;
+mod fake;
+mod hello;
+mod inventory;
+mod roles;
+mod schedule;
+mod schema;
+
+/// Host every request without `host` targets
+pub const HOST: &str = "local";
+
+/// Host whose daemon is down
+pub const UNREACHABLE_HOST: &str = "down";
+
+/// Token of the `ci` client, an operator
+pub const CLIENT_TOKEN: &str = "ci-token";
+
+/// Token of the `ops` client, an admin
+pub const ADMIN_TOKEN: &str = "admin-token";
+
+/// Token of the `dashboard` client, a viewer
+pub const VIEWER_TOKEN: &str = "viewer-token";
+
+/// Token agents register with
+pub const AGENT_TOKEN: &str = "agent-token";
+
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct TestServer {
+    url: String,
+    hash_file: std::path::PathBuf,
+    pub docker: Arc<FakeDocker>
+}
+
+static SERVER: OnceLock<TestServer> = OnceLock::new();
+
+/// Starts the shared server on its own runtime the first time, so that it outlives each test's runtime
+pub async fn server() -> &'static TestServer {
+    let server = SERVER.get_or_init(|| {
+        let port = std::net::UdpSocket::bind("127.0.0.1:0").and_then(|socket| socket.local_addr()).expect("Failed to find a free port").port();
+        let data_dir = std::env::temp_dir().join("admin-api-test");
+        let hash_file = data_dir.join("certificate.hash");
+        let _ = std::fs::remove_dir_all(&data_dir);
+        std::fs::create_dir_all(&data_dir).expect("Failed to create the data directory");
+        
+        config::init(Config {
+            port,
+            admin_address: None,
+            data_dir: data_dir.to_string_lossy().to_string(),
+            alerts: AlertsConfig { rules: Vec::new(), ..Default::default() },
+            metrics_history: MetricsHistoryConfig { interval: 0, ..Default::default() },
+            event_history: EventHistoryConfig::default(),
+            agent_token: Some(AGENT_TOKEN.to_string()),
+            clients: vec![
+                config::ClientConfig { name: "ops".to_string(), token: ADMIN_TOKEN.to_string(), role: Role::Admin },
+                config::ClientConfig { name: "ci".to_string(), token: CLIENT_TOKEN.to_string(), role: Role::Operator },
+                config::ClientConfig { name: "dashboard".to_string(), token: VIEWER_TOKEN.to_string(), role: Role::Viewer }
+            ],
+            tls: TlsConfig {
+                self_signed: true,
+                hash_file: Some(hash_file.to_string_lossy().to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let _ = CryptoProvider::install_default(default_provider());
+        
+        let fake = Arc::new(FakeDocker::new());
+        hosts::set_backend(HOST, fake.clone());
+        hosts::set_backend(UNREACHABLE_HOST, Arc::new(FakeDocker::unreachable()));
+        
+        std::thread::spawn(|| {
+            let runtime = tokio::runtime::Runtime::new().expect("Failed to start the server runtime");
+            if let Err(e) = runtime.block_on(webtransport::start_webtransport(CancellationToken::new())) {
+                panic!("Test server failed: {:?}", e);
+            }
+        });
+        
+        TestServer {
+            url: format!("https://localhost:{}", port),
+            hash_file,
+            docker: fake
+        }
+    });
+    
+    let ready = async {
+        while !server.hash_file.exists() || !server.docker.listening() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, ready).await.expect("Test server did not start");
+    server
+}
+
+/// Client speaking the legacy JSON format on a single bidirectional stream
+pub struct TestClient {
+    _endpoint: Endpoint<wtransport::endpoint::endpoint_side::Client>,
+    _connection: Connection,
+    send_stream: SendStream,
+    recv_stream: RecvStream,
+    buffer: Vec<u8>
+}
+
+impl TestClient {
+    pub async fn connect() -> Self {
+        let server = server().await;
+        let hash = std::fs::read_to_string(&server.hash_file).expect("Failed to read the certificate hash");
+        let hash = Sha256Digest::from_str_fmt(hash.trim(), Sha256DigestFmt::DottedHex).expect("Invalid certificate hash");
+        
+        let config = ClientConfig::builder().with_bind_default().with_server_certificate_hashes([hash]).build();
+        let endpoint = Endpoint::client(config).expect("Failed to create the client endpoint");
+        let connection = endpoint.connect(&server.url).await.expect("Failed to connect");
+        let (send_stream, recv_stream) = connection.open_bi().await.expect("Failed to open a stream").await.expect("Failed to open a stream");
+        
+        Self {
+            _endpoint: endpoint,
+            _connection: connection,
+            send_stream,
+            recv_stream,
+            buffer: Vec::new()
+        }
+    }
+    
+    pub async fn send(&mut self, event: Value) {
+        self.send_stream.write_all(event.to_string().as_bytes()).await.expect("Failed to send event");
+    }
+    
+    /// Next event, `None` when nothing arrives in time
+    pub async fn recv(&mut self, timeout: Duration) -> Option<Value> {
+        tokio::time::timeout(timeout, async {
+            loop {
+                let mut events = serde_json::Deserializer::from_slice(&self.buffer).into_iter::<Value>();
+                match events.next() {
+                    Some(Ok(event)) => {
+                        let offset = events.byte_offset();
+                        self.buffer.drain(..offset);
+                        return Some(event);
+                    },
+                    Some(Err(error)) if !error.is_eof() => panic!("Invalid event received: {:?}", error),
+                    _ => {}
+                }
+                
+                let mut chunk = [0; 4096];
+                match self.recv_stream.read(&mut chunk).await {
+                    Ok(Some(n)) if n > 0 => self.buffer.extend_from_slice(&chunk[..n]),
+                    _ => return None
+                }
+            }
+        }).await.ok().flatten()
+    }
+    
+    /// Events received until one of `type` matching the predicate, which is returned last
+    pub async fn until(&mut self, typ: &str, predicate: impl Fn(&Value) -> bool) -> Vec<Value> {
+        let mut events = Vec::new();
+        loop {
+            let event = self.recv(TIMEOUT).await.unwrap_or_else(|| panic!("Timed out waiting for {}, received {:?}", typ, events));
+            let found = event["type"] == typ && predicate(&event);
+            events.push(event);
+            if found {
+                return events;
+            }
+        }
+    }
+    
+    /// First event of `type` matching the predicate, the others are skipped
+    pub async fn expect(&mut self, typ: &str, predicate: impl Fn(&Value) -> bool) -> Value {
+        self.until(typ, predicate).await.pop().unwrap_or_default()
+    }
+}
@@ -0,0 +1,68 @@
+use serde_json::{json, Value};
+
+use super::{server, TestClient, CLIENT_TOKEN, VIEWER_TOKEN};
+
+async fn client(token: &str) -> TestClient {
+    let mut client = TestClient::connect().await;
+    client.send(json!({ "type": "Hello", "data": { "version": 2, "token": token } })).await;
+    client.expect("Hello", |_| true).await;
+    client
+}
+
+fn forbidden(typ: &'static str) -> impl Fn(&Value) -> bool {
+    move |event| event["data"]["event"] == typ && event["data"]["code"] == "forbidden"
+}
+
+#[tokio::test]
+async fn viewers_cannot_act_on_containers_or_schedules() {
+    let docker = &server().await.docker;
+    docker.add_container("d0010000000000000000", "viewed", "running", &[("suite", "roles")]);
+    let mut viewer = client(VIEWER_TOKEN).await;
+    
+    viewer.send(json!({ "type": "DockerContainerStop", "data": { "containerId": "viewed" } })).await;
+    let refused = viewer.expect("SystemError", forbidden("DockerContainerStop")).await;
+    assert_eq!(refused["data"]["error"], "DockerContainerStop requires the operator role");
+    
+    viewer.send(json!({ "type": "DockerContainerBulk", "data": { "id": "viewer", "action": "stop", "selector": { "labels": { "suite": "roles" } } } })).await;
+    viewer.expect("SystemError", forbidden("DockerContainerBulk")).await;
+    
+    let schedule = json!({ "id": "viewer", "name": "Nightly", "cron": "0 3 * * *", "action": "restart", "target": { "labels": { "suite": "roles" } } });
+    viewer.send(json!({ "type": "ScheduleCreate", "data": { "schedule": schedule } })).await;
+    viewer.expect("SystemError", forbidden("ScheduleCreate")).await;
+    viewer.send(json!({ "type": "ScheduleDelete", "data": { "id": "viewer" } })).await;
+    viewer.expect("SystemError", forbidden("ScheduleDelete")).await;
+    
+    viewer.send(json!({ "type": "DockerContainerList", "data": { "filters": { "label": ["suite=roles"] } } })).await;
+    let list = viewer.expect("DockerContainerList", |_| true).await;
+    assert_eq!(list["data"]["total"], 1);
+    assert_eq!(docker.state("viewed").as_deref(), Some("running"));
+}
+
+#[tokio::test]
+async fn operators_act_on_containers() {
+    let docker = &server().await.docker;
+    docker.add_container("d0020000000000000000", "operated", "exited", &[]);
+    let mut operator = client(CLIENT_TOKEN).await;
+    
+    operator.send(json!({ "type": "DockerContainerStart", "data": { "containerId": "operated" } })).await;
+    operator.expect("DockerContainerStart", |event| event["data"]["containerId"] == "d0020000000000000000").await;
+    assert_eq!(docker.state("operated").as_deref(), Some("running"));
+}
+
+#[tokio::test]
+async fn anonymous_streams_keep_the_operator_role_while_deprecated() {
+    let docker = &server().await.docker;
+    docker.add_container("d0030000000000000000", "anonymous-worker", "exited", &[]);
+    let mut anonymous = TestClient::connect().await;
+    
+    anonymous.send(json!({ "type": "Hello", "data": { "version": 2 } })).await;
+    let hello = anonymous.expect("Hello", |_| true).await;
+    assert_eq!(hello["data"]["identity"]["role"], "operator");
+    let deprecated = hello["data"]["deprecations"].as_array().into_iter().flatten()
+        .any(|deprecation| deprecation["shape"].as_str().is_some_and(|shape| shape.contains("anonymousRole")));
+    assert!(deprecated, "tokenless actions should be announced as deprecated");
+    
+    anonymous.send(json!({ "type": "DockerContainerStart", "data": { "containerId": "anonymous-worker" } })).await;
+    anonymous.expect("DockerContainerStart", |event| event["data"]["containerId"] == "d0030000000000000000").await;
+    assert_eq!(docker.state("anonymous-worker").as_deref(), Some("running"));
+}
@@ -0,0 +1,63 @@
+use serde_json::{json, Value};
+
+use super::{server, TestClient, ADMIN_TOKEN, CLIENT_TOKEN};
+
+async fn client(token: &str) -> TestClient {
+    let mut client = TestClient::connect().await;
+    client.send(json!({ "type": "Hello", "data": { "version": 2, "token": token } })).await;
+    client.expect("Hello", |_| true).await;
+    client
+}
+
+fn schedule(suite: &str, cron: &str) -> Value {
+    json!({ "name": suite, "cron": cron, "action": "stop", "target": { "labels": { "suite": suite } } })
+}
+
+#[tokio::test]
+async fn schedules_on_protected_containers_require_an_admin() {
+    let docker = &server().await.docker;
+    docker.add_container("f0010000000000000000", "scheduled-vault", "running", &[("suite", "schedule-protected"), ("admin-api.protected", "true")]);
+    let mut operator = client(CLIENT_TOKEN).await;
+    let mut admin = client(ADMIN_TOKEN).await;
+    
+    operator.send(json!({ "type": "ScheduleCreate", "data": { "schedule": schedule("schedule-protected", "0 3 * * *") } })).await;
+    let refused = operator.expect("ScheduleCreate", |_| true).await;
+    assert_eq!(refused["data"]["error"], "Container scheduled-vault is protected, an authenticated admin is required");
+    assert!(refused["data"]["schedule"].is_null());
+    
+    admin.send(json!({ "type": "ScheduleCreate", "data": { "schedule": schedule("schedule-protected", "0 3 * * *") } })).await;
+    let created = admin.expect("ScheduleCreate", |_| true).await;
+    assert_eq!(created["data"]["schedule"]["owner"], "ops");
+    
+    let mut update = created["data"]["schedule"].clone();
+    update["owner"] = json!("ops");
+    operator.send(json!({ "type": "ScheduleUpdate", "data": { "schedule": update } })).await;
+    let refused = operator.expect("ScheduleUpdate", |_| true).await;
+    assert!(refused["data"]["error"].is_string());
+    
+    admin.send(json!({ "type": "ScheduleDelete", "data": { "id": created["data"]["schedule"]["id"] } })).await;
+    admin.expect("ScheduleDelete", |event| event["data"]["deleted"] == true).await;
+}
+
+#[tokio::test]
+async fn scheduled_runs_skip_protected_containers() {
+    let docker = &server().await.docker;
+    let mut operator = client(CLIENT_TOKEN).await;
+    
+    operator.send(json!({ "type": "ScheduleCreate", "data": { "schedule": schedule("schedule-run", "* * * * * *") } })).await;
+    let created = operator.expect("ScheduleCreate", |_| true).await;
+    let id = created["data"]["schedule"]["id"].clone();
+    assert_eq!(created["data"]["schedule"]["owner"], "ci");
+    
+    // Labelled after the schedule was accepted, the check at run time still applies
+    docker.add_container("f0020000000000000000", "scheduled-later", "running", &[("suite", "schedule-run"), ("admin-api.protected", "true")]);
+    let run = operator.expect("ScheduleRun", |event| event["data"]["scheduleId"] == id && event["data"]["results"].as_array().is_some_and(|results| !results.is_empty())).await;
+    let result = &run["data"]["results"][0];
+    assert_eq!(result["containerId"], "f0020000000000000000");
+    assert_eq!(result["success"], false);
+    assert_eq!(result["error"], "Container scheduled-later is protected, an authenticated admin is required");
+    assert_eq!(docker.state("scheduled-later").as_deref(), Some("running"));
+    
+    operator.send(json!({ "type": "ScheduleDelete", "data": { "id": id } })).await;
+    operator.expect("ScheduleDelete", |event| event["data"]["deleted"] == true).await;
+}
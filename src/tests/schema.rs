@@ -0,0 +1,77 @@
+use serde_json::{json, Value};
+
+use crate::schema;
+
+use super::{server, TestClient};
+
+fn assert_valid(validator: &jsonschema::Validator, event: &Value) {
+    let errors: Vec<String> = validator.iter_errors(event).map(|error| format!("{} at {}", error, error.instance_path)).collect();
+    assert!(errors.is_empty(), "{} does not match the schema: {:?}", event["type"], errors);
+}
+
+#[test]
+fn every_event_type_is_described() {
+    let schema = schema::protocol().to_string();
+    
+    for typ in ["SystemStatus", "SystemSubscribe", "DockerContainerList", "DockerEventHistory", "AgentRequest", "AlertChanged", "ScheduleRun", "MetricsQuery"] {
+        assert!(schema.contains(&format!("\"const\":\"{}\"", typ)), "{} should be described", typ);
+    }
+    assert!(!schema.contains("could not be traced"));
+}
+
+#[test]
+fn docker_models_are_traced() {
+    let schema = schema::protocol();
+    let definitions = &schema["$defs"];
+    
+    assert_eq!(definitions["ContainerSummary"]["properties"]["Id"], json!({ "anyOf": [{ "type": "string" }, { "type": "null" }] }));
+    assert_eq!(definitions["Port"]["required"], json!(["PrivatePort"]));
+    assert_eq!(definitions["ContainerStateStatusEnum"]["enum"].as_array().map(Vec::len), Some(8));
+    // `Id` is an alias of `id`, only the serialized name is a property
+    assert!(definitions["Stats"]["properties"]["id"].is_object());
+    assert!(definitions["Stats"]["properties"].get("Id").is_none());
+    // Untagged in bollard, typed as anything
+    assert_eq!(definitions["MemoryStats"]["properties"]["stats"], json!({}));
+}
+
+#[test]
+fn typescript_definitions_cover_the_schema() {
+    let dir = std::env::temp_dir().join("admin-api-schema");
+    schema::write(&dir).expect("Failed to write the schema");
+    let types = std::fs::read_to_string(dir.join(schema::TYPES_FILE)).expect("Failed to read the types");
+    let client = std::fs::read_to_string(dir.join(schema::CLIENT_FILE)).expect("Failed to read the client");
+    
+    assert!(types.contains("export type Event = SystemEvent | DockerEvent | AgentEvent | AlertEvent | ScheduleEvent | MetricsEvent;"));
+    for name in schema::protocol()["$defs"].as_object().expect("definitions should be listed").keys() {
+        assert!(
+            types.contains(&format!("export interface {} {{", name)) || types.contains(&format!("export type {} = ", name)),
+            "{} should be exported", name
+        );
+    }
+    assert!(client.contains("from \"./protocol\""));
+}
+
+#[tokio::test]
+async fn server_answers_match_the_schema() {
+    let validator = jsonschema::validator_for(&schema::protocol()).expect("The schema should be valid");
+    assert!(!validator.is_valid(&json!({ "type": "DockerStatus", "data": { "status": "up" } })));
+    assert!(!validator.is_valid(&json!({ "type": "DockerContainerList", "data": { "containers": [{ "Id": "66660000000000000000" }] } })));
+    server().await.docker.add_container("66660000000000000000", "schema-web", "running", &[("tier", "web")]);
+    let mut client = TestClient::connect().await;
+    
+    let requests = [
+        json!({ "type": "DockerStatus", "data": {} }),
+        json!({ "type": "DockerContainerList", "data": {} }),
+        json!({ "type": "DockerContainerInspect", "data": { "containerId": "schema-web" } }),
+        json!({ "type": "DockerContainerStats", "data": { "containerId": "66660000000000000000" } }),
+        json!({ "type": "DockerContainerStart", "data": { "containerId": "66660000000000000000" } })
+    ];
+    for request in requests {
+        assert_valid(&validator, &request);
+        client.send(request.clone()).await;
+        let typ = request["type"].as_str().unwrap_or_default();
+        for event in client.until(typ, |_| true).await {
+            assert_valid(&validator, &event);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, Mutex};
+use tracing::Instrument;
+
+use crate::{events::{agent::{AgentEvent, AgentRegisterData}, docker::DockerEvent, Event}, serializers::SendEvent, services::{agents::{self, AgentHandle}, bus::EventBus, docker, event_history, hosts}};
+
+use super::session::Session;
+
+/// Handles the messages of an agent stream. `registration` holds the agent once it registered,
+/// and is unregistered by the caller when the stream closes.
+pub async fn handle_message(session: &Arc<Mutex<Session>>, registration: &mut Option<Arc<AgentHandle>>, event: AgentEvent) {
+    match event {
+        AgentEvent::AgentRegister { data } => {
+            // An agent named after a configured host would take over the calls aimed at it
+            let refusal = if registration.is_some() {
+                Some("stream already registered")
+            } else if !agents::authorize(data.token.as_deref()) {
+                Some("invalid token")
+            } else if hosts::configured_names().contains(&data.host_id) {
+                Some("host ID of a configured host")
+            } else {
+                None
+            };
+            let accepted = refusal.is_none();
+            
+            if accepted {
+                tracing::info!(host = data.host_id, "Agent registered");
+                let (requests, mut rx) = mpsc::unbounded_channel::<Event>();
+                *registration = Some(agents::register(data.host_id.clone(), requests));
+                
+                let session_clone = session.clone();
+                tokio::spawn(async move {
+                    while let Some(request) = rx.recv().await {
+                        if let Err(e) = session_clone.lock().await.write_event(&request).await {
+                            tracing::error!("Failed to send agent request: {:?}", e);
+                            break;
+                        }
+                    }
+                }.in_current_span());
+            } else {
+                tracing::warn!(host = data.host_id, reason = refusal, "Agent registration refused");
+            }
+            
+            let mut session = session.lock().await;
+            if accepted {
+                session.set_agent();
+            }
+            if let Err(e) = session.write_event(&Event::Agent(AgentEvent::AgentRegister {
+                data: AgentRegisterData {
+                    host_id: data.host_id,
+                    token: None,
+                    accepted: Some(accepted)
+                }
+            })).await {
+                tracing::error!("Failed to answer agent registration: {:?}", e);
+            }
+        },
+        AgentEvent::AgentResponse { data } => match registration {
+            Some(agent) => agent.complete(data),
+            None => tracing::warn!(request_id = data.request_id, "Ignoring agent response on an unregistered stream")
+        },
+        AgentEvent::AgentRequest { .. } => {
+            tracing::warn!("Ignoring agent request sent to the hub");
+        }
+    }
+}
+
+/// Publishes an event forwarded by an agent as an event of its registered host, whatever host the agent
+/// tagged it with. Raw events go through the alerts and the history like those of the configured hosts.
+pub async fn forward_event(bus: &mut EventBus, agent: &AgentHandle, event: DockerEvent) {
+    let host = agent.host_id();
+    match event {
+        DockerEvent::DockerEventMessage { data } => docker::publish_docker_event(bus, host, &event_history::to_message(&data)).await,
+        DockerEvent::DockerStatus { mut data } => {
+            data.host = Some(host.to_string());
+            bus.send_event(Event::Docker(DockerEvent::DockerStatus { data })).await;
+        },
+        event => tracing::debug!(host, event = <&'static str>::from(&event), "Dropping derived event sent by an agent")
+    }
+}
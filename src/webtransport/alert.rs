@@ -0,0 +1,24 @@
+use crate::{events::{alert::{AlertEvent, AlertListData}, Event}, serializers::SendEvent, services::alerts};
+
+use super::{dispatch::Dispatcher, session::Session};
+
+pub fn register(dispatcher: &mut Dispatcher) {
+    dispatcher.register::<AlertEvent>(|session, event| Box::pin(handle_message(session, event)));
+}
+
+async fn handle_message(session: &mut Session, event: &AlertEvent) {
+    match event {
+      AlertEvent::AlertList { data } => {
+        let alerts = alerts::list(data.include_resolved.unwrap_or(false)).await;
+        session.send_event(Event::Alert(AlertEvent::AlertList {
+            data: AlertListData {
+                alerts: Some(alerts),
+                include_resolved: data.include_resolved
+            }
+        })).await;
+      },
+      AlertEvent::AlertChanged { .. } => {
+        tracing::warn!("AlertChanged is only sent by the server");
+      }
+    }
+}
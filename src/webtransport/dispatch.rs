@@ -0,0 +1,48 @@
+use std::{collections::HashMap, sync::Arc};
+
+use futures::future::BoxFuture;
+
+use crate::events::{registry::Family, Event};
+
+use super::session::Session;
+
+type Handler = Arc<dyn for<'a> Fn(&'a mut Session, &'a Event) -> BoxFuture<'a, ()> + Send + Sync>;
+
+/// Handlers of client events, keyed on `type`. Each handler module registers its own family.
+#[derive(Default)]
+pub struct Dispatcher {
+    handlers: HashMap<&'static str, Handler>
+}
+
+impl Dispatcher {
+    /// Routes every type of the family `F` to `handler`
+    pub fn register<F: Family>(&mut self, handler: for<'a> fn(&'a mut Session, &'a F) -> BoxFuture<'a, ()>) {
+        let handler: Handler = Arc::new(move |session: &mut Session, event: &Event| match F::of(event) {
+            Some(event) => handler(session, event),
+            None => Box::pin(async {})
+        });
+        for typ in F::VARIANTS {
+            if self.handlers.insert(typ, handler.clone()).is_some() {
+                panic!("Event type `{}` has two handlers", typ);
+            }
+        }
+    }
+    
+    /// Every handled type, sorted
+    pub fn types(&self) -> Vec<&'static str> {
+        let mut types: Vec<&'static str> = self.handlers.keys().copied().collect();
+        types.sort_unstable();
+        types
+    }
+    
+    /// Runs the handler of the event's type, `false` if there is none
+    pub async fn dispatch(&self, session: &mut Session, event: &Event) -> bool {
+        match self.handlers.get(event.name()) {
+            Some(handler) => {
+                handler(session, event).await;
+                true
+            },
+            None => false
+        }
+    }
+}
@@ -1,97 +1,287 @@
-use crate::{events::{docker::{DockerContainerInspectData, DockerContainerListData, DockerContainerRestartData, DockerEvent, DockerStatusData}, Event}, serializers::SendEvent, services::docker};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub async fn handle_message(send_stream: &mut wtransport::SendStream, event: &DockerEvent) {
+use futures::StreamExt;
+
+use crate::{events::{docker::{BulkAction, BulkProgressData, BulkResult, DockerContainerBulkData, DockerContainerInspectData, DockerContainerListData, DockerContainerRestartData, DockerContainerStartData, DockerContainerStopData, DockerEvent, DockerEventHistoryData, DockerStatusData}, system::{Channel, Topic}, Event}, serializers::{EventDTO, SendEvent}, services::{bulk, confirm, docker, event_history, hosts, inventory, telemetry}};
+
+use super::{dispatch::Dispatcher, session::Session};
+
+pub fn register(dispatcher: &mut Dispatcher) {
+    dispatcher.register::<DockerEvent>(|session, event| Box::pin(handle_message(session, event)));
+}
+
+async fn handle_message(session: &mut Session, event: &DockerEvent) {
     match event {
-        DockerEvent::DockerStatus { .. } => {
-            send_stream.send_event(Event::Docker(DockerEvent::DockerStatus {
-                data: DockerStatusData {
-                    status: Some(docker::ping().await)
-                }
-            })).await;
-            log::info!("{:?}", Event::Docker(DockerEvent::DockerContainerRestart {
-                data: DockerContainerRestartData {
-                    container_id: Some("container_id".to_string()),
-                }
-            }));
+        DockerEvent::DockerStatus { data } => {
+            for host in hosts::resolve(data.host.as_deref()) {
+                session.send_event(Event::Docker(DockerEvent::DockerStatus {
+                    data: DockerStatusData {
+                        status: Some(docker::ping(Some(&host)).await),
+                        host: Some(host)
+                    }
+                })).await;
+            }
         },
-        DockerEvent::DockerContainerList { .. } => {
-            let containers = match docker::get_containers().await {
+        DockerEvent::DockerContainerList { data } => {
+            let filters = data.filters.clone().unwrap_or_default();
+            let mut containers = match docker::get_host_containers(data.host.as_deref(), &filters).await {
                 Ok(containers) => containers,
                 Err(error) => {
-                    log::error!("Failed to get containers: {:?}", error);
-                    send_stream.send_event(Event::Docker(DockerEvent::DockerStatus {
+                    tracing::error!("Failed to get containers: {:?}", error);
+                    session.send_event(Event::Docker(DockerEvent::DockerStatus {
                         data: DockerStatusData {
-                            status: Some(docker::ping().await)
+                            status: Some(docker::ping(data.host.as_deref()).await),
+                            host: data.host.clone()
                         }
                     })).await;
                     Vec::new()
                 }
             };
             
-            send_stream.send_event(Event::Docker(DockerEvent::DockerContainerList {
+            // Without sort keys nor paging, containers keep the order of the daemon
+            let sort = data.sort.as_deref().unwrap_or_default();
+            if !sort.is_empty() || data.cursor.is_some() || data.limit.is_some() {
+                docker::sort_containers(&mut containers, sort);
+            }
+            let total = containers.len();
+            let start = data.cursor.unwrap_or(0).min(total);
+            let end = data.limit.map_or(total, |limit| start.saturating_add(limit.max(1)).min(total));
+            
+            session.send_event(Event::Docker(DockerEvent::DockerContainerList {
                 data: DockerContainerListData {
-                    containers: Some(containers)
+                    containers: Some(containers[start..end].iter().map(|container| container.to_json(data.projection, data.fields.as_deref())).collect()),
+                    host: data.host.clone(),
+                    projection: data.projection,
+                    fields: data.fields.clone(),
+                    filters: data.filters.clone(),
+                    sort: data.sort.clone(),
+                    limit: data.limit,
+                    cursor: data.cursor,
+                    next_cursor: (end < total).then_some(end),
+                    total: Some(total)
                 }
             })).await;
         },
         DockerEvent::DockerContainerInspect { data } => {
             match &data.container_id {
                 Some(container_id) => {
-                    let container = match docker::get_container(&container_id).await {
+                    let container = match docker::get_container(data.host.as_deref(), container_id).await {
                         Ok(container) => container,
                         Err(error) => {
-                            log::error!("Failed to inspect container: {:?}", error);
+                            tracing::error!("Failed to inspect container: {:?}", error);
                             return;
                         }
                     };
                     
-                    send_stream.send_event(Event::Docker(DockerEvent::DockerContainerInspect {
+                    session.send_event(Event::Docker(DockerEvent::DockerContainerInspect {
                         data: DockerContainerInspectData {
                             container_id: Some(container_id.clone()),
-                            container: Some(container)
+                            container: Some(container.to_json(data.projection, data.fields.as_deref())),
+                            host: data.host.clone(),
+                            projection: data.projection,
+                            fields: data.fields.clone()
                         }
                     })).await;
                 },
                 None => {
-                    log::error!("No container ID provided");
+                    tracing::error!("No container ID provided");
                 }
             }
         },
         DockerEvent::DockerContainerStart { data } => {
             match &data.container_id {
                 Some(container_id) => {
-                    if let Err(error) = docker::start_container(&container_id).await {
-                        log::error!("Failed to start container: {:?}", error);
+                    match confirm::check_container(session.identity(), session.id(), BulkAction::Start, data.host.as_deref(), container_id, data.dry_run.unwrap_or_default(), None).await {
+                        Ok(()) => if let Err(error) = docker::start_container(data.host.as_deref(), container_id).await {
+                            tracing::error!("Failed to start container: {:?}", error);
+                        },
+                        Err(held) => session.send_event(Event::Docker(DockerEvent::DockerContainerStart {
+                            data: DockerContainerStartData {
+                                container_id: Some(container_id.clone()),
+                                host: data.host.clone(),
+                                dry_run: data.dry_run,
+                                affected: held.affected,
+                                error: held.error
+                            }
+                        })).await
                     }
                 },
                 None => {
-                    log::error!("No container ID provided");
+                    tracing::error!("No container ID provided");
                 }
             }
         },
         DockerEvent::DockerContainerRestart { data } => {
             match &data.container_id {
                 Some(container_id) => {
-                    if let Err(error) = docker::restart_container(&container_id).await {
-                        log::error!("Failed to restart container: {:?}", error);
+                    match confirm::check_container(session.identity(), session.id(), BulkAction::Restart, data.host.as_deref(), container_id, data.dry_run.unwrap_or_default(), None).await {
+                        Ok(()) => if let Err(error) = docker::restart_container(data.host.as_deref(), container_id).await {
+                            tracing::error!("Failed to restart container: {:?}", error);
+                        },
+                        Err(held) => session.send_event(Event::Docker(DockerEvent::DockerContainerRestart {
+                            data: DockerContainerRestartData {
+                                container_id: Some(container_id.clone()),
+                                host: data.host.clone(),
+                                dry_run: data.dry_run,
+                                affected: held.affected,
+                                error: held.error
+                            }
+                        })).await
                     }
                 },
                 None => {
-                    log::error!("No container ID provided");
+                    tracing::error!("No container ID provided");
                 }
             }
         },
         DockerEvent::DockerContainerStop { data } => {
             match &data.container_id {
                 Some(container_id) => {
-                    if let Err(error) = docker::stop_container(&container_id).await {
-                        log::error!("Failed to stop container: {:?}", error);
+                    match confirm::check_container(session.identity(), session.id(), BulkAction::Stop, data.host.as_deref(), container_id, data.dry_run.unwrap_or_default(), data.confirm.as_deref()).await {
+                        Ok(()) => if let Err(error) = docker::stop_container(data.host.as_deref(), container_id).await {
+                            tracing::error!("Failed to stop container: {:?}", error);
+                        },
+                        Err(held) => session.send_event(Event::Docker(DockerEvent::DockerContainerStop {
+                            data: DockerContainerStopData {
+                                container_id: Some(container_id.clone()),
+                                host: data.host.clone(),
+                                dry_run: data.dry_run,
+                                confirm: held.confirm,
+                                expires_at: held.expires_at,
+                                affected: held.affected,
+                                error: held.error
+                            }
+                        })).await
                     }
                 },
                 None => {
-                    log::error!("No container ID provided");
+                    tracing::error!("No container ID provided");
+                }
+            }
+        },
+        DockerEvent::DockerContainerStats { data } => {
+            match &data.container_id {
+                Some(container_id) => {
+                    let stats = match docker::get_container_stats(data.host.as_deref(), container_id, false).await {
+                        Ok(stats) => stats,
+                        Err(error) => {
+                            tracing::error!("Failed to get container stats: {:?}", error);
+                            return;
+                        }
+                    };
+                    
+                    session.send_event(Event::Docker(DockerEvent::DockerContainerStats {
+                        data: telemetry::container_stats(&hosts::name_or_default(data.host.as_deref()), &stats)
+                    })).await;
+                },
+                None => {
+                    tracing::error!("No container ID provided");
                 }
             }
         },
+        DockerEvent::DockerEventMessage { .. } => {
+            tracing::warn!("DockerEventMessage is only sent by the server");
+        },
+        DockerEvent::DockerEventHistory { data } => {
+            let data = match event_history::query(data).await {
+                Ok(data) => data,
+                Err(error) => {
+                    tracing::error!("Failed to query Docker event history: {}", error);
+                    DockerEventHistoryData {
+                        error: Some(error),
+                        ..Default::default()
+                    }
+                }
+            };
+            session.send_event(Event::Docker(DockerEvent::DockerEventHistory { data })).await;
+        },
+        DockerEvent::DockerContainerSync { data } => {
+            // Subscribed before the state is read: a patch already in the answer may follow, the client skips it
+            session.subscribe(Topic::Containers, Channel::Stream);
+            session.send_event(Event::Docker(DockerEvent::DockerContainerSync { data: inventory::sync(data) })).await;
+        },
+        DockerEvent::DockerContainerPatch { .. } => {
+            tracing::warn!("DockerContainerPatch is only sent by the server");
+        },
+        DockerEvent::DockerContainerBulk { data } => {
+            bulk_action(session, data).await;
+        },
+        DockerEvent::DockerContainerBulkProgress { .. } => {
+            tracing::warn!("DockerContainerBulkProgress is only sent by the server");
+        }
     }
+}
+
+/// Runs a bulk action, streaming the progress, then answers with the summary
+async fn bulk_action(session: &mut Session, data: &DockerContainerBulkData) {
+    let id = bulk::id(data);
+    let started_at = now();
+    let action = data.action;
+    let mut summary = DockerContainerBulkData {
+        id: Some(id.clone()),
+        action,
+        host: data.host.clone(),
+        containers: data.containers.clone(),
+        selector: data.selector.clone(),
+        concurrency: data.concurrency,
+        order: data.order.clone(),
+        dry_run: data.dry_run,
+        confirm: None,
+        expires_at: None,
+        affected: None,
+        started_at: Some(started_at),
+        finished_at: None,
+        succeeded: None,
+        failed: None,
+        results: None,
+        error: None
+    };
+    
+    let targets = match bulk::targets(data).await {
+        Ok(targets) => targets,
+        Err(error) => {
+            tracing::error!(bulk = id, "Failed to resolve bulk targets: {}", error);
+            summary.error = Some(error);
+            summary.finished_at = Some(now());
+            session.send_event(Event::Docker(DockerEvent::DockerContainerBulk { data: summary })).await;
+            return;
+        }
+    };
+    
+    if let Err(held) = confirm::check(session.identity(), session.id(), action, &targets, data.dry_run.unwrap_or_default(), data.confirm.as_deref()).await {
+        summary.affected = held.affected;
+        summary.confirm = held.confirm;
+        summary.expires_at = held.expires_at;
+        summary.error = held.error;
+        session.send_event(Event::Docker(DockerEvent::DockerContainerBulk { data: summary })).await;
+        return;
+    }
+    
+    let mut results = Vec::with_capacity(targets.len());
+    let mut progress = bulk::run(action, &targets, data.concurrency);
+    while let Some((index, result)) = progress.next().await {
+        session.send_event(Event::Docker(DockerEvent::DockerContainerBulkProgress {
+            data: BulkProgressData {
+                id: id.clone(),
+                action,
+                done: results.len() + 1,
+                total: targets.len(),
+                result: result.clone()
+            }
+        })).await;
+        results.push((index, result));
+    }
+    results.sort_by_key(|(index, _)| *index);
+    
+    let results: Vec<BulkResult> = results.into_iter().map(|(_, result)| result).collect();
+    summary.succeeded = Some(results.iter().filter(|result| result.success).count());
+    summary.failed = Some(results.iter().filter(|result| !result.success).count());
+    summary.results = Some(results);
+    summary.finished_at = Some(now());
+    bulk::audit(&summary).await;
+    session.send_event(Event::Docker(DockerEvent::DockerContainerBulk { data: summary })).await;
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default()
 }
\ No newline at end of file
@@ -0,0 +1,12 @@
+//! The handler modules of client events, each registering its own family. Nothing else lives here:
+//! a family clients send gets its module and one line below.
+
+use super::{alert, dispatch::Dispatcher, docker, metrics, schedule, system};
+
+pub fn register(dispatcher: &mut Dispatcher) {
+    system::register(dispatcher);
+    docker::register(dispatcher);
+    alert::register(dispatcher);
+    schedule::register(dispatcher);
+    metrics::register(dispatcher);
+}
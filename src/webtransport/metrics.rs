@@ -0,0 +1,31 @@
+use crate::{events::{metrics::{MetricsEvent, MetricsQueryData}, Event}, serializers::SendEvent, services::history};
+
+use super::{dispatch::Dispatcher, session::Session};
+
+pub fn register(dispatcher: &mut Dispatcher) {
+    dispatcher.register::<MetricsEvent>(|session, event| Box::pin(handle_message(session, event)));
+}
+
+async fn handle_message(session: &mut Session, event: &MetricsEvent) {
+    match event {
+      MetricsEvent::MetricsQuery { data } => {
+        let data = match history::query(data).await {
+            Ok(data) => data,
+            Err(error) => {
+                tracing::error!("Failed to query metrics history: {}", error);
+                MetricsQueryData {
+                    host: data.host.clone(),
+                    container: data.container.clone(),
+                    metrics: data.metrics.clone(),
+                    from: data.from,
+                    to: data.to,
+                    step: data.step,
+                    series: None,
+                    error: Some(error)
+                }
+            }
+        };
+        session.send_event(Event::Metrics(MetricsEvent::MetricsQuery { data })).await;
+      }
+    }
+}
@@ -1,200 +1,354 @@
 use std::error::Error;
-use std::sync::Arc;
-use tokio::sync::{broadcast, Mutex};
-use wtransport::{endpoint::IncomingSession, Connection, Endpoint, Identity, ServerConfig};
-use crate::services;
-use crate::events::Event;
+use std::sync::{atomic::{AtomicU64, Ordering}, Arc, LazyLock};
+use std::time::Duration;
+use tokio::sync::{broadcast::error::RecvError, Mutex};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::Instrument;
+use wtransport::{endpoint::{endpoint_side::Server, IncomingSession}, Connection, Endpoint, VarInt};
+use crate::{config, logging};
+use crate::serializers::{format::{Format, FormatError, FrameReader}, SendEvent};
+use crate::services::{self, bus::EventBus};
+use crate::events::{registry::DecodeError, system::{ErrorCode, Role, ServerShutdownData, SystemErrorData, SystemEvent}, Event};
 
+pub mod agent;
+pub mod alert;
+pub mod schedule;
+pub mod metrics;
 pub mod system;
 pub mod docker;
+pub mod dispatch;
+mod handlers;
+pub mod session;
+pub mod tls;
 
-pub async fn start_webtransport() -> Result<(), Box<dyn Error + Send + Sync>> {
-    let identity = match Identity::load_pemfiles("localhost.pem", "localhost-key.pem").await {
-        Ok(identity) => identity,
+use dispatch::Dispatcher;
+use session::Session;
+
+/// Application error code used to close connections when the server shuts down
+pub const SHUTDOWN_ERROR_CODE: VarInt = VarInt::from_u32(0x1);
+
+const FLUSH_DELAY: Duration = Duration::from_millis(500);
+
+/// Identifies each handled request in the logs
+static REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Handlers of the events clients send, agent messages aside
+static DISPATCHER: LazyLock<Dispatcher> = LazyLock::new(|| {
+    let mut dispatcher = Dispatcher::default();
+    handlers::register(&mut dispatcher);
+    dispatcher
+});
+
+/// State shared by every connection
+#[derive(Clone)]
+pub struct ServerState {
+    pub bus: EventBus,
+    pub shutdown: CancellationToken,
+    /// In-flight client requests, awaited before closing connections
+    pub actions: TaskTracker
+}
+
+pub async fn start_webtransport(shutdown: CancellationToken) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (identity, certificate) = match tls::load_identity(&config::get().tls).await {
+        Ok(loaded) => loaded,
         Err(e) => {
-            log::error!("Failed to load identity: {:?}", e);
-            return Err(Box::new(e));
+            tracing::error!("Failed to load identity: {:?}", e);
+            return Err(e);
         }
     };
     
-    let config = ServerConfig::builder()
-        .with_bind_default(4433)
-        .with_identity(identity)
-        .build();
+    if let Err(e) = services::store::open(&config::get().data_dir) {
+        tracing::error!("Failed to open store: {:?}", e);
+        return Err(e);
+    }
     
-    let server = match Endpoint::server(config) {
-        Ok(server) => server,
+    let server = match Endpoint::server(tls::server_config(identity)) {
+        Ok(server) => Arc::new(server),
         Err(e) => {
-            log::error!("Failed to create server: {:?}", e);
+            tracing::error!("Failed to create server: {:?}", e);
             return Err(Box::new(e));
         }
     };
+    tls::publish(&config::get().tls, certificate).await;
     
-    let (tx, _rx) = broadcast::channel::<String>(100);
+    let state = ServerState {
+        bus: EventBus::new(),
+        shutdown,
+        actions: TaskTracker::new()
+    };
     
     //TODO handle errors
-    tokio::spawn(services::docker::listen_docker_events(tx.clone()));
+    services::hosts::open_tunnels(state.shutdown.clone());
+    services::docker::listen_all_docker_events(state.bus.clone());
+    tokio::spawn(services::telemetry::sample_telemetry(state.bus.clone()));
+    tokio::spawn(services::alerts::watch_alerts(state.bus.clone()));
+    tokio::spawn(services::notifiers::watch_notifications(state.bus.clone()));
+    tokio::spawn(services::history::record_history());
+    tokio::spawn(services::event_history::record_event_history(state.bus.clone()));
+    tokio::spawn(services::inventory::watch_containers(state.bus.clone()));
+    tokio::spawn(services::scheduler::run_scheduler(state.bus.clone(), state.shutdown.clone()));
+    tokio::spawn(tls::watch_identity(server.clone(), state.bus.clone(), state.shutdown.clone()));
     
     loop {
-        let incoming_session = server.accept().await;
-        log::info!("Incoming session from {:?}", incoming_session.remote_address());
+        let incoming_session = tokio::select! {
+            incoming_session = server.accept() => incoming_session,
+            _ = state.shutdown.cancelled() => break
+        };
+        tracing::info!("Incoming session from {:?}", incoming_session.remote_address());
         
-        let tx_clone = tx.clone();
+        let state_clone = state.clone();
         tokio::spawn(async move {
-            if let Err(e) = handle_connection(incoming_session, tx_clone).await {
-                log::error!("Failed to handle connection: {:?}", e);
+            if let Err(e) = handle_connection(incoming_session, state_clone).await {
+                tracing::error!("Failed to handle connection: {:?}", e);
             }
         });
     }
+    
+    drain(&server, state).await;
+    Ok(())
+}
+
+/// Warns clients, waits for in-flight requests up to the configured timeout, then closes every connection
+async fn drain(server: &Endpoint<Server>, mut state: ServerState) {
+    let config = config::get();
+    tracing::info!("Shutting down, {} open connections", server.open_connections());
+    
+    state.bus.send_event(Event::System(SystemEvent::ServerShutdown {
+        data: ServerShutdownData {
+            reason: Some("Server is restarting".to_string()),
+            reconnect_after: Some(config.reconnect_after)
+        }
+    })).await;
+    
+    state.actions.close();
+    if tokio::time::timeout(config.shutdown_timeout(), state.actions.wait()).await.is_err() {
+        tracing::warn!("{} requests still running after {:?}, closing anyway", state.actions.len(), config.shutdown_timeout());
+    }
+    
+    // Leaves the forwarders a moment to flush the shutdown notice before the connections go away
+    tokio::time::sleep(FLUSH_DELAY).await;
+    server.close(SHUTDOWN_ERROR_CODE, b"server shutdown");
+    server.wait_idle().await;
 }
 
-async fn handle_connection(incoming_session: IncomingSession, tx: broadcast::Sender<String>) -> Result<(), Box<dyn Error + Send + Sync>> {
+async fn handle_connection(incoming_session: IncomingSession, state: ServerState) -> Result<(), Box<dyn Error + Send + Sync>> {
     let request = match incoming_session.await {
         Ok(request) => request,
         Err(e) => {
-            log::error!("Failed to accept incoming session: {:?}", e);
+            tracing::error!("Failed to accept incoming session: {:?}", e);
             return Err(Box::new(e));
         }
     };
     
+    let span = tracing::info_span!(
+        "connection",
+        remote = %request.remote_address(),
+        origin = request.origin().unwrap_or_default(),
+        user_agent = request.user_agent().unwrap_or_default()
+    );
+    
     let connection = match request.accept().await {
         Ok(connection) => connection,
         Err(e) => {
-            log::error!("Failed to accept incoming request: {:?}", e);
+            tracing::error!(parent: &span, "Failed to accept incoming request: {:?}", e);
             return Err(Box::new(e));
         }
     };
     
-    // let datagram_handle = tokio::spawn(handle_datagram(connection.clone(), tx.clone()));
-    
-    let _ = tokio::spawn(handle_bidirectionnal(connection, tx));
+    tokio::spawn(handle_bidirectionnal(connection, state).instrument(span));
     
-    // let _ = tokio::join!(datagram_handle, bidirectional_handle);
     Ok(())
 }
 
-// ! Problems with datagrams, they have a maximum size of 65507 bytes
-
-// async fn handle_datagram(connection: Connection, tx: broadcast::Sender<String>) -> Result<(), Box<dyn Error + Send + Sync>> {
-//     log::info!("Accepted datagram connection from {:?}", connection.remote_address());
-//     let mut rx = tx.subscribe();
-    
-//     loop {
-//         let datagram = match connection.receive_datagram().await {
-//             Ok(datagram) => datagram,
-//             Err(e) => {
-//                 log::error!("Failed to receive datagram: {:?}", e);
-//                 return Err(Box::new(e));
-//             }
-//         };
-//         let received_message = String::from_utf8_lossy(&datagram);
-//         log::info!("Received datagram message: {:?}", received_message);
-        
-//         let event: Event = match serde_json::from_str(&received_message) {
-//             Ok(event) => event,
-//             Err(e) => {
-//                 log::error!("Failed to parse event: {:?}", e);
-//                 continue;
-//             }
-//         };
-        
-//         match &event {
-//             Event::Docker(docker_event) => {
-//                 match docker_event {
-//                     DockerEvent::DockerStatus => {
-//                         let event = "DockerStatus".to_string();
-//                         connection.send_datagram(event.as_bytes())?;
-//                     },
-//                     DockerEvent::DockerContainersRestart { data } => {
-//                         let containers = docker::get_containers().await?;
-//                         let mut buffer = Vec::new();
-//                         {
-//                             let mut compressor = CompressorWriter::new(&mut buffer, 4096, 11, 22);
-//                             compressor.write_all(containers.to_json().to_string().as_bytes())?;
-//                         } 
-//                         log::info!("Restarting container: {:?}:{:?} max size: {:?}", containers.to_json().to_string().as_bytes().len(), buffer.len(), connection.max_datagram_size());
-//                         if let Err(error) = connection.send_datagram(buffer) {
-//                             log::error!("Failed to send event: {:?}", error);
-//                         }
-//                     }
-//                 }
-//             },
-//             Event::System(system_event) => {
-//                 match system_event {
-//                     SystemEvent::SystemStatus => {
-//                         let event = "SystemStatus".to_string();
-//                         connection.send_datagram(event.as_bytes())?;
-//                     }
-//                 }
-//             },
-//         }
-//     }
-// }
-
-async fn handle_bidirectionnal(connection: Connection, tx: broadcast::Sender<String>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    log::info!("Accepted bidirectional connection from {:?}", connection.remote_address());
+async fn handle_bidirectionnal(connection: Connection, state: ServerState) -> Result<(), Box<dyn Error + Send + Sync>> {
+    tracing::info!("Accepted bidirectional connection");
+    services::metrics::SESSIONS_ACTIVE.inc();
     
-    while let Ok((send_stream, mut recv_stream)) = connection.accept_bi().await {
-        log::trace!("Accepted bidirectional stream");
+    loop {
+        let (send_stream, mut recv_stream) = tokio::select! {
+            stream = connection.accept_bi() => match stream {
+                Ok(stream) => stream,
+                Err(_) => break
+            },
+            _ = state.shutdown.cancelled() => break
+        };
+        tracing::trace!("Accepted bidirectional stream");
         
-        let mut rx = tx.subscribe();
+        let mut rx = state.bus.subscribe();
         
-        let send_stream = Arc::new(Mutex::new(send_stream));
-        let send_stream_clone = send_stream.clone();
+        let session = Arc::new(Mutex::new(Session::new(connection.clone(), send_stream)));
+        let session_clone = session.clone();
         
         tokio::spawn(async move {
-            while let Ok(event) = rx.recv().await {
-                let mut stream = send_stream_clone.lock().await;
-                let _ = stream.write_all(event.as_bytes()).await;
+            loop {
+                let event = match rx.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Bidirectional stream lagged, {} events dropped", skipped);
+                        services::metrics::BROADCAST_LAGGED.inc_by(skipped);
+                        continue;
+                    },
+                    Err(RecvError::Closed) => break
+                };
+                
+                let mut session = session_clone.lock().await;
+                if let Err(e) = session.forward_event(&event).await {
+                    tracing::error!("Failed to forward event: {:?}", e);
+                    break;
+                }
             }
-        });
+        }.in_current_span());
         
+        let mut state = state.clone();
         tokio::spawn(async move {
            let mut buffer = [0; 4096];
-           loop {
+           let mut reader = FrameReader::new(Format::default());
+           let mut registration = None;
+           'read: loop {
             match recv_stream.read(&mut buffer).await {
                 Ok(Some(0)) => {
-                    log::info!("Bidirectional connection closed");
+                    tracing::info!("Bidirectional connection closed");
                     break;
                 },
                 Ok(Some(n)) => {
-                    let received_message = String::from_utf8_lossy(&buffer[..n]);
-                    log::info!("Received bidirectional message: {:?}", received_message);
-                    let mut stream = send_stream.lock().await;
-                    handle_message(&mut *stream, received_message.to_string()).await;
+                    reader.push(&buffer[..n]);
+                    while let Some(result) = reader.next_event() {
+                        let event = match result {
+                            Ok(event) => event,
+                            Err(e) => {
+                                tracing::warn!("Failed to read event: {}", e);
+                                session.lock().await.send_event(Event::System(SystemEvent::SystemError { data: read_error(&e) })).await;
+                                continue;
+                            }
+                        };
+                        
+                        if let Event::Agent(agent_event) = event {
+                            agent::handle_message(&session, &mut registration, agent_event).await;
+                            continue;
+                        }
+                        
+                        // Events of a registered agent come from its Docker host, they are broadcast to the clients.
+                        // Anything else would let an agent speak for the server.
+                        if let Some(agent) = &registration {
+                            match event {
+                                Event::Docker(event) => agent::forward_event(&mut state.bus, agent, event).await,
+                                event => tracing::warn!(host = agent.host_id(), event = event.name(), "Dropping non-Docker event sent by an agent")
+                            }
+                            continue;
+                        }
+                        
+                        if state.shutdown.is_cancelled() {
+                            tracing::warn!(event = event.name(), "Ignoring request received during shutdown");
+                            session.lock().await.send_event(Event::System(SystemEvent::SystemError {
+                                data: SystemErrorData {
+                                    event: Some(event.name().to_string()),
+                                    code: Some(ErrorCode::ShuttingDown),
+                                    error: Some("The server is shutting down".to_string())
+                                }
+                            })).await;
+                            continue;
+                        }
+                        
+                        match event.changes_session() {
+                            true => {
+                                let mut session = session.lock().await;
+                                state.actions.track_future(handle_message(&mut session, event)).await;
+                                reader.set_format(session.format());
+                                if session.closed() {
+                                    tracing::info!("Bidirectional stream closed by the server");
+                                    break 'read;
+                                }
+                            },
+                            false => {
+                                let mut detached = session.lock().await.detach();
+                                state.actions.track_future(handle_message(&mut detached, event)).await;
+                                session.lock().await.set_started();
+                            }
+                        }
+                    }
                 },
                 Ok(None) => {
-                    log::info!("No bidirectional data received");
+                    tracing::info!("No bidirectional data received");
                     break;
                 },
                 Err(e) => {
-                    log::error!("Failed to read from stream: {:?}", e);
+                    tracing::error!("Failed to read from stream: {:?}", e);
                     break;
                 }
             }
-           } 
-        });
+           }
+           
+           if let Some(agent) = registration {
+               tracing::info!(host = agent.host_id(), "Agent disconnected");
+               services::agents::unregister(&agent);
+           }
+        }.in_current_span());
     }
     
+    services::metrics::SESSIONS_ACTIVE.dec();
     Ok(())
 }
 
-async fn handle_message(send_stream: &mut wtransport::SendStream, message: String) {
-    log::info!("Received message: {:?}", message);
-    let event: Event = match serde_json::from_str(&message) {
-        Ok(event) => event,
-        Err(e) => {
-            log::error!("Failed to parse event: {:?}", e);
-            return;
+async fn handle_message(session: &mut Session, event: Event) {
+    let span = tracing::info_span!(
+        "request",
+        request_id = REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+        event = event.name(),
+        container_id = event.container_id(),
+        identity = session.identity().name
+    );
+    
+    async {
+        tracing::info!("Received event");
+        tracing::trace!(payload = %logging::redact(&event), "Event payload");
+        services::metrics::EVENTS_RECEIVED.with_label_values(&[event.name()]).inc();
+        let _timer = services::metrics::HANDLER_DURATION.with_label_values(&[event.name()]).start_timer();
+        
+        let required = event.required_role();
+        if session.identity().role < required {
+            forbid(session, &event, required).await;
+        } else if !DISPATCHER.dispatch(session, &event).await {
+            tracing::warn!("{} is not handled on client streams", event.name());
+        }
+        session.set_started();
+    }.instrument(span).await
+}
+
+/// Answers a request the role of the client does not allow, and records it in the audit log
+async fn forbid(session: &mut Session, event: &Event, required: Role) {
+    let identity = session.identity().clone();
+    let error = format!("{} requires the {} role", event.name(), format!("{:?}", required).to_lowercase());
+    tracing::warn!(target: "audit", identity = identity.name, role = ?identity.role, "{}", error);
+    
+    let details = serde_json::json!({ "event": event.name(), "role": identity.role, "required": required });
+    if let Err(e) = services::store::audit::append("request.refused", Some(&identity.name), &details).await {
+        tracing::error!(identity = identity.name, "Failed to audit refused request: {:?}", e);
+    }
+    
+    session.send_event(Event::System(SystemEvent::SystemError {
+        data: SystemErrorData {
+            event: Some(event.name().to_string()),
+            code: Some(ErrorCode::Forbidden),
+            error: Some(error)
         }
+    })).await;
+}
+
+/// Tells the client why an event was rejected
+fn read_error(error: &FormatError) -> SystemErrorData {
+    let (event, code) = match error {
+        FormatError::Event(e) => (e.event_type().map(str::to_string), match e {
+            DecodeError::Malformed(_) => ErrorCode::Malformed,
+            DecodeError::UnknownType(_) => ErrorCode::UnknownType,
+            DecodeError::Invalid { .. } => ErrorCode::InvalidEvent
+        }),
+        FormatError::FrameTooLarge(_) => (None, ErrorCode::FrameTooLarge),
+        _ => (None, ErrorCode::Malformed)
     };
     
-    match &event {
-        Event::Docker(docker_event) => {
-            docker::handle_message(send_stream, docker_event).await;
-        },
-        Event::System(system_event) => {
-            system::handle_message(send_stream, system_event).await;
-        },
+    SystemErrorData {
+        event,
+        code: Some(code),
+        error: Some(error.to_string())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,56 @@
+use crate::{events::{schedule::{Schedule, ScheduleData, ScheduleDeleteData, ScheduleEvent, ScheduleListData}, Event}, serializers::SendEvent, services::scheduler};
+
+use super::{dispatch::Dispatcher, session::Session};
+
+pub fn register(dispatcher: &mut Dispatcher) {
+    dispatcher.register::<ScheduleEvent>(|session, event| Box::pin(handle_message(session, event)));
+}
+
+async fn handle_message(session: &mut Session, event: &ScheduleEvent) {
+    match event {
+      ScheduleEvent::ScheduleList { .. } => {
+        session.send_event(Event::Schedule(ScheduleEvent::ScheduleList {
+            data: ScheduleListData {
+                schedules: Some(scheduler::list())
+            }
+        })).await;
+      },
+      ScheduleEvent::ScheduleCreate { data } => {
+        let result = match &data.schedule {
+            Some(schedule) => scheduler::create(schedule.clone(), session.identity()).await,
+            None => Err("Missing schedule".to_string())
+        };
+        session.send_event(Event::Schedule(ScheduleEvent::ScheduleCreate { data: schedule_data(result) })).await;
+      },
+      ScheduleEvent::ScheduleUpdate { data } => {
+        let result = match &data.schedule {
+            Some(schedule) => scheduler::update(schedule.clone(), session.identity()).await,
+            None => Err("Missing schedule".to_string())
+        };
+        session.send_event(Event::Schedule(ScheduleEvent::ScheduleUpdate { data: schedule_data(result) })).await;
+      },
+      ScheduleEvent::ScheduleDelete { data } => {
+        let result = scheduler::delete(&data.id).await;
+        session.send_event(Event::Schedule(ScheduleEvent::ScheduleDelete {
+            data: ScheduleDeleteData {
+                id: data.id.clone(),
+                deleted: Some(result.is_ok()),
+                error: result.err()
+            }
+        })).await;
+      },
+      ScheduleEvent::ScheduleRun { .. } => {
+        tracing::warn!("ScheduleRun is only sent by the server");
+      }
+    }
+}
+
+fn schedule_data(result: Result<Schedule, String>) -> ScheduleData {
+    match result {
+        Ok(schedule) => ScheduleData { schedule: Some(schedule), error: None },
+        Err(error) => {
+            tracing::error!("Failed to save schedule: {}", error);
+            ScheduleData { schedule: None, error: Some(error) }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::HashMap, error::Error, sync::{atomic::{AtomicU64, Ordering}, Arc}};
+
+use tokio::sync::Mutex;
+use wtransport::{Connection, SendStream};
+
+use crate::{events::{system::{Channel, Identity, Topic}, Event}, serializers::{format::Format, SendEvent}, services::{auth, metrics, telemetry}};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Sending half of a bidirectional stream, along with the wire format negotiated by the client,
+/// its telemetry subscriptions and who it is authenticated as
+pub struct Session {
+    /// Unique for the process, confirmation tokens are only redeemed on the stream they were issued to
+    id: u64,
+    connection: Connection,
+    /// Shared with the detached copies, each frame is written whole
+    send_stream: Arc<Mutex<SendStream>>,
+    format: Format,
+    subscriptions: HashMap<Topic, Channel>,
+    identity: Identity,
+    /// Set once the first request was handled, `Hello` is only accepted before
+    started: bool,
+    /// Set once the stream was finished, the remaining requests are dropped
+    closed: bool,
+    /// Set once a registered agent owns the stream, broadcasts are no longer forwarded to it
+    agent: bool
+}
+
+impl Session {
+    pub fn new(connection: Connection, send_stream: SendStream) -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            connection,
+            send_stream: Arc::new(Mutex::new(send_stream)),
+            format: Format::default(),
+            subscriptions: HashMap::new(),
+            identity: auth::anonymous(),
+            started: false,
+            closed: false,
+            agent: false
+        }
+    }
+    
+    /// Copy answering on the same stream, for a request that does not change the session: it is handled
+    /// without holding the session, broadcasts keep being forwarded meanwhile
+    pub fn detach(&self) -> Self {
+        Self {
+            id: self.id,
+            connection: self.connection.clone(),
+            send_stream: self.send_stream.clone(),
+            format: self.format,
+            subscriptions: HashMap::new(),
+            identity: self.identity.clone(),
+            started: self.started,
+            closed: self.closed,
+            agent: self.agent
+        }
+    }
+    
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    
+    pub fn identity(&self) -> &Identity {
+        &self.identity
+    }
+    
+    pub fn set_identity(&mut self, identity: Identity) {
+        self.identity = identity;
+    }
+    
+    pub fn started(&self) -> bool {
+        self.started
+    }
+    
+    pub fn set_started(&mut self) {
+        self.started = true;
+    }
+    
+    pub fn closed(&self) -> bool {
+        self.closed
+    }
+    
+    pub fn max_datagram_size(&self) -> Option<usize> {
+        self.connection.max_datagram_size()
+    }
+    
+    /// Ends the sending half once everything written was delivered
+    pub async fn finish(&mut self) {
+        self.closed = true;
+        if let Err(e) = self.send_stream.lock().await.finish().await {
+            tracing::debug!("Failed to finish stream: {:?}", e);
+        }
+    }
+    
+    pub fn format(&self) -> Format {
+        self.format
+    }
+    
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
+    
+    pub fn set_agent(&mut self) {
+        self.agent = true;
+    }
+    
+    pub fn subscribe(&mut self, topic: Topic, channel: Channel) {
+        if self.subscriptions.insert(topic, channel).is_none() {
+            telemetry::add_subscriber(topic);
+        }
+    }
+    
+    pub fn unsubscribe(&mut self, topic: Topic) {
+        if self.subscriptions.remove(&topic).is_some() {
+            telemetry::remove_subscriber(topic);
+        }
+    }
+    
+    pub async fn write_event(&mut self, event: &Event) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let frame = self.format.frame(event)?;
+        self.send_stream.lock().await.write_all(&frame).await?;
+        metrics::BYTES_SENT.with_label_values(&["stream"]).inc_by(frame.len() as u64);
+        Ok(())
+    }
+    
+    /// Delivers a broadcast event, honouring the channel of the matching subscription.
+    /// Telemetry the client did not subscribe to is dropped.
+    pub async fn forward_event(&mut self, event: &Event) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.agent || self.closed {
+            return Ok(());
+        }
+        
+        let Some(topic) = event.topic() else {
+            return self.write_event(event).await;
+        };
+        
+        match self.subscriptions.get(&topic) {
+            Some(Channel::Datagram) => {
+                let message = self.format.encode(event)?;
+                match self.connection.max_datagram_size() {
+                    Some(max_size) if message.len() <= max_size => {
+                        let size = message.len() as u64;
+                        self.connection.send_datagram(message)?;
+                        metrics::BYTES_SENT.with_label_values(&["datagram"]).inc_by(size);
+                        Ok(())
+                    },
+                    max_size => {
+                        tracing::trace!("Datagram of {} bytes exceeds {:?}, falling back to stream", message.len(), max_size);
+                        self.write_event(event).await
+                    }
+                }
+            },
+            Some(Channel::Stream) => self.write_event(event).await,
+            None => Ok(())
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        for (topic, _) in self.subscriptions.drain() {
+            telemetry::remove_subscriber(topic);
+        }
+    }
+}
+
+impl SendEvent for Session {
+    async fn send_event(&mut self, event: Event) {
+        if let Err(error) = self.write_event(&event).await {
+            tracing::error!("Failed to send event: {:?}", error);
+        }
+    }
+}
@@ -1,9 +1,154 @@
-use crate::events::system::SystemEvent;
+use crate::{events::{system::{Deprecation, HelloData, HelloFeatures, HelloLimits, SystemEvent, SystemNegotiateData, SystemSubscribeData}, Event, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION}, serializers::{format::{Compression, Encoding, Format, MAX_FRAME_SIZE}, SendEvent}, services::{auth, event_history, history, telemetry}};
 
-pub async fn handle_message(send_stream: &mut wtransport::SendStream, event: &SystemEvent) {
+use super::{dispatch::Dispatcher, session::Session, tls, DISPATCHER};
+
+pub fn register(dispatcher: &mut Dispatcher) {
+    dispatcher.register::<SystemEvent>(|session, event| Box::pin(handle_message(session, event)));
+}
+
+async fn handle_message(session: &mut Session, event: &SystemEvent) {
     match event {
+      SystemEvent::Hello { data } => {
+        hello(session, data).await;
+      },
       SystemEvent::SystemStatus => {
-        log::info!("SystemStatus");
+        tracing::info!("SystemStatus");
+      },
+      SystemEvent::SystemNegotiate { data } => {
+        let format = negotiate(session.format(), data.encoding, data.compression, data.compression_threshold);
+        
+        // The answer still uses the previous format, the client switches once it is received
+        session.send_event(Event::System(SystemEvent::SystemNegotiate {
+            data: SystemNegotiateData {
+                encoding: Some(format.encoding),
+                compression: Some(format.compression),
+                compression_threshold: Some(format.compression_threshold)
+            }
+        })).await;
+        session.set_format(format);
+        tracing::info!("Negotiated wire format: {:?}", format);
+      },
+      SystemEvent::SystemSubscribe { data } => {
+        let channel = data.channel.unwrap_or_default();
+        session.subscribe(data.topic, channel);
+        tracing::info!("Subscribed to {:?} over {:?}", data.topic, channel);
+      },
+      SystemEvent::SystemUnsubscribe { data } => {
+        session.unsubscribe(data.topic);
+        tracing::info!("Unsubscribed from {:?}", data.topic);
+      },
+      SystemEvent::SystemLoad { .. } => {
+        match telemetry::read_host_load().await {
+            Some(load) => session.send_event(Event::System(SystemEvent::SystemLoad { data: load })).await,
+            None => tracing::error!("Host load is not available")
+        }
+      },
+      SystemEvent::SystemCertificate { .. } => {
+        session.send_event(Event::System(SystemEvent::SystemCertificate { data: tls::current_certificate() })).await;
+      },
+      SystemEvent::ServerShutdown { .. } => {
+        tracing::warn!("ServerShutdown is only sent by the server");
+      },
+      SystemEvent::SystemError { .. } => {
+        tracing::warn!("SystemError is only sent by the server");
       }
     }
-}
\ No newline at end of file
+}
+
+fn negotiate(current: Format, encoding: Option<Encoding>, compression: Option<Compression>, compression_threshold: Option<usize>) -> Format {
+    Format {
+        encoding: encoding.unwrap_or(current.encoding),
+        compression: compression.unwrap_or(current.compression),
+        compression_threshold: compression_threshold.unwrap_or(current.compression_threshold)
+    }
+}
+
+/// Shapes of the protocol before the handshake, still accepted
+fn deprecations() -> Vec<Deprecation> {
+    let deprecation = |shape: &str, replacement: &str| Deprecation {
+        shape: shape.to_string(),
+        replacement: replacement.to_string(),
+        removed_in: PROTOCOL_VERSION + 1
+    };
+    
+    vec![
+        deprecation("Streams without `Hello`", "`Hello` as the first event"),
+        deprecation("`SystemNegotiate`", "`features` of `Hello`"),
+        deprecation("`ID` in place of `containerId`", "`containerId`"),
+        deprecation("Container actions without a token (`anonymousRole` defaulting to `operator`)", "A `clients` token with the `operator` role")
+    ]
+}
+
+/// Rejections answer the versions the server accepts, then close the stream
+async fn reject(session: &mut Session, data: &HelloData, error: String) {
+    tracing::warn!(version = data.version, software = data.software, "Hello rejected: {}", error);
+    session.send_event(Event::System(SystemEvent::Hello {
+        data: HelloData {
+            version: Some(PROTOCOL_VERSION),
+            min_version: Some(MIN_PROTOCOL_VERSION),
+            error: Some(error),
+            ..Default::default()
+        }
+    })).await;
+    session.finish().await;
+}
+
+async fn hello(session: &mut Session, data: &HelloData) {
+    let version = data.version.unwrap_or(MIN_PROTOCOL_VERSION);
+    if session.started() {
+        return reject(session, data, "`Hello` must be the first event of the stream".to_string()).await;
+    }
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+        let error = format!("Unsupported protocol version {}, the server accepts {} to {}", version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION);
+        return reject(session, data, error).await;
+    }
+    let Some(identity) = auth::authenticate(data.token.as_deref()) else {
+        return reject(session, data, "Invalid token".to_string()).await;
+    };
+    
+    let requested = data.features.as_ref();
+    let format = negotiate(
+        session.format(),
+        requested.and_then(|features| features.encoding),
+        requested.and_then(|features| features.compression),
+        requested.and_then(|features| features.compression_threshold)
+    );
+    let subscriptions: Vec<SystemSubscribeData> = requested.and_then(|features| features.subscriptions.as_ref())
+        .into_iter()
+        .flatten()
+        .map(|subscription| SystemSubscribeData { topic: subscription.topic, channel: Some(subscription.channel.unwrap_or_default()) })
+        .collect();
+    for subscription in &subscriptions {
+        session.subscribe(subscription.topic, subscription.channel.unwrap_or_default());
+    }
+    
+    tracing::info!(identity = identity.name, role = ?identity.role, version, software = data.software, "Hello");
+    session.set_identity(identity.clone());
+    
+    // Like `SystemNegotiate`, the answer still uses the previous format
+    session.send_event(Event::System(SystemEvent::Hello {
+        data: HelloData {
+            version: Some(PROTOCOL_VERSION),
+            min_version: Some(MIN_PROTOCOL_VERSION),
+            software: Some(format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))),
+            token: None,
+            features: Some(HelloFeatures {
+                encoding: Some(format.encoding),
+                compression: Some(format.compression),
+                compression_threshold: Some(format.compression_threshold),
+                subscriptions: Some(subscriptions)
+            }),
+            events: Some(DISPATCHER.types().into_iter().map(str::to_string).collect()),
+            limits: Some(HelloLimits {
+                max_frame_size: MAX_FRAME_SIZE,
+                max_datagram_size: session.max_datagram_size(),
+                max_event_history: event_history::MAX_LIMIT,
+                max_metrics_points: history::MAX_POINTS
+            }),
+            identity: Some(identity),
+            deprecations: Some(deprecations()),
+            error: None
+        }
+    })).await;
+    session.set_format(format);
+}
@@ -0,0 +1,115 @@
+use std::{error::Error, sync::{Arc, RwLock}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use tokio_util::sync::CancellationToken;
+use wtransport::{endpoint::endpoint_side::Server, Endpoint, Identity, ServerConfig};
+
+use crate::{config::{self, TlsConfig}, events::{system::{SystemCertificateData, SystemEvent}, Event}, serializers::SendEvent, services::bus::EventBus};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Certificate currently served, shared with the `SystemCertificate` handler
+static CURRENT: RwLock<Option<SystemCertificateData>> = RwLock::new(None);
+
+pub fn current_certificate() -> SystemCertificateData {
+    CURRENT.read().map(|current| current.clone().unwrap_or_default()).unwrap_or_default()
+}
+
+pub fn server_config(identity: Identity) -> ServerConfig {
+    ServerConfig::builder()
+        .with_bind_default(config::get().port)
+        .with_identity(identity)
+        .build()
+}
+
+/// Loads the configured identity, generating a self-signed one when asked to. It is only published once
+/// the server serves it.
+pub async fn load_identity(tls: &TlsConfig) -> Result<(Identity, SystemCertificateData), Box<dyn Error + Send + Sync>> {
+    let (identity, expires_at) = if tls.self_signed {
+        let identity = Identity::self_signed_builder()
+            .subject_alt_names(&tls.subject_alt_names)
+            .from_now_utc()
+            .validity_days(tls.validity_days)
+            .build()?;
+        (identity, Some(now() + tls.validity_days as u64 * SECONDS_PER_DAY))
+    } else {
+        (Identity::load_pemfiles(&tls.certificate, &tls.key).await?, None)
+    };
+    
+    let certificate = SystemCertificateData {
+        hash: identity.certificate_chain().as_slice().first().map(|certificate| certificate.hash().to_string()),
+        expires_at
+    };
+    
+    Ok((identity, certificate))
+}
+
+/// Writes the hash file and answers `SystemCertificate` with the certificate the server now serves
+pub async fn publish(tls: &TlsConfig, certificate: SystemCertificateData) {
+    tracing::info!(hash = certificate.hash.as_deref(), expires_at = certificate.expires_at, "Serving certificate");
+    
+    if let (Some(hash_file), Some(hash)) = (&tls.hash_file, &certificate.hash)
+        && let Err(e) = tokio::fs::write(hash_file, hash).await {
+        tracing::error!("Failed to write certificate hash to {:?}: {:?}", hash_file, e);
+    }
+    
+    if let Ok(mut current) = CURRENT.write() {
+        *current = Some(certificate);
+    }
+}
+
+/// Rotates the self-signed certificate before it expires, or reloads the certificate files when they change
+pub async fn watch_identity(server: Arc<Endpoint<Server>>, mut bus: EventBus, shutdown: CancellationToken) {
+    let tls = &config::get().tls;
+    let mut last_modified = files_modified(tls).await;
+    
+    loop {
+        let delay = if tls.self_signed {
+            let lifetime = tls.validity_days as u64 * SECONDS_PER_DAY;
+            Duration::from_secs(lifetime.saturating_sub(tls.renew_before * 60 * 60).max(60))
+        } else {
+            Duration::from_secs(tls.reload_interval.max(1))
+        };
+        
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {},
+            _ = shutdown.cancelled() => return
+        }
+        
+        if !tls.self_signed {
+            let modified = files_modified(tls).await;
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+            tracing::info!("Certificate files changed, reloading");
+        }
+        
+        let (identity, certificate) = match load_identity(tls).await {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                tracing::error!("Failed to reload identity: {:?}", e);
+                continue;
+            }
+        };
+        
+        // Existing connections keep their session, only new handshakes use the new certificate. Until then
+        // the previous one is still the one clients must pin.
+        match server.reload_config(server_config(identity), false) {
+            Ok(_) => {
+                publish(tls, certificate).await;
+                bus.send_event(Event::System(SystemEvent::SystemCertificate { data: current_certificate() })).await;
+            },
+            Err(e) => tracing::error!("Failed to reload server configuration: {:?}", e)
+        }
+    }
+}
+
+async fn files_modified(tls: &TlsConfig) -> Option<(SystemTime, SystemTime)> {
+    let certificate = tokio::fs::metadata(&tls.certificate).await.and_then(|metadata| metadata.modified()).ok()?;
+    let key = tokio::fs::metadata(&tls.key).await.and_then(|metadata| metadata.modified()).ok()?;
+    Some((certificate, key))
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}